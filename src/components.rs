@@ -0,0 +1,310 @@
+//! Components - expand custom tags in markdown, such as `<Callout type="warn">text</Callout>`,
+//! using a handlebars template for each registered component.
+//!
+//! Component names begin with an upper-case letter. The component's template is rendered
+//! with the tag's attributes as variables, plus
+//!  - `content`: the inner content, converted from markdown to html
+//!  - `raw`: the inner content (markdown source)
+//!
+//! Template output is not html-escaped, so templates should use attribute values with care.
+//! Tags inside code spans and code blocks are not expanded.
+//! Components may be nested, including components with the same name.
+//!
+use crate::{
+    md_parser::{markdown_to_html, ParseOptions, ParseResult},
+    placeholder::{extract, FoundTag, Placeholders},
+    Error, Result,
+};
+use handlebars::Handlebars;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Name of placeholders inserted into markdown where a component was found,
+/// and replaced with the component's html after parsing
+const PLACEHOLDER_KIND: &str = "mdsite-component";
+
+/// What to do with a tag that looks like a component, but isn't registered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnknownComponent {
+    /// Leave the tag in the document as-is (default)
+    Literal,
+    /// Return Error::UnknownComponent
+    Error,
+}
+
+/// Registry of components
+pub struct Components<'reg> {
+    hb: Handlebars<'reg>,
+    unknown: UnknownComponent,
+}
+
+impl<'reg> Default for Components<'reg> {
+    fn default() -> Self {
+        let mut hb = Handlebars::new();
+        hb.register_escape_fn(handlebars::no_escape);
+        Self {
+            hb,
+            unknown: UnknownComponent::Literal,
+        }
+    }
+}
+
+/// Component tag found in markdown source
+#[derive(Debug, PartialEq)]
+struct ComponentTag<'md> {
+    /// byte range of the whole tag, including inner content and end tag
+    range: Range<usize>,
+    name: &'md str,
+    attributes: BTreeMap<String, String>,
+    inner: &'md str,
+}
+
+impl<'md> FoundTag for ComponentTag<'md> {
+    fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl<'reg> Components<'reg> {
+    /// Create empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set handling of unregistered components
+    pub fn set_unknown(&mut self, unknown: UnknownComponent) {
+        self.unknown = unknown;
+    }
+
+    /// Register component with its handlebars template.
+    /// Name must begin with an upper-case ascii letter, e.g., "Callout".
+    pub fn register(&mut self, name: &str, template: &str) -> Result<()> {
+        if !is_component_name(name) {
+            return Err(Error::InvalidComponentName(name.to_string()));
+        }
+        self.hb.register_template_string(name, template)?;
+        Ok(())
+    }
+
+    /// Returns true if the component is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.hb.has_template(name)
+    }
+
+    /// Expand components in the markdown, and convert it to html.
+    pub fn markdown_to_html(&self, markdown: &str, opt: &ParseOptions) -> Result<ParseResult> {
+        let mut placeholders = Placeholders::new(PLACEHOLDER_KIND);
+        let source = extract(
+            markdown,
+            find_tag,
+            |tag| {
+                if self.contains(tag.name) {
+                    self.expand(tag, opt).map(Some)
+                } else if self.unknown == UnknownComponent::Error {
+                    Err(Error::UnknownComponent(tag.name.to_string()))
+                } else {
+                    Ok(None)
+                }
+            },
+            &mut placeholders,
+        )?;
+        let mut html = markdown_to_html(&source, opt)?;
+        if !placeholders.is_empty() {
+            html.content = placeholders.replace(&html.content);
+            html.blocks.clear();
+        }
+        Ok(html)
+    }
+
+    /// Render component's template
+    fn expand(&self, tag: &ComponentTag, opt: &ParseOptions) -> Result<String> {
+        let mut data = serde_json::Map::new();
+        for (k, v) in tag.attributes.iter() {
+            data.insert(k.clone(), serde_json::Value::from(v.as_str()));
+        }
+        // inner content may contain other components
+        let content = self.markdown_to_html(tag.inner, opt)?.content;
+        data.insert("content".into(), serde_json::Value::from(content));
+        data.insert("raw".into(), serde_json::Value::from(tag.inner));
+        Ok(self.hb.render(tag.name, &data)?)
+    }
+}
+
+/// Component names begin with an ascii upper-case letter, followed by ascii alphanumerics
+fn is_component_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Find the next component tag starting at or after byte offset `from`
+fn find_tag(markdown: &str, from: usize) -> Option<ComponentTag<'_>> {
+    let mut search = from;
+    while let Some(ix) = markdown[search..].find('<') {
+        let start = search + ix;
+        search = start + 1;
+        if let Some(tag) = parse_tag(markdown, start) {
+            return Some(tag);
+        }
+    }
+    None
+}
+
+/// Parse component tag at byte offset start, which is the position of '<'
+fn parse_tag(markdown: &str, start: usize) -> Option<ComponentTag<'_>> {
+    let rest = &markdown[start + 1..];
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+    let name = &rest[..name_len];
+    if !is_component_name(name) {
+        return None;
+    }
+    let (attributes, open_len, self_closing) = parse_attributes(&rest[name_len..])?;
+    let inner_start = start + 1 + name_len + open_len;
+    if self_closing {
+        return Some(ComponentTag {
+            range: start..inner_start,
+            name,
+            attributes,
+            inner: "",
+        });
+    }
+    let inner_len = find_end_tag(&markdown[inner_start..], name)?;
+    Some(ComponentTag {
+        range: start..(inner_start + inner_len + name.len() + 3),
+        name,
+        attributes,
+        inner: &markdown[inner_start..inner_start + inner_len],
+    })
+}
+
+/// Byte offset of the end tag (`</Name>`) of a component whose content begins s.
+/// Nested components with the same name (and their end tags) are skipped.
+fn find_end_tag(s: &str, name: &str) -> Option<usize> {
+    let end_tag = format!("</{}>", name);
+    let mut search = 0;
+    while let Some(ix) = s[search..].find('<') {
+        let pos = search + ix;
+        if s[pos..].starts_with(&end_tag) {
+            return Some(pos);
+        }
+        search = match parse_tag(s, pos) {
+            Some(nested) if nested.name == name => nested.range.end,
+            _ => pos + 1,
+        };
+    }
+    None
+}
+
+/// Parse attributes following the tag name, through the closing '>' or '/>'.
+/// Values may be quoted with '"' or '\'', and attributes without values are set to "".
+/// Returns attributes, number of bytes consumed, and whether the tag is self-closing.
+fn parse_attributes(s: &str) -> Option<(BTreeMap<String, String>, usize, bool)> {
+    let mut attributes = BTreeMap::new();
+    let mut pos = 0;
+    loop {
+        let rest = &s[pos..];
+        let trimmed = rest.trim_start();
+        pos += rest.len() - trimmed.len();
+        if trimmed.starts_with("/>") {
+            return Some((attributes, pos + 2, true));
+        }
+        if trimmed.starts_with('>') {
+            return Some((attributes, pos + 1, false));
+        }
+        // attribute name
+        let name_len = trimmed
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(trimmed.len());
+        if name_len == 0 {
+            return None;
+        }
+        let name = &trimmed[..name_len];
+        pos += name_len;
+        let rest = &s[pos..];
+        if let Some(val) = rest.strip_prefix('=') {
+            let quote = val.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+            let val_len = val[1..].find(quote)?;
+            attributes.insert(name.to_string(), val[1..1 + val_len].to_string());
+            // '=' + quotes + value
+            pos += val_len + 3;
+        } else {
+            attributes.insert(name.to_string(), String::new());
+        }
+    }
+}
+
+#[test]
+fn test_parse_tag() {
+    let md = r#"before <Callout type="warn" open>**hi**</Callout> after"#;
+    let tag = find_tag(md, 0).expect("tag");
+    assert_eq!(tag.name, "Callout");
+    assert_eq!(tag.inner, "**hi**");
+    assert_eq!(tag.attributes.get("type").map(|s| s.as_str()), Some("warn"));
+    assert_eq!(tag.attributes.get("open").map(|s| s.as_str()), Some(""));
+    assert_eq!(&md[tag.range.end..], " after");
+
+    let tag = find_tag("<Icon name='star' />", 0).expect("self-closing");
+    assert_eq!(tag.name, "Icon");
+    assert_eq!(tag.inner, "");
+
+    assert!(find_tag("<div>lower case</div>", 0).is_none());
+    assert!(find_tag("<Callout>no end tag", 0).is_none());
+
+    let md = "<Box>a <Box>b</Box> <Box/> c</Box> d";
+    let tag = find_tag(md, 0).expect("nested");
+    assert_eq!(tag.inner, "a <Box>b</Box> <Box/> c");
+    assert_eq!(&md[tag.range.end..], " d");
+}
+
+#[test]
+fn test_expand_components() {
+    let mut components = Components::new();
+    components
+        .register(
+            "Callout",
+            r#"<div class="callout {{type}}">{{content}}</div>"#,
+        )
+        .expect("register");
+
+    let md = "# Title\n\n<Callout type=\"warn\">Be **careful**</Callout>\n\n<Widget size=\"2\">x</Widget>\n\n`<Callout>code</Callout>`\n";
+    let html = components
+        .markdown_to_html(md, &ParseOptions::default())
+        .expect("expand");
+    assert!(
+        html.content
+            .contains("<div class=\"callout warn\"><p>Be <strong>careful</strong></p>\n</div>"),
+        "registered component expanded: {}",
+        html.content
+    );
+    assert!(
+        html.content.contains("<Widget size=\"2\">x</Widget>"),
+        "unknown component left literal"
+    );
+    assert!(
+        html.content
+            .contains("<code>&lt;Callout&gt;code&lt;/Callout&gt;</code>"),
+        "code span untouched"
+    );
+
+    // nested components with the same name
+    let html = components
+        .markdown_to_html(
+            "<Callout type=\"a\">outer <Callout type=\"b\">inner</Callout> end</Callout>",
+            &ParseOptions::default(),
+        )
+        .expect("nested");
+    assert_eq!(
+        html.content,
+        "<div class=\"callout a\"><p>outer <div class=\"callout b\"><p>inner</p>\n</div> end</p>\n</div>"
+    );
+
+    components.set_unknown(UnknownComponent::Error);
+    assert!(matches!(
+        components.markdown_to_html(md, &ParseOptions::default()),
+        Err(Error::UnknownComponent(name)) if name == "Widget"
+    ));
+}
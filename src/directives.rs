@@ -0,0 +1,392 @@
+//! Directives - extension syntax for embeds, figures, tabs, etc., expanded by
+//! handlers registered by the caller.
+//!
+//! Block directives enclose markdown content, and may be nested, including directives
+//! with the same name:
+//! ```text
+//! ::: tabs rust python
+//! content
+//! :::
+//! ```
+//! Inline directives have the form `@[youtube](dQw4w9WgXcQ)`.
+//!
+//! Arguments are separated by whitespace, and may be quoted with '"'.
+//! Arguments of the form `key=value` are named, others are positional.
+//! Directives inside code spans and code blocks are not expanded.
+//!
+use crate::{
+    md_parser::{markdown_to_html, ParseOptions, ParseResult},
+    placeholder::{extract, FoundTag, Placeholders},
+    Error, Result,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+/// Name of placeholders inserted into markdown where a directive was found,
+/// and replaced with the handler's html after parsing
+const PLACEHOLDER_KIND: &str = "mdsite-directive";
+/// Start and end of block directive
+const BLOCK_FENCE: &str = ":::";
+/// Start of inline directive
+const INLINE_START: &str = "@[";
+
+/// Directive handler. Returns html for the directive.
+pub type DirectiveHandler<'reg> = Box<dyn Fn(&Directive) -> Result<String> + Send + Sync + 'reg>;
+
+/// What to do with a directive that doesn't have a registered handler
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UnknownDirective {
+    /// Leave the directive in the document as-is (default)
+    #[default]
+    PassThrough,
+    /// Return Error::UnknownDirective
+    Error,
+}
+
+/// Directive passed to a handler
+#[derive(Debug, Default, PartialEq)]
+pub struct Directive {
+    /// Directive name
+    pub name: String,
+    /// Positional arguments
+    pub args: Vec<String>,
+    /// Named (`key=value`) arguments
+    pub named: BTreeMap<String, String>,
+    /// Inner content (markdown source) of a block directive. Empty for inline directives.
+    pub content: String,
+    /// Inner content converted to html, with nested directives expanded.
+    /// Empty for inline directives.
+    pub html: String,
+    /// True for block directives
+    pub block: bool,
+}
+
+/// Directive found in markdown source
+#[derive(Debug, PartialEq)]
+struct DirectiveTag<'md> {
+    /// byte range of the whole directive, including inner content and end fence
+    range: Range<usize>,
+    name: &'md str,
+    args: &'md str,
+    inner: &'md str,
+    block: bool,
+}
+
+impl<'md> FoundTag for DirectiveTag<'md> {
+    fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    fn is_block(&self) -> bool {
+        self.block
+    }
+}
+
+/// Registry of directive handlers
+#[derive(Default)]
+pub struct Directives<'reg> {
+    handlers: HashMap<String, DirectiveHandler<'reg>>,
+    unknown: UnknownDirective,
+}
+
+impl<'reg> Directives<'reg> {
+    /// Create empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set handling of unregistered directives
+    pub fn set_unknown(&mut self, unknown: UnknownDirective) {
+        self.unknown = unknown;
+    }
+
+    /// Register handler for the directive name. A handler registered for an existing name
+    /// replaces the previous one.
+    pub fn register(&mut self, name: &str, handler: DirectiveHandler<'reg>) {
+        self.handlers.insert(name.to_string(), handler);
+    }
+
+    /// Returns true if the directive has a handler
+    pub fn contains(&self, name: &str) -> bool {
+        self.handlers.contains_key(name)
+    }
+
+    /// Expand directives in the markdown, and convert it to html.
+    pub fn markdown_to_html(&self, markdown: &str, opt: &ParseOptions) -> Result<ParseResult> {
+        let mut placeholders = Placeholders::new(PLACEHOLDER_KIND);
+        let expand = |tag: &DirectiveTag| {
+            if self.contains(tag.name) {
+                self.expand(tag, opt).map(Some)
+            } else if self.unknown == UnknownDirective::Error {
+                Err(Error::UnknownDirective(tag.name.to_string()))
+            } else {
+                Ok(None)
+            }
+        };
+        // block directives first, so that inline directives in their content
+        // are handled when the content is converted
+        let source = extract(markdown, find_block, expand, &mut placeholders)?;
+        let source = extract(&source, find_inline, expand, &mut placeholders)?;
+        let mut html = markdown_to_html(&source, opt)?;
+        if !placeholders.is_empty() {
+            html.content = placeholders.replace(&html.content);
+            html.blocks.clear();
+        }
+        Ok(html)
+    }
+
+    /// Call directive's handler
+    fn expand(&self, tag: &DirectiveTag, opt: &ParseOptions) -> Result<String> {
+        let handler = match self.handlers.get(tag.name) {
+            Some(handler) => handler,
+            None => return Err(Error::Bug(format!("no handler for {}", tag.name))),
+        };
+        let (args, named) = parse_args(tag.args);
+        let html = if tag.block {
+            // inner content may contain other directives
+            self.markdown_to_html(tag.inner, opt)?.content
+        } else {
+            String::new()
+        };
+        handler(&Directive {
+            name: tag.name.to_string(),
+            args,
+            named,
+            content: tag.inner.to_string(),
+            html,
+            block: tag.block,
+        })
+    }
+}
+
+/// Directive names are ascii alphanumerics, '-', and '_'
+fn is_directive_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// If the line opens a block directive, returns name and args.
+/// The fence may be indented with spaces or tabs.
+fn block_start(line: &str) -> Option<(&str, &str)> {
+    let rest = line
+        .trim_start_matches([' ', '\t'])
+        .strip_prefix(BLOCK_FENCE)?
+        .trim_start();
+    let name_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let name = &rest[..name_len];
+    if !is_directive_name(name) {
+        return None;
+    }
+    Some((name, rest[name_len..].trim()))
+}
+
+/// Find the next block directive whose opening line starts at or after byte offset `from`.
+/// Blocks without a closing fence are ignored.
+fn find_block(markdown: &str, from: usize) -> Option<DirectiveTag<'_>> {
+    let mut offset = from;
+    for line in markdown[from..].split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        // only consider lines that begin at a line start
+        if start > 0 && !markdown[..start].ends_with('\n') {
+            continue;
+        }
+        let (name, args) = match block_start(line) {
+            Some(found) => found,
+            None => continue,
+        };
+        let inner_start = offset;
+        let mut inner_end = offset;
+        let mut depth = 1;
+        for inner in markdown[inner_start..].split_inclusive('\n') {
+            if inner.trim() == BLOCK_FENCE {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(DirectiveTag {
+                        range: start..(inner_end + inner.len()),
+                        name,
+                        args,
+                        inner: &markdown[inner_start..inner_end],
+                        block: true,
+                    });
+                }
+            } else if block_start(inner).is_some() {
+                depth += 1;
+            }
+            inner_end += inner.len();
+        }
+        // unclosed: continue with the next line
+    }
+    None
+}
+
+/// Find the next inline directive starting at or after byte offset `from`
+fn find_inline(markdown: &str, from: usize) -> Option<DirectiveTag<'_>> {
+    let mut search = from;
+    while let Some(ix) = markdown[search..].find(INLINE_START) {
+        let start = search + ix;
+        search = start + 1;
+        let rest = &markdown[start + INLINE_START.len()..];
+        let name_len = match rest.find("](") {
+            Some(len) => len,
+            None => continue,
+        };
+        let name = &rest[..name_len];
+        if !is_directive_name(name) {
+            continue;
+        }
+        let args_start = name_len + 2;
+        let args_len = match rest[args_start..].find([')', '\n']) {
+            Some(len) if rest[args_start + len..].starts_with(')') => len,
+            _ => continue,
+        };
+        return Some(DirectiveTag {
+            range: start..(start + INLINE_START.len() + args_start + args_len + 1),
+            name,
+            args: &rest[args_start..args_start + args_len],
+            inner: "",
+            block: false,
+        });
+    }
+    None
+}
+
+/// Split arguments into positional and named arguments.
+/// Arguments are separated by whitespace, and may be quoted with '"'.
+fn parse_args(s: &str) -> (Vec<String>, BTreeMap<String, String>) {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut in_token = false;
+    let mut in_quote = false;
+    for c in s.chars() {
+        match c {
+            '"' => {
+                in_quote = !in_quote;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !in_quote => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            c => {
+                token.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(token);
+    }
+    let mut args = Vec::new();
+    let mut named = BTreeMap::new();
+    for token in tokens {
+        match token.split_once('=') {
+            Some((key, val)) if is_directive_name(key) => {
+                named.insert(key.to_string(), val.to_string());
+            }
+            _ => args.push(token),
+        }
+    }
+    (args, named)
+}
+
+#[test]
+fn test_parse_directives() {
+    let md = "text\n::: tabs rust \"c sharp\" active=1\ninner\n::: note\nx\n:::\n:::\nafter";
+    let tag = find_block(md, 0).expect("block");
+    assert_eq!(tag.name, "tabs");
+    assert_eq!(tag.inner, "inner\n::: note\nx\n:::\n");
+    assert_eq!(&md[tag.range.end..], "after");
+    let (args, named) = parse_args(tag.args);
+    assert_eq!(args, vec!["rust", "c sharp"]);
+    assert_eq!(named.get("active").map(|s| s.as_str()), Some("1"));
+    assert!(find_block("::: open\nno end\n", 0).is_none());
+
+    let md = "::: note\nouter\n::: note\ninner\n:::\n:::\nafter";
+    let tag = find_block(md, 0).expect("nested with same name");
+    assert_eq!(tag.inner, "outer\n::: note\ninner\n:::\n");
+    assert_eq!(&md[tag.range.end..], "after");
+
+    let md = "see @[youtube](abc123 start=10) here";
+    let tag = find_inline(md, 0).expect("inline");
+    assert_eq!(tag.name, "youtube");
+    assert_eq!(tag.args, "abc123 start=10");
+    assert_eq!(&md[tag.range.end..], " here");
+    assert!(find_inline("email @[not a name](x)", 0).is_none());
+}
+
+#[test]
+fn test_expand_directives() {
+    let mut directives = Directives::new();
+    directives.register(
+        "youtube",
+        Box::new(|d: &Directive| {
+            Ok(format!(
+                r#"<iframe src="https://www.youtube.com/embed/{}"></iframe>"#,
+                d.args.first().map(|s| s.as_str()).unwrap_or_default()
+            ))
+        }),
+    );
+    directives.register(
+        "tabs",
+        Box::new(|d: &Directive| {
+            Ok(format!(
+                r#"<div class="tabs" data-tabs="{}">{}</div>"#,
+                d.args.join(","),
+                d.html
+            ))
+        }),
+    );
+
+    let md = "# Video\n\nWatch @[youtube](dQw4w9WgXcQ) now.\n\n::: tabs rust python\nUse **either** @[youtube](xyz)\n:::\n\n::: figure\nkept\n:::\n\n`@[youtube](code)`\n";
+    let html = directives
+        .markdown_to_html(md, &ParseOptions::default())
+        .expect("expand");
+    assert!(
+        html.content.contains(
+            "<p>Watch <iframe src=\"https://www.youtube.com/embed/dQw4w9WgXcQ\"></iframe> now.</p>"
+        ),
+        "inline directive: {}",
+        html.content
+    );
+    assert!(
+        html.content.contains(
+            "<div class=\"tabs\" data-tabs=\"rust,python\"><p>Use <strong>either</strong> <iframe src=\"https://www.youtube.com/embed/xyz\"></iframe></p>\n</div>"
+        ),
+        "block directive: {}",
+        html.content
+    );
+    assert!(
+        html.content.contains("<p>::: figure\nkept\n:::</p>"),
+        "unknown directive passed through"
+    );
+    assert!(
+        html.content.contains("<code>@[youtube](code)</code>"),
+        "code span untouched"
+    );
+
+    directives.set_unknown(UnknownDirective::Error);
+    assert!(matches!(
+        directives.markdown_to_html(md, &ParseOptions::default()),
+        Err(Error::UnknownDirective(name)) if name == "figure"
+    ));
+
+    // fences indented with non-ascii whitespace are not directives
+    directives.set_unknown(UnknownDirective::PassThrough);
+    for md in [
+        "\u{3000}::: note\ntext\n:::\n",
+        "```\n\u{3000}::: tabs a\ntext\n:::\n```\n",
+        "`\u{3000}@[youtube](x)` \u{3000}::: note\n",
+    ]
+    .iter()
+    {
+        let html = directives
+            .markdown_to_html(md, &ParseOptions::default())
+            .expect("no panic");
+        assert!(!html.content.contains("class=\"tabs\""), "{}", html.content);
+    }
+}
@@ -0,0 +1,69 @@
+//! Structured representation of a markdown document, for headless/api use
+//!
+use crate::{
+    markdown::{split_markdown, Frontmatter},
+    md_parser::{markdown_to_html, markdown_to_text, ParseOptions},
+    Error, Result,
+};
+use serde_json::{json, Value as JsonValue};
+
+/// Convert a markdown document, including frontmatter, to a json object with keys
+///  - `frontmatter`: object with frontmatter values (empty if there is no frontmatter)
+///  - `content_html`: content converted to html
+///  - `toc`: generated table of contents, or null if the document has no toc flag
+///  - `plain_text`: content as plain text
+///  - `headings`: array of `{level, text, slug}`
+///  - `metadata`: object with `frontmatter_format` ("toml", "yaml", "json", or null),
+///    `word_count` (as in `ParseResult`), and `diagnostics` (array of messages from the parser)
+pub fn document_to_json(markdown: &str, opt: &ParseOptions) -> Result<JsonValue> {
+    let (front, body) = split_markdown(markdown);
+    let format = match front {
+        Frontmatter::Toml(_) => Some("toml"),
+        Frontmatter::Yaml(_) => Some("yaml"),
+        Frontmatter::Json(_) => Some("json"),
+        Frontmatter::Empty => None,
+    };
+    let frontmatter = serde_json::to_value(front.to_toml()?)
+        .map_err(|e| Error::FrontmatterParse(e.to_string()))?;
+    let html = markdown_to_html(body, opt)?;
+    let plain_text = markdown_to_text(body);
+    Ok(json!({
+        "frontmatter": frontmatter,
+        "content_html": html.content,
+        "toc": html.toc,
+        "plain_text": plain_text,
+        "headings": html.headings,
+        "metadata": {
+            "frontmatter_format": format,
+            "word_count": html.word_count,
+            "diagnostics": html.diagnostics,
+        },
+    }))
+}
+
+#[test]
+fn test_document_to_json() {
+    let md = "+++\ntitle = \"Guide\"\n+++\n<!-- toc -->\n# Intro\n\nHello world.\n";
+    let doc = document_to_json(md, &ParseOptions::default()).expect("json");
+    for key in [
+        "frontmatter",
+        "content_html",
+        "toc",
+        "plain_text",
+        "metadata",
+        "headings",
+    ]
+    .iter()
+    {
+        assert!(doc.get(key).is_some(), "missing key {}", key);
+    }
+    assert_eq!(doc["frontmatter"]["title"], "Guide");
+    assert_eq!(
+        doc["content_html"],
+        "\n<h1 id=\"intro\">Intro</h1>\n<p>Hello world.</p>\n"
+    );
+    assert_eq!(doc["plain_text"], "Intro\n\nHello world.\n");
+    assert_eq!(doc["headings"][0]["slug"], "intro");
+    assert_eq!(doc["metadata"]["frontmatter_format"], "toml");
+    assert_eq!(doc["metadata"]["word_count"], 3);
+}
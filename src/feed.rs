@@ -0,0 +1,369 @@
+//! RSS and Atom feeds, for blogs
+//!
+use crate::{
+    md_parser::{escape_html, ParseResult},
+    render::parse_date,
+    Error, Result, TomlMap,
+};
+use chrono::{DateTime, FixedOffset};
+use std::path::PathBuf;
+use toml::value::Value as TomlValue;
+
+/// Feed information
+#[derive(Clone, Debug, Default)]
+pub struct FeedChannel {
+    /// Feed title, e.g., the site or blog name
+    pub title: String,
+    /// Url of the site, e.g., "https://example.com/blog/"
+    pub link: String,
+    /// Description of the feed
+    pub description: String,
+    /// Url of the feed itself, for the Atom `id` and `rel="self"` link.
+    /// If not set, `link` is used as the Atom id.
+    pub feed_url: Option<String>,
+}
+
+/// Page to add to a feed with `items_from_pages`
+#[derive(Clone, Debug)]
+pub struct FeedPage<'p> {
+    /// Source path of the page, for reporting errors
+    pub path: PathBuf,
+    /// Url of the page
+    pub link: String,
+    /// Page frontmatter
+    pub frontmatter: &'p TomlMap,
+    /// Parsed page content
+    pub page: &'p ParseResult,
+}
+
+/// Feed item (RSS item or Atom entry)
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedItem {
+    /// Title of the page
+    pub title: String,
+    /// Url of the page
+    pub link: String,
+    /// Publication date
+    pub date: DateTime<FixedOffset>,
+    /// Summary of the page
+    pub description: Option<String>,
+    /// Page content html
+    pub content: Option<String>,
+}
+
+impl FeedItem {
+    /// Item for a page, from its frontmatter `title`, `date`, and `description` values,
+    /// and the parsed content. If the frontmatter has no description, the page excerpt
+    /// (`ParseResult.excerpt`) is used.
+    /// Returns Error::FrontmatterParse if title or date is missing, or the date isn't valid.
+    pub fn from_frontmatter<T: Into<String>>(
+        map: &TomlMap,
+        link: T,
+        page: &ParseResult,
+    ) -> Result<Self> {
+        let title = map
+            .get("title")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| Error::FrontmatterParse("feed item is missing 'title'".into()))?;
+        let date = map.get("date").and_then(toml_date).ok_or_else(|| {
+            Error::FrontmatterParse(format!("feed item '{}' needs a valid 'date'", title))
+        })?;
+        let description = map
+            .get("description")
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .or_else(|| page.excerpt.clone());
+        Ok(Self {
+            title: title.to_string(),
+            link: link.into(),
+            date,
+            description,
+            content: Some(page.content.clone()),
+        })
+    }
+}
+
+/// Feed items for the pages, like `FeedItem::from_frontmatter`. Pages that can't be
+/// converted, such as pages without a valid date, are skipped, and returned with their
+/// errors, so one bad page doesn't prevent generating the feed.
+/// Returns (items, skipped pages)
+pub fn items_from_pages(pages: &[FeedPage<'_>]) -> (Vec<FeedItem>, Vec<(PathBuf, Error)>) {
+    let mut items = Vec::new();
+    let mut skipped = Vec::new();
+    for page in pages.iter() {
+        match FeedItem::from_frontmatter(page.frontmatter, page.link.as_str(), page.page) {
+            Ok(item) => items.push(item),
+            Err(e) => skipped.push((page.path.clone(), e)),
+        }
+    }
+    (items, skipped)
+}
+
+/// Date from a frontmatter value: a toml datetime, or a string in one of the formats
+/// of `render::parse_date`
+pub(crate) fn toml_date(value: &TomlValue) -> Option<DateTime<FixedOffset>> {
+    match value {
+        TomlValue::String(s) => parse_date(s),
+        TomlValue::Datetime(dt) => parse_date(&dt.to_string()),
+        _ => None,
+    }
+}
+
+/// Generate RSS 2.0 feed. Dates are formatted as RFC822 (e.g., "Mon, 15 Jan 2024 10:30:00 -0800"),
+/// html in descriptions and content is escaped, and content is added as `content:encoded`.
+/// Returns Error::InvalidBaseUrl if the channel link isn't an http or https url.
+pub fn generate_rss(channel: FeedChannel, items: &[FeedItem]) -> Result<String> {
+    check_link(&channel.link)?;
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<rss version=\"2.0\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\">\n",
+        "<channel>\n"
+    ));
+    push_element(&mut xml, 1, "title", &channel.title);
+    push_element(&mut xml, 1, "link", &channel.link);
+    push_element(&mut xml, 1, "description", &channel.description);
+    if let Some(latest) = items.iter().map(|item| item.date).max() {
+        push_element(&mut xml, 1, "lastBuildDate", &latest.to_rfc2822());
+    }
+    for item in items.iter() {
+        xml.push_str("  <item>\n");
+        push_element(&mut xml, 2, "title", &item.title);
+        push_element(&mut xml, 2, "link", &item.link);
+        push_element(&mut xml, 2, "guid", &item.link);
+        push_element(&mut xml, 2, "pubDate", &item.date.to_rfc2822());
+        if let Some(description) = &item.description {
+            push_element(&mut xml, 2, "description", description);
+        }
+        if let Some(content) = &item.content {
+            push_element(&mut xml, 2, "content:encoded", content);
+        }
+        xml.push_str("  </item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    Ok(xml)
+}
+
+/// Generate Atom feed. Dates are formatted as RFC3339 (e.g., "2024-01-15T10:30:00-08:00"),
+/// and the feed's `updated` is the latest item date.
+/// Returns Error::InvalidBaseUrl if the channel link isn't an http or https url.
+pub fn generate_atom(channel: FeedChannel, items: &[FeedItem]) -> Result<String> {
+    check_link(&channel.link)?;
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n"
+    ));
+    push_element(&mut xml, 1, "title", &channel.title);
+    if !channel.description.is_empty() {
+        push_element(&mut xml, 1, "subtitle", &channel.description);
+    }
+    push_link(&mut xml, 1, &channel.link, None);
+    if let Some(feed_url) = &channel.feed_url {
+        push_link(&mut xml, 1, feed_url, Some("self"));
+    }
+    push_element(
+        &mut xml,
+        1,
+        "id",
+        channel.feed_url.as_deref().unwrap_or(&channel.link),
+    );
+    if let Some(latest) = items.iter().map(|item| item.date).max() {
+        push_element(&mut xml, 1, "updated", &latest.to_rfc3339());
+    }
+    for item in items.iter() {
+        xml.push_str("  <entry>\n");
+        push_element(&mut xml, 2, "title", &item.title);
+        push_link(&mut xml, 2, &item.link, None);
+        push_element(&mut xml, 2, "id", &item.link);
+        push_element(&mut xml, 2, "updated", &item.date.to_rfc3339());
+        if let Some(description) = &item.description {
+            push_element(&mut xml, 2, "summary", description);
+        }
+        if let Some(content) = &item.content {
+            xml.push_str(&format!(
+                "    <content type=\"html\">{}</content>\n",
+                escape_html(content)
+            ));
+        }
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    Ok(xml)
+}
+
+/// Returns Error::InvalidBaseUrl if the link isn't an http or https url
+fn check_link(link: &str) -> Result<()> {
+    if link.starts_with("http://") || link.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(Error::InvalidBaseUrl(link.to_string()))
+    }
+}
+
+/// Append element with escaped text on its own line, indented two spaces per level
+fn push_element(xml: &mut String, level: usize, name: &str, text: &str) {
+    xml.push_str(&format!(
+        "{:indent$}<{name}>{}</{name}>\n",
+        "",
+        escape_html(text),
+        indent = level * 2,
+        name = name
+    ));
+}
+
+/// Append Atom link element
+fn push_link(xml: &mut String, level: usize, href: &str, rel: Option<&str>) {
+    let rel = rel
+        .map(|rel| format!(" rel=\"{}\"", rel))
+        .unwrap_or_default();
+    xml.push_str(&format!(
+        "{:indent$}<link href=\"{}\"{}/>\n",
+        "",
+        escape_html(href),
+        rel,
+        indent = level * 2
+    ));
+}
+
+#[cfg(test)]
+fn test_items() -> Vec<FeedItem> {
+    use crate::md_parser::{markdown_to_html, ParseOptions};
+
+    let page = |front: &str, md: &str, link: &str| {
+        let map: TomlMap = toml::from_str(front).unwrap();
+        let html = markdown_to_html(md, &ParseOptions::default()).unwrap();
+        FeedItem::from_frontmatter(&map, link, &html).expect("item")
+    };
+    vec![
+        page(
+            "title = \"First & Best\"\ndate = 2024-01-15T10:30:00-08:00\ndescription = \"Intro\"",
+            "Hello **world**",
+            "https://example.com/blog/first.html",
+        ),
+        page(
+            "title = \"Second\"\ndate = \"2024-02-01\"",
+            "Short summary.\n\n<!-- more -->\n\nThe rest.",
+            "https://example.com/blog/second.html",
+        ),
+    ]
+}
+
+#[cfg(test)]
+fn test_channel() -> FeedChannel {
+    FeedChannel {
+        title: "Blog".into(),
+        link: "https://example.com/blog/".into(),
+        description: "News".into(),
+        feed_url: Some("https://example.com/blog/atom.xml".into()),
+    }
+}
+
+#[test]
+fn test_feed_item() {
+    let items = test_items();
+    assert_eq!(items[0].title, "First & Best");
+    assert_eq!(items[0].description.as_deref(), Some("Intro"));
+    assert_eq!(
+        items[0].content.as_deref(),
+        Some("<p>Hello <strong>world</strong></p>\n")
+    );
+    // excerpt is used if there's no description
+    assert_eq!(
+        items[1].description.as_deref(),
+        Some("<p>Short summary.</p>\n")
+    );
+
+    let html = crate::md_parser::markdown_to_html_default("text").unwrap();
+    let map: TomlMap = toml::from_str("title = \"No date\"").unwrap();
+    assert!(matches!(
+        FeedItem::from_frontmatter(&map, "https://example.com/x.html", &html),
+        Err(Error::FrontmatterParse(_))
+    ));
+}
+
+#[test]
+fn test_generate_rss() {
+    let xml = generate_rss(test_channel(), &test_items()).expect("rss");
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\""));
+    assert!(xml.contains("  <title>Blog</title>\n  <link>https://example.com/blog/</link>\n"));
+    assert!(xml.contains("  <lastBuildDate>Thu, 1 Feb 2024 00:00:00 +0000</lastBuildDate>\n"));
+    assert!(xml.contains(concat!(
+        "  <item>\n",
+        "    <title>First &amp; Best</title>\n",
+        "    <link>https://example.com/blog/first.html</link>\n",
+        "    <guid>https://example.com/blog/first.html</guid>\n",
+        "    <pubDate>Mon, 15 Jan 2024 10:30:00 -0800</pubDate>\n",
+        "    <description>Intro</description>\n",
+        "    <content:encoded>&lt;p&gt;Hello &lt;strong&gt;world&lt;/strong&gt;&lt;/p&gt;\n",
+        "</content:encoded>\n",
+        "  </item>\n"
+    )));
+    assert!(xml.contains("    <pubDate>Thu, 1 Feb 2024 00:00:00 +0000</pubDate>\n"));
+    assert!(xml.ends_with("</channel>\n</rss>\n"));
+
+    let channel = FeedChannel {
+        link: "example.com".into(),
+        ..test_channel()
+    };
+    assert!(matches!(
+        generate_rss(channel, &[]),
+        Err(Error::InvalidBaseUrl(_))
+    ));
+}
+
+#[test]
+fn test_generate_atom() {
+    let xml = generate_atom(test_channel(), &test_items()).expect("atom");
+    assert!(xml.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n"));
+    assert!(xml.contains(concat!(
+        "  <link href=\"https://example.com/blog/\"/>\n",
+        "  <link href=\"https://example.com/blog/atom.xml\" rel=\"self\"/>\n",
+        "  <id>https://example.com/blog/atom.xml</id>\n",
+        "  <updated>2024-02-01T00:00:00+00:00</updated>\n",
+    )));
+    assert!(xml.contains(concat!(
+        "  <entry>\n",
+        "    <title>First &amp; Best</title>\n",
+        "    <link href=\"https://example.com/blog/first.html\"/>\n",
+        "    <id>https://example.com/blog/first.html</id>\n",
+        "    <updated>2024-01-15T10:30:00-08:00</updated>\n",
+        "    <summary>Intro</summary>\n",
+    )));
+    assert!(xml.ends_with("  </entry>\n</feed>\n"));
+}
+
+#[test]
+fn test_items_from_pages() {
+    use crate::md_parser::markdown_to_html_default;
+
+    let html = markdown_to_html_default("text").unwrap();
+    let fronts = [
+        "title = \"One\"\ndate = \"2024-01-01\"",
+        "title = \"Two\"",
+        "title = \"Three\"\ndate = 2024-03-01",
+    ]
+    .iter()
+    .map(|front| toml::from_str::<TomlMap>(front).unwrap())
+    .collect::<Vec<_>>();
+    let pages = fronts
+        .iter()
+        .enumerate()
+        .map(|(n, frontmatter)| FeedPage {
+            path: PathBuf::from(format!("blog/{}.md", n)),
+            link: format!("https://example.com/blog/{}.html", n),
+            frontmatter,
+            page: &html,
+        })
+        .collect::<Vec<_>>();
+    let (items, skipped) = items_from_pages(&pages);
+    assert_eq!(
+        items
+            .iter()
+            .map(|item| item.title.as_str())
+            .collect::<Vec<_>>(),
+        vec!["One", "Three"]
+    );
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].0, PathBuf::from("blog/1.md"));
+    assert!(matches!(skipped[0].1, Error::FrontmatterParse(_)));
+    assert!(generate_rss(test_channel(), &items).is_ok());
+}
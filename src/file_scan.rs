@@ -6,9 +6,12 @@ use crate::{
     markdown::{parse_frontmatter, split_markdown},
     Error, Result,
 };
-use ignore::{DirEntry, WalkBuilder};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{DirEntry, Walk, WalkBuilder};
 use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, VecDeque};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 const MARKDOWN_EXTENSION: &str = "md";
 const HANDLEBARS_EXTENSION: &str = "hbs";
@@ -21,11 +24,16 @@ fn split(entry: &DirEntry) -> (&Path, &Path) {
 }
 
 /// Markdown file info
+#[derive(Default)]
 pub struct MarkdownPath {
     ///  Full path to file, including source path
     pub path: PathBuf,
     /// Relative path from its source dir
     pub rel_path: PathBuf,
+    /// Last modified time, if `ScanOptions.collect_metadata` is set
+    pub modified: Option<SystemTime>,
+    /// File size in bytes, if `ScanOptions.collect_metadata` is set
+    pub size: Option<u64>,
 }
 
 /// Markdown file info with data
@@ -39,11 +47,37 @@ pub struct MarkdownData<T: DeserializeOwned> {
 }
 
 /// Results of file scan
+#[derive(Default)]
 pub struct ScanResults {
     /// All templates found
     pub templates: Vec<PathBuf>,
     /// All markdown files found
     pub markdown: Vec<MarkdownPath>,
+    /// Path and error message for each markdown file whose metadata couldn't be read,
+    /// if `ScanOptions.collect_metadata` is set. These files are still in `markdown`.
+    pub metadata_errors: Vec<(PathBuf, String)>,
+}
+
+impl ScanResults {
+    /// Add entry to the markdown, templates, or metadata_errors list
+    fn add(&mut self, entry: ScanEntry) {
+        match entry {
+            ScanEntry::Markdown(mdp) => self.markdown.push(mdp),
+            ScanEntry::Template(path) => self.templates.push(path),
+            ScanEntry::MetadataError(path, e) => self.metadata_errors.push((path, e)),
+        }
+    }
+}
+
+/// File found by `scan_iter`
+pub enum ScanEntry {
+    /// Markdown file
+    Markdown(MarkdownPath),
+    /// Template file
+    Template(PathBuf),
+    /// Path and error message of a markdown file whose metadata couldn't be read,
+    /// if `ScanOptions.collect_metadata` is set. This follows the file's `Markdown` entry.
+    MetadataError(PathBuf, String),
 }
 
 /// Options for file scanner
@@ -52,6 +86,25 @@ pub struct ScanOptions {
     pub follow_links: bool,
     /// Whether to load and parse frontmatter from markdown files (default false).
     pub load_frontmatter: bool,
+    /// Whether to read the modified time and size of markdown files (default false).
+    pub collect_metadata: bool,
+    /// File extensions of markdown files, without the leading '.' (default: `["md"]`).
+    /// Extensions may have more than one part (e.g., "html.md"), and are case-insensitive.
+    pub markdown_extensions: Vec<String>,
+    /// File extensions of templates, without the leading '.' (default: `["hbs"]`).
+    /// Extensions may have more than one part (e.g., "html.hbs"), and are case-insensitive.
+    pub template_extensions: Vec<String>,
+    /// If not empty, only files whose relative path (from its source dir) matches
+    /// one of these globs are included, e.g., `blog/**/*.md`. `*` does not match '/';
+    /// use `**` to match any number of folders.
+    pub include_globs: Vec<String>,
+    /// Files whose relative path matches one of these globs are skipped, e.g., `drafts/**`.
+    /// Exclusions take precedence over `include_globs`.
+    pub exclude_globs: Vec<String>,
+    /// Maximum folder depth to scan, or None for no limit (the default).
+    /// Depth 1 includes only files directly in each source folder, depth 2 also
+    /// includes files in their sub-folders, etc.
+    pub max_depth: Option<usize>,
 }
 
 impl Default for ScanOptions {
@@ -59,10 +112,43 @@ impl Default for ScanOptions {
         Self {
             follow_links: false,
             load_frontmatter: false,
+            collect_metadata: false,
+            markdown_extensions: vec![MARKDOWN_EXTENSION.to_string()],
+            template_extensions: vec![HANDLEBARS_EXTENSION.to_string()],
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
+            max_depth: None,
         }
     }
 }
 
+/// Compile glob patterns into a set. Returns Error::InvalidGlob if a pattern is invalid.
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.iter() {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| Error::InvalidGlob(pattern.clone(), e.to_string()))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| Error::InvalidGlob(patterns.join(","), e.to_string()))
+}
+
+/// Returns true if the file name ends with '.' followed by one of the extensions,
+/// ignoring case
+fn has_extension(file_name: &str, extensions: &[String]) -> bool {
+    let file_name = file_name.to_lowercase();
+    extensions.iter().any(|ext| {
+        let ext = ext.trim_start_matches('.').to_lowercase();
+        file_name.len() > ext.len() + 1
+            && file_name.ends_with(&ext)
+            && file_name[..file_name.len() - ext.len()].ends_with('.')
+    })
+}
+
 /// Collects parsed metadata from each file. If there are any errors reading the file
 /// (such as file permission problems), returns an Error.
 /// Does not return errors immediately if frontmatter isn't parsed correctly
@@ -72,28 +158,148 @@ impl Default for ScanOptions {
 pub fn load_frontmatter<T: DeserializeOwned>(
     files: Vec<MarkdownPath>,
 ) -> Result<Vec<MarkdownData<T>>> {
-    use std::fs::read_to_string;
+    files.into_iter().map(load_file_frontmatter).collect()
+}
 
-    files
-        .into_iter()
-        .map(|mdp| {
-            let body = read_to_string(&mdp.path)?;
-            let (front, _) = split_markdown(&body);
-            let frontmatter = parse_frontmatter(front);
-            Ok(MarkdownData {
-                path: mdp.path,
-                rel_path: mdp.rel_path,
-                frontmatter,
-            })
+/// Same as `load_frontmatter`, but files are read and parsed on multiple threads.
+/// Results are in the same order as files.
+#[cfg(feature = "parallel")]
+pub fn par_load_frontmatter<T: DeserializeOwned + Send>(
+    files: Vec<MarkdownPath>,
+) -> Result<Vec<MarkdownData<T>>> {
+    use rayon::prelude::*;
+
+    files.into_par_iter().map(load_file_frontmatter).collect()
+}
+
+/// Read file and parse its frontmatter
+fn load_file_frontmatter<T: DeserializeOwned>(mdp: MarkdownPath) -> Result<MarkdownData<T>> {
+    let body = std::fs::read_to_string(&mdp.path)?;
+    let (front, _) = split_markdown(&body);
+    let frontmatter = parse_frontmatter(front);
+    Ok(MarkdownData {
+        path: mdp.path,
+        rel_path: mdp.rel_path,
+        frontmatter,
+    })
+}
+
+/// Returns path and error message for each file whose frontmatter failed to parse,
+/// in the same order as data, for a build report.
+pub fn summarize_frontmatter_errors<T: DeserializeOwned>(
+    data: &[MarkdownData<T>],
+) -> Vec<(PathBuf, String)> {
+    data.iter()
+        .filter_map(|md| match &md.frontmatter {
+            Err(e) => Some((md.path.clone(), e.to_string())),
+            Ok(_) => None,
         })
         .collect()
 }
 
+/// Sort pages into reading order, by weight, for navigation and prev/next links.
+/// `weight` returns the weight from a page's frontmatter (e.g., a `weight` or `order` field).
+/// Pages are ordered by increasing weight, and pages with equal weights by rel_path.
+/// Pages without a weight, including pages whose frontmatter failed to parse,
+/// follow the weighted pages, ordered by rel_path.
+pub fn sort_by_weight<T, F>(docs: &mut [MarkdownData<T>], weight: F)
+where
+    T: DeserializeOwned,
+    F: Fn(&T) -> Option<i64>,
+{
+    docs.sort_by_cached_key(|md| {
+        let weight = md.frontmatter.as_ref().ok().and_then(&weight);
+        (weight.is_none(), weight, md.rel_path.clone())
+    });
+}
+
 /// scan folders to build index of markdown and template files
 pub fn index_sources(sources: &[PathBuf], opt: &ScanOptions) -> Result<ScanResults> {
-    let mut markdown: Vec<MarkdownPath> = Vec::new();
-    let mut templates: Vec<PathBuf> = Vec::new();
+    let mut results = ScanResults::default();
+    for entry in scan_iter(sources, opt) {
+        results.add(entry?);
+    }
+    check_template_names(&results.templates)?;
+    Ok(results)
+}
+
+/// Scan folders for markdown and template files, like `index_sources`, but return
+/// each file as it is found, so processing can start before the walk is complete.
+/// Errors, such as an invalid source folder or glob, are returned by the iterator.
+/// Unlike `index_sources`, templates with the same file name are not reported.
+pub fn scan_iter<'opt>(
+    sources: &[PathBuf],
+    opt: &'opt ScanOptions,
+) -> impl Iterator<Item = Result<ScanEntry>> + 'opt {
+    let mut iter = ScanIter {
+        walk: None,
+        opt,
+        include: GlobSet::empty(),
+        exclude: GlobSet::empty(),
+        pending: VecDeque::new(),
+    };
+    let setup = glob_set(&opt.include_globs).and_then(|include| {
+        let exclude = glob_set(&opt.exclude_globs)?;
+        Ok((include, exclude, walk_sources(sources, opt)?))
+    });
+    match setup {
+        Ok((include, exclude, walk)) => {
+            iter.include = include;
+            iter.exclude = exclude;
+            iter.walk = Some(walk);
+        }
+        Err(e) => iter.pending.push_back(Err(e)),
+    }
+    iter
+}
 
+/// Iterator for scan_iter
+struct ScanIter<'opt> {
+    /// Directory walker, or None if the scan couldn't start
+    walk: Option<Walk>,
+    opt: &'opt ScanOptions,
+    include: GlobSet,
+    exclude: GlobSet,
+    /// Entries found but not yet returned
+    pending: VecDeque<Result<ScanEntry>>,
+}
+
+impl<'opt> Iterator for ScanIter<'opt> {
+    type Item = Result<ScanEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.pending.pop_front() {
+                return Some(entry);
+            }
+            let entry = match ok_entry(self.walk.as_mut()?.next()?) {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+            if !file_filter(&entry) {
+                continue;
+            }
+            let (_, relative_path) = split(&entry);
+            if self.exclude.is_match(relative_path)
+                || (!self.opt.include_globs.is_empty() && !self.include.is_match(relative_path))
+            {
+                continue;
+            }
+            if let Err(e) = add_file(
+                &mut self.pending,
+                entry.path(),
+                relative_path,
+                self.opt,
+                || entry.metadata().map_err(|e| e.to_string()),
+            ) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Directory walker for the source folders
+fn walk_sources(sources: &[PathBuf], opt: &ScanOptions) -> Result<Walk> {
     let mut walk = match sources.split_first() {
         Some((first, others)) => {
             if !first.is_dir() {
@@ -115,42 +321,95 @@ pub fn index_sources(sources: &[PathBuf], opt: &ScanOptions) -> Result<ScanResul
         // enable ignore files from  parents of each included dir
         .parents(true)
         // whether to follow symbolic links
-        .follow_links(opt.follow_links);
-    for res in walk.build() {
-        let entry = ok_entry(res)?;
-        if !file_filter(&entry) {
-            continue;
-        }
-        let (_, relative_path) = split(&entry);
-        if let Some(ext) = entry.path().extension() {
-            match ext.to_str() {
-                Some(MARKDOWN_EXTENSION) => {
-                    markdown.push(MarkdownPath {
-                        path: entry.path().to_path_buf(),
-                        rel_path: relative_path.to_path_buf(),
-                    });
-                }
-                Some(HANDLEBARS_EXTENSION) => {
-                    // handlebars requires template name to be unicode
-                    // (we use file name as the template name).
-                    match entry.path().file_name() {
-                        Some(oss) if oss.to_str().is_some() => {}
-                        _ => {
-                            return Err(Error::NonUnicodeFilename(
-                                entry.path().display().to_string(),
-                            ))
-                        }
-                    };
-                    templates.push(entry.into_path())
+        .follow_links(opt.follow_links)
+        .max_depth(opt.max_depth);
+    Ok(walk.build())
+}
+
+/// Classify a single file as markdown or template, using the extensions in the options.
+/// Results contain the file in `markdown` or `templates`, or neither if it has another
+/// extension. `rel_path` of a markdown file is its file name. Include and exclude globs
+/// are not applied. Returns Error::InvalidScanFile if the path is not a file.
+pub fn scan_file(path: &Path, opt: &ScanOptions) -> Result<ScanResults> {
+    if !path.is_file() {
+        return Err(Error::InvalidScanFile(path.display().to_string()));
+    }
+    let mut entries = VecDeque::new();
+    // is_file succeeded, so there is a file name
+    let rel_path = Path::new(path.file_name().unwrap_or_default());
+    add_file(&mut entries, path, rel_path, opt, || {
+        path.metadata().map_err(|e| e.to_string())
+    })?;
+    let mut results = ScanResults::default();
+    for entry in entries {
+        results.add(entry?);
+    }
+    Ok(results)
+}
+
+/// Add markdown or template entry for the file, based on its extension.
+/// Files with other extensions are skipped.
+fn add_file<F>(
+    entries: &mut VecDeque<Result<ScanEntry>>,
+    path: &Path,
+    rel_path: &Path,
+    opt: &ScanOptions,
+    metadata: F,
+) -> Result<()>
+where
+    F: FnOnce() -> std::result::Result<std::fs::Metadata, String>,
+{
+    let file_name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return Ok(()),
+    };
+    if has_extension(&file_name, &opt.markdown_extensions) {
+        let mut mdp = MarkdownPath {
+            path: path.to_path_buf(),
+            rel_path: rel_path.to_path_buf(),
+            ..Default::default()
+        };
+        let mut metadata_error = None;
+        if opt.collect_metadata {
+            match metadata() {
+                Ok(meta) => {
+                    mdp.size = Some(meta.len());
+                    mdp.modified = meta.modified().ok();
                 }
-                _ => {}
+                Err(e) => metadata_error = Some(ScanEntry::MetadataError(mdp.path.clone(), e)),
             }
         }
+        entries.push_back(Ok(ScanEntry::Markdown(mdp)));
+        entries.extend(metadata_error.map(Ok));
+    } else if has_extension(&file_name, &opt.template_extensions) {
+        // handlebars requires template name to be unicode
+        // (we use file name as the template name).
+        match path.file_name() {
+            Some(oss) if oss.to_str().is_some() => {}
+            _ => return Err(Error::NonUnicodeFilename(path.display().to_string())),
+        };
+        entries.push_back(Ok(ScanEntry::Template(path.to_path_buf())));
+    }
+    Ok(())
+}
+
+/// Returns Error::DuplicateTemplateName if two templates have the same file name,
+/// since they would be registered with the same template name.
+fn check_template_names(templates: &[PathBuf]) -> Result<()> {
+    let mut by_name: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for path in templates.iter() {
+        // index_sources only accepts templates with unicode file names
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            by_name
+                .entry(name.to_string())
+                .or_default()
+                .push(path.clone());
+        }
+    }
+    match by_name.into_iter().find(|(_, paths)| paths.len() > 1) {
+        Some((name, paths)) => Err(Error::DuplicateTemplateName { name, paths }),
+        None => Ok(()),
     }
-    Ok(ScanResults {
-        templates,
-        markdown,
-    })
 }
 
 /// get rid of files we don't care about
@@ -173,3 +432,386 @@ fn ok_entry<E: std::fmt::Display>(res: std::result::Result<DirEntry, E>) -> Resu
     }
     Ok(entry)
 }
+
+#[test]
+fn test_summarize_frontmatter_errors() {
+    use crate::TomlMap;
+
+    let page = |path: &str, frontmatter: Result<TomlMap>| MarkdownData {
+        path: PathBuf::from("content").join(path),
+        rel_path: PathBuf::from(path),
+        frontmatter,
+    };
+    let data = vec![
+        page("a.md", Ok(TomlMap::new())),
+        page("b.md", Err(Error::FrontmatterParse("missing title".into()))),
+        page("c.md", Ok(TomlMap::new())),
+        page("d.md", Err(Error::FrontmatterParse("bad date".into()))),
+    ];
+    let errors = summarize_frontmatter_errors(&data);
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].0, PathBuf::from("content/b.md"));
+    assert!(errors[0].1.contains("missing title"));
+    assert_eq!(errors[1].0, PathBuf::from("content/d.md"));
+    assert!(errors[1].1.contains("bad date"));
+}
+
+#[test]
+fn test_sort_by_weight() {
+    use crate::TomlMap;
+
+    let page = |path: &str, weight: Option<i64>| {
+        let mut map = TomlMap::new();
+        if let Some(weight) = weight {
+            map.insert("weight".into(), weight.into());
+        }
+        MarkdownData {
+            path: PathBuf::from("content").join(path),
+            rel_path: PathBuf::from(path),
+            frontmatter: Ok(map),
+        }
+    };
+    let mut docs = vec![
+        page("zebra.md", Some(1)),
+        page("setup.md", Some(2)),
+        page("about.md", None),
+        page("intro.md", Some(1)),
+    ];
+    sort_by_weight(&mut docs, |fm: &TomlMap| {
+        fm.get("weight").and_then(|w| w.as_integer())
+    });
+    let order = docs
+        .iter()
+        .map(|md| md.rel_path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(order, vec!["intro.md", "zebra.md", "setup.md", "about.md"]);
+}
+
+#[test]
+fn test_scan_extensions() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in &[
+        "a.md",
+        "b.markdown",
+        "c.MDOWN",
+        "d.txt",
+        "page.hbs",
+        "nav.html.hbs",
+        "x.hbs.bak",
+    ] {
+        std::fs::write(dir.path().join(name), "").unwrap();
+    }
+    let names = |mut paths: Vec<PathBuf>| {
+        paths.sort();
+        paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+    };
+    let sources = vec![dir.path().to_path_buf()];
+
+    let scan = index_sources(&sources, &ScanOptions::default()).expect("scan");
+    assert_eq!(
+        names(scan.markdown.into_iter().map(|m| m.path).collect()),
+        vec!["a.md"]
+    );
+    assert_eq!(names(scan.templates), vec!["nav.html.hbs", "page.hbs"]);
+
+    let opt = ScanOptions {
+        markdown_extensions: vec!["md".into(), "markdown".into(), "mdown".into()],
+        template_extensions: vec!["html.hbs".into()],
+        ..Default::default()
+    };
+    let scan = index_sources(&sources, &opt).expect("scan");
+    assert_eq!(
+        names(scan.markdown.into_iter().map(|m| m.path).collect()),
+        vec!["a.md", "b.markdown", "c.MDOWN"]
+    );
+    assert_eq!(names(scan.templates), vec!["nav.html.hbs"]);
+}
+
+#[test]
+fn test_scan_globs() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in &[
+        "index.md",
+        "blog/one.md",
+        "blog/2021/two.md",
+        "blog/notes.txt",
+        "blog/drafts/three.md",
+        "drafts/four.md",
+    ] {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let sources = vec![dir.path().to_path_buf()];
+    let scan = |include: &[&str], exclude: &[&str]| {
+        let opt = ScanOptions {
+            include_globs: include.iter().map(|s| s.to_string()).collect(),
+            exclude_globs: exclude.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let mut paths = index_sources(&sources, &opt)
+            .expect("scan")
+            .markdown
+            .into_iter()
+            .map(|m| m.rel_path.to_string_lossy().replace('\\', "/"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    };
+
+    assert_eq!(
+        scan(&["blog/**/*.md"], &[]),
+        vec!["blog/2021/two.md", "blog/drafts/three.md", "blog/one.md"]
+    );
+    assert_eq!(
+        scan(&[], &["drafts/**"]),
+        vec![
+            "blog/2021/two.md",
+            "blog/drafts/three.md",
+            "blog/one.md",
+            "index.md"
+        ]
+    );
+    // exclusions win over inclusions
+    assert_eq!(
+        scan(&["blog/**/*.md"], &["**/drafts/**"]),
+        vec!["blog/2021/two.md", "blog/one.md"]
+    );
+    // '*' doesn't cross folders
+    assert_eq!(scan(&["*.md"], &[]), vec!["index.md"]);
+
+    let opt = ScanOptions {
+        include_globs: vec!["blog/[".into()],
+        ..Default::default()
+    };
+    assert!(matches!(
+        index_sources(&sources, &opt),
+        Err(Error::InvalidGlob(..))
+    ));
+}
+
+#[test]
+fn test_scan_metadata() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::write(dir.path().join("page.md"), "# Hello\n").unwrap();
+    let sources = vec![dir.path().to_path_buf()];
+
+    let scan = index_sources(&sources, &ScanOptions::default()).expect("scan");
+    assert_eq!(scan.markdown[0].size, None);
+    assert_eq!(scan.markdown[0].modified, None);
+
+    let opt = ScanOptions {
+        collect_metadata: true,
+        ..Default::default()
+    };
+    let scan = index_sources(&sources, &opt).expect("scan");
+    assert_eq!(scan.markdown.len(), 1);
+    assert_eq!(scan.markdown[0].size, Some(8));
+    assert!(scan.markdown[0].modified.is_some());
+    assert!(scan.metadata_errors.is_empty());
+}
+
+#[test]
+fn test_duplicate_template_names() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in &["site/page.hbs", "theme/page.hbs", "theme/post.hbs"] {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let sources = vec![dir.path().join("site"), dir.path().join("theme")];
+
+    let scan = index_sources(&sources[1..], &ScanOptions::default()).expect("scan");
+    assert_eq!(scan.templates.len(), 2);
+
+    match index_sources(&sources, &ScanOptions::default()) {
+        Err(Error::DuplicateTemplateName { name, mut paths }) => {
+            assert_eq!(name, "page.hbs");
+            paths.sort();
+            assert_eq!(
+                paths,
+                vec![sources[0].join("page.hbs"), sources[1].join("page.hbs")]
+            );
+        }
+        other => panic!("expected duplicate template error, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_scan_file() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let md = dir.path().join("page.md");
+    let hbs = dir.path().join("page.hbs");
+    let txt = dir.path().join("notes.txt");
+    for path in &[&md, &hbs, &txt] {
+        std::fs::write(path, "hello").unwrap();
+    }
+    let opt = ScanOptions {
+        collect_metadata: true,
+        ..Default::default()
+    };
+
+    let scan = scan_file(&md, &opt).expect("scan");
+    assert!(scan.templates.is_empty());
+    assert_eq!(scan.markdown.len(), 1);
+    assert_eq!(scan.markdown[0].path, md);
+    assert_eq!(scan.markdown[0].rel_path, PathBuf::from("page.md"));
+    assert_eq!(scan.markdown[0].size, Some(5));
+
+    let scan = scan_file(&hbs, &opt).expect("scan");
+    assert!(scan.markdown.is_empty());
+    assert_eq!(scan.templates, vec![hbs]);
+
+    let scan = scan_file(&txt, &opt).expect("scan");
+    assert!(scan.markdown.is_empty() && scan.templates.is_empty());
+
+    assert!(matches!(
+        scan_file(dir.path(), &opt),
+        Err(Error::InvalidScanFile(_))
+    ));
+}
+
+#[test]
+fn test_scan_iter() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in &[
+        "index.md",
+        "blog/one.md",
+        "blog/two.md",
+        "layouts/page.hbs",
+        "a.txt",
+    ] {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let sources = vec![dir.path().to_path_buf()];
+    let opt = ScanOptions {
+        exclude_globs: vec!["blog/two.md".into()],
+        ..Default::default()
+    };
+
+    let mut markdown = Vec::new();
+    let mut templates = Vec::new();
+    for entry in scan_iter(&sources, &opt) {
+        match entry.expect("entry") {
+            ScanEntry::Markdown(mdp) => markdown.push(mdp.rel_path),
+            ScanEntry::Template(path) => templates.push(path),
+            ScanEntry::MetadataError(path, e) => panic!("{}: {}", path.display(), e),
+        }
+    }
+    let scan = index_sources(&sources, &opt).expect("scan");
+    let scanned = scan
+        .markdown
+        .into_iter()
+        .map(|mdp| mdp.rel_path)
+        .collect::<Vec<_>>();
+    assert_eq!(markdown, scanned, "same files in the same order");
+    assert_eq!(templates, scan.templates);
+    markdown.sort();
+    assert_eq!(
+        markdown,
+        vec![PathBuf::from("blog/one.md"), PathBuf::from("index.md")]
+    );
+
+    let missing = vec![dir.path().join("missing")];
+    let mut iter = scan_iter(&missing, &opt);
+    assert!(matches!(iter.next(), Some(Err(Error::InvalidScanDir(_)))));
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_scan_max_depth() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for name in &[
+        "index.md",
+        "a/one.md",
+        "a/b/two.md",
+        "a/b/c/three.md",
+        "a/page.hbs",
+    ] {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let sources = vec![dir.path().to_path_buf()];
+    let scan_depth = |max_depth| {
+        let opt = ScanOptions {
+            max_depth,
+            ..Default::default()
+        };
+        let scan = index_sources(&sources, &opt).expect("scan");
+        let mut paths = scan
+            .markdown
+            .into_iter()
+            .map(|mdp| mdp.rel_path.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        paths.sort();
+        (paths, scan.templates.len())
+    };
+    assert_eq!(scan_depth(Some(1)), (vec!["index.md".to_string()], 0));
+    assert_eq!(
+        scan_depth(Some(2)),
+        (vec!["a/one.md".to_string(), "index.md".to_string()], 1)
+    );
+    assert_eq!(scan_depth(None).0.len(), 4, "no limit");
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_par_load_frontmatter() {
+    use crate::TomlMap;
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let files = || {
+        (0..20)
+            .map(|n| MarkdownPath {
+                path: dir.path().join(format!("page{}.md", n)),
+                rel_path: PathBuf::from(format!("page{}.md", n)),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>()
+    };
+    for (n, mdp) in files().iter().enumerate() {
+        let md = if n == 7 {
+            "+++\ntitle = \n+++\nbad frontmatter\n".to_string()
+        } else {
+            format!("+++\ntitle = \"Page {}\"\n+++\nbody\n", n)
+        };
+        std::fs::write(&mdp.path, md).unwrap();
+    }
+    let summary = |data: Vec<MarkdownData<TomlMap>>| {
+        data.into_iter()
+            .map(|md| {
+                let title = md
+                    .frontmatter
+                    .ok()
+                    .and_then(|fm| fm.get("title").and_then(|t| t.as_str()).map(String::from));
+                (md.rel_path, title)
+            })
+            .collect::<Vec<_>>()
+    };
+    let sequential = summary(load_frontmatter(files()).expect("load"));
+    let parallel = summary(par_load_frontmatter(files()).expect("load"));
+    assert_eq!(parallel, sequential);
+    assert_eq!(
+        parallel[3],
+        (PathBuf::from("page3.md"), Some("Page 3".into()))
+    );
+    assert_eq!(parallel[7].1, None);
+
+    // missing file is an error
+    let mut missing = files();
+    missing.push(MarkdownPath {
+        path: dir.path().join("missing.md"),
+        rel_path: PathBuf::from("missing.md"),
+        ..Default::default()
+    });
+    assert!(matches!(
+        par_load_frontmatter::<TomlMap>(missing),
+        Err(Error::Io(_))
+    ));
+}
@@ -1,11 +1,26 @@
 //! github client library for fetching content from Github
 //!
-use crate::{Error, Result};
+use crate::{
+    output::{site_path, OutputSink},
+    Error, Result, TomlMap,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+use toml::value::Value as TomlValue;
 
 const GITHUB_ENDPOINT: &str = "https://api.github.com";
 const GH_USER_AGENT: &str = "mdsite";
+/// Max number of blob requests in progress at once, for get_many_by_sha and sync_markdown.
+/// A good default for the concurrency param of get_contents_by_sha
+pub const DEFAULT_CONCURRENCY: usize = 8;
+/// Longest wait before repeating a request (for a rate limit to reset, or backoff)
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+/// Max folder depth walked when a recursive tree listing is truncated
+const MAX_TREE_DEPTH: usize = 32;
 
 /// Response from Github list-tree
 #[derive(Debug, Deserialize)]
@@ -21,7 +36,9 @@ pub struct GithubTree {
 pub struct GithubTreeItem {
     pub path: String,
     // mode: String,
-    // type: String (tree | blob | ...)
+    /// object type: "tree" (folder), "blob" (file), or "commit" (submodule)
+    #[serde(rename = "type", default)]
+    pub kind: String,
     pub sha: String,
     // url: String
 }
@@ -31,10 +48,36 @@ pub struct GithubTreeItem {
 struct ContentResponse {
     size: u64,
     sha: String,
+    /// base64 content, or empty if the file is too large for the contents api
+    #[serde(default)]
     content: String,
     encoding: String,
 }
 
+impl ContentResponse {
+    /// True if the contents api omitted the content, which it does for files over 1MB:
+    /// the encoding is "none" and the content is empty
+    fn is_truncated(&self) -> bool {
+        self.encoding == "none" || (self.content.is_empty() && self.size > 0)
+    }
+}
+
+/// Result of `get_content_by_path_if_changed`
+#[derive(Debug, PartialEq)]
+pub enum ContentStatus {
+    /// Content changed, or no etag was given
+    Changed {
+        /// file content
+        bytes: Vec<u8>,
+        /// blob sha
+        sha: String,
+        /// ETag of the response, to pass to the next request
+        etag: Option<String>,
+    },
+    /// Content hasn't changed since the response with the etag (status 304)
+    Unchanged,
+}
+
 /// A person in github api (author or committer)
 pub struct Person {
     /// Person's name
@@ -51,7 +94,23 @@ pub struct Commit<'params> {
     pub bytes: &'params Vec<u8>,
     /// branch name
     pub branch: &'params str,
-    /// sha of previous commit
+    /// blob sha of the file being replaced, or empty for a new file
+    pub prev_sha: &'params str,
+    /// Commit message
+    pub message: &'params str,
+    /// Name of committer to be written to commit log
+    pub committer_name: &'params str,
+    /// Email of committer to be written to commit log
+    pub committer_email: &'params str,
+}
+
+/// Parameters for delete request
+pub struct Delete<'params> {
+    /// path to content within repo
+    pub path: &'params str,
+    /// branch name
+    pub branch: &'params str,
+    /// blob sha of the file being deleted (required)
     pub prev_sha: &'params str,
     /// Commit message
     pub message: &'params str,
@@ -61,6 +120,46 @@ pub struct Commit<'params> {
     pub committer_email: &'params str,
 }
 
+/// Summary of a commit, from commit history
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitInfo {
+    /// commit sha
+    pub sha: String,
+    /// Author's name
+    pub author_name: String,
+    /// Author's email
+    pub author_email: String,
+    /// Author date, RFC3339 format
+    pub date: String,
+}
+
+/// item in list-commits response
+#[derive(Deserialize)]
+struct CommitListItem {
+    sha: String,
+    commit: CommitDetail,
+}
+#[derive(Deserialize)]
+struct CommitDetail {
+    author: CommitPerson,
+}
+#[derive(Deserialize)]
+struct CommitPerson {
+    name: String,
+    email: String,
+    date: String,
+}
+
+/// Result of `commit_idempotent`
+#[derive(Debug, PartialEq)]
+pub enum CommitOutcome {
+    /// Content was committed. Params are (content-sha, commit-sha)
+    Committed(String, String),
+    /// A previous attempt, whose response was lost, had already committed the content.
+    /// Param is content-sha
+    AlreadyCommitted(String),
+}
+
 /// a portion of the commit-content response containing fields we care about
 #[derive(Deserialize)]
 struct WithSha {
@@ -72,6 +171,34 @@ struct CommitResp {
     content: WithSha,
     commit: WithSha,
 }
+/// a portion of the delete-content response containing fields we care about
+#[derive(Deserialize)]
+struct DeleteResp {
+    commit: WithSha,
+}
+
+/// Retry policy for transient errors: rate limits (429, or 403 with rate limit headers)
+/// and server errors (5xx). Other errors, such as 401 or 404, are not retried.
+/// Writes (PUT and DELETE, e.g., `commit` and `delete_content`) are only retried for
+/// rate limits, since after a server error, github may have applied the change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Max number of times a request is repeated. Default 3.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each subsequent retry, if the response
+    /// doesn't say how long to wait (with `Retry-After` or `x-ratelimit-reset`).
+    /// Default 500ms.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
 
 /// Github Api client
 pub struct Github {
@@ -81,60 +208,245 @@ pub struct Github {
     owner: String,
     /// github personal api token
     api_token: String,
+    /// api base url, without trailing slash
+    endpoint: String,
+    /// http client, shared by all requests so that connections are reused
+    client: reqwest::Client,
+    /// retries for transient errors
+    retry: RetryPolicy,
+    /// timeout for each request, in addition to any timeout of the client
+    timeout: Option<Duration>,
 }
 
 impl Github {
+    /// Builder for a client with optional settings. Repo, owner, and token are required.
+    /// `Github::builder().repo("docs").owner("me").token(token).max_retries(5).build()?`
+    pub fn builder() -> GithubBuilder {
+        GithubBuilder::default()
+    }
+
     pub fn init<T: Into<String>>(repo: T, owner: T, api_token: T) -> Self {
+        Self::with_client(repo, owner, api_token, reqwest::Client::new())
+    }
+
+    /// Create Github client with a pre-configured http client (e.g., with a proxy or timeouts)
+    pub fn with_client<T: Into<String>>(
+        repo: T,
+        owner: T,
+        api_token: T,
+        client: reqwest::Client,
+    ) -> Self {
         Github {
             repo: repo.into(),
             owner: owner.into(),
             api_token: api_token.into(),
+            endpoint: GITHUB_ENDPOINT.to_string(),
+            client,
+            retry: RetryPolicy::default(),
+            timeout: None,
         }
     }
 
-    /// List objects at HEAD of specified branch that match predicate
+    /// Set retry policy for transient errors
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a different api endpoint, such as `https://github.mycorp.com/api/v3` for
+    /// Github Enterprise Server. Trailing slashes are removed.
+    /// The default is the public endpoint `https://api.github.com`.
+    pub fn with_endpoint<T: Into<String>>(mut self, endpoint: T) -> Self {
+        self.endpoint = endpoint.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// List objects at HEAD of specified branch that match predicate.
+    /// Large repos, whose listing github truncates, are listed one folder at a time.
+    /// Returns Error::GithubTruncated if the list is still incomplete.
     pub async fn list_content<P>(&self, branch: &str, predicate: P) -> Result<Vec<GithubTreeItem>>
     where
         P: Fn(&GithubTreeItem) -> bool,
     {
+        let (url, resp) = self.get_tree(branch).await?;
+        if resp.truncated {
+            return Err(Error::GithubTruncated(url));
+        }
+
+        // just get paths for content items - in the proper folder and ending with ".md"
+        let tree = resp.tree.into_iter().filter(predicate).collect();
+        Ok(tree)
+    }
+
+    /// Fetch recursive tree listing at HEAD of branch. Returns url and response.
+    /// If github truncates the listing, the tree is walked one folder at a time,
+    /// to a depth of MAX_TREE_DEPTH. The response is only truncated if a single folder
+    /// has too many entries to list, or the walk reached the depth limit.
+    async fn get_tree(&self, branch: &str) -> Result<(String, GithubTree)> {
         let url = format!(
             "{endpoint}/repos/{owner}/{repo}/git/trees/{branch}?recursive=1",
-            endpoint = GITHUB_ENDPOINT,
+            endpoint = &self.endpoint,
             owner = &self.owner,
             repo = &self.repo,
             branch = branch
         );
         let resp: GithubTree = self.get(&url).await?;
+        if !resp.truncated {
+            return Ok((url, resp));
+        }
+        let mut tree = Vec::new();
+        let mut truncated = false;
+        // folders to list: (tree-sha, path prefix, depth)
+        let mut pending = vec![(branch.to_string(), String::new(), 0)];
+        while let Some((sha, prefix, depth)) = pending.pop() {
+            let folder: GithubTree = self.get(&self.tree_url(&sha)).await?;
+            truncated |= folder.truncated;
+            for mut item in folder.tree {
+                item.path = format!("{}{}", prefix, item.path);
+                if item.kind == "tree" {
+                    if depth < MAX_TREE_DEPTH {
+                        pending.push((item.sha.clone(), format!("{}/", item.path), depth + 1));
+                    } else {
+                        truncated = true;
+                    }
+                }
+                tree.push(item);
+            }
+        }
+        tree.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok((url, GithubTree { tree, truncated }))
+    }
 
-        // just get paths for content items - in the proper folder and ending with ".md"
-        let tree = resp.tree.into_iter().filter(predicate).collect();
-        Ok(tree)
+    /// Url for non-recursive listing of tree (a branch name or tree sha)
+    fn tree_url(&self, tree: &str) -> String {
+        format!(
+            "{endpoint}/repos/{owner}/{repo}/git/trees/{tree}",
+            endpoint = &self.endpoint,
+            owner = &self.owner,
+            repo = &self.repo,
+            tree = tree
+        )
+    }
+
+    /// Fetch markdown files (ending in ".md") under the path prefix, at HEAD of the branch.
+    /// Folders and submodules are skipped, even if their names end in ".md".
+    /// Returns (path, content) for each file, ordered by path.
+    /// Blobs are fetched concurrently, up to DEFAULT_CONCURRENCY at a time, and requests
+    /// that fail with transient errors are retried (see `RetryPolicy`).
+    /// Returns Error::GithubTruncated if the file list is incomplete, because a folder
+    /// has too many entries for github to list, or is nested too deeply.
+    pub async fn sync_markdown(
+        &self,
+        branch: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        ContentSource::sync_markdown(self, branch, prefix).await
+    }
+
+    /// Retrieves github content for each SHA id, with up to `concurrency` requests
+    /// in progress at once. Results are in the same order as `blob_ids`.
+    pub async fn get_many_by_sha(
+        &self,
+        blob_ids: &[&str],
+        concurrency: usize,
+    ) -> Result<Vec<Vec<u8>>> {
+        ContentSource::get_many_by_sha(self, blob_ids, concurrency).await
+    }
+
+    /// Retrieves github content for each SHA id, like `get_many_by_sha`, but returns
+    /// a result for each blob, so that one failed request doesn't fail the others.
+    /// Results are (id, content-or-error), in the same order as `ids`.
+    pub async fn get_contents_by_sha(
+        &self,
+        ids: &[String],
+        concurrency: usize,
+    ) -> Result<Vec<(String, Result<Vec<u8>>)>> {
+        ContentSource::get_contents_by_sha(self, ids, concurrency).await
     }
 
-    /// Retrieve object by path and branch HEAD. Returns content and blob sha
+    /// Retrieve object by path and branch HEAD. Returns content and blob sha.
+    /// Files too large for the contents api (over 1MB) are fetched with the blob api,
+    /// which supports files up to 100MB. Returns Error::GithubTooLarge if that fails.
     pub async fn get_content_by_path(
         &self,
         content_path: &str,
         branch: &str,
     ) -> Result<(Vec<u8>, String)> {
-        let url = format!(
+        let url = self.content_url(content_path, branch);
+        let resp: ContentResponse = self.get(&url).await?;
+        let bytes = self.content_bytes(&url, &resp).await?;
+        Ok((bytes, resp.sha))
+    }
+
+    /// Retrieve object by path and branch HEAD, like `get_content_by_path`, unless it is
+    /// unchanged since the response with the etag. Unchanged content isn't downloaded,
+    /// and, for authenticated requests, doesn't count against the rate limit.
+    /// Store the etag of a `ContentStatus::Changed` result for the next request.
+    pub async fn get_content_by_path_if_changed(
+        &self,
+        content_path: &str,
+        branch: &str,
+        etag: Option<&str>,
+    ) -> Result<ContentStatus> {
+        let url = self.content_url(content_path, branch);
+        let mut req = self.client.get(&url);
+        if let Some(etag) = etag {
+            req = req.header("If-None-Match", etag);
+        }
+        let resp = self.send(&url, req).await?;
+        if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(ContentStatus::Unchanged);
+        }
+        let etag = resp
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let resp: ContentResponse = resp
+            .json()
+            .await
+            .map_err(|e| Error::Github(url.to_string(), e.to_string()))?;
+        let bytes = self.content_bytes(&url, &resp).await?;
+        Ok(ContentStatus::Changed {
+            bytes,
+            sha: resp.sha,
+            etag,
+        })
+    }
+
+    /// Url for contents api
+    fn content_url(&self, content_path: &str, branch: &str) -> String {
+        format!(
             "{endpoint}/repos/{owner}/{repo}/contents/{content_path}/?ref={branch}",
-            endpoint = GITHUB_ENDPOINT,
+            endpoint = &self.endpoint,
             owner = &self.owner,
             repo = &self.repo,
             content_path = content_path,
             branch = branch, // ref
-        );
-        let resp: ContentResponse = self.get(&url).await?;
-        let bytes = decode_content(&url, &resp)?;
-        Ok((bytes, resp.sha))
+        )
+    }
+
+    /// Decode content from the contents api. If the file was too large for the
+    /// contents api, it is fetched with the blob api.
+    async fn content_bytes(&self, url: &str, resp: &ContentResponse) -> Result<Vec<u8>> {
+        if resp.is_truncated() {
+            return self
+                .get_content_by_sha(&resp.sha)
+                .await
+                .map_err(|e| Error::GithubTooLarge {
+                    url: url.to_string(),
+                    size: resp.size,
+                    reason: e.to_string(),
+                });
+        }
+        decode_content(url, resp)
     }
 
     /// Retrieves github content by its SHA id
     pub async fn get_content_by_sha(&self, blob_id: &str) -> Result<Vec<u8>> {
         let url = format!(
             "{endpoint}/repos/{owner}/{repo}/git/blobs/{blob_id}",
-            endpoint = GITHUB_ENDPOINT,
+            endpoint = &self.endpoint,
             owner = &self.owner,
             repo = &self.repo,
             blob_id = blob_id
@@ -148,14 +460,14 @@ impl Github {
     /// Commit content. Result is (content-sha, commit-sha)
     pub async fn commit(&self, params: &Commit<'_>) -> Result<(String, String)> {
         let url = format!(
-            "{}/repos/{owner}/{repo}/contents/{path}",
-            GITHUB_ENDPOINT,
+            "{endpoint}/repos/{owner}/{repo}/contents/{path}",
+            endpoint = &self.endpoint,
             owner = &self.owner,
             repo = &self.repo,
             path = params.path
         );
 
-        let body = json!({
+        let mut body = json!({
             "message": params.message,
             "content": base64::encode(params.bytes),
             "sha": params.prev_sha,
@@ -165,14 +477,117 @@ impl Github {
                 "email": params.committer_email,
             }
         });
+        if params.prev_sha.is_empty() {
+            // creating a new file
+            if let Some(obj) = body.as_object_mut() {
+                obj.remove("sha");
+            }
+        }
         let resp: CommitResp = self.put(&url, &body).await?;
 
         Ok((resp.content.sha, resp.commit.sha))
     }
 
+    /// Delete content. Result is commit-sha.
+    /// Returns Error::GithubMissingSha if prev_sha is empty.
+    pub async fn delete_content(&self, params: &Delete<'_>) -> Result<String> {
+        if params.prev_sha.is_empty() {
+            return Err(Error::GithubMissingSha(params.path.to_string()));
+        }
+        let url = format!(
+            "{endpoint}/repos/{owner}/{repo}/contents/{path}",
+            endpoint = &self.endpoint,
+            owner = &self.owner,
+            repo = &self.repo,
+            path = params.path
+        );
+
+        let body = json!({
+            "message": params.message,
+            "sha": params.prev_sha,
+            "branch": params.branch,
+            "committer" : {
+                "name": params.committer_name,
+                "email": params.committer_email,
+            }
+        });
+        let resp: DeleteResp = self.delete(&url, &body).await?;
+
+        Ok(resp.commit.sha)
+    }
+
+    /// Commit content, retrying up to `retries` times if the request fails in transport
+    /// (e.g., the connection drops) or with a server error, after the policy's backoff.
+    /// Other errors, such as 401, or 422 for a stale `prev_sha`, are returned at once.
+    /// If a request fails after github has applied it (e.g., the connection drops
+    /// before the response arrives), a blind retry would commit twice. Before each retry,
+    /// this checks whether the file at the path already has the intended content,
+    /// and if so, returns `CommitOutcome::AlreadyCommitted` without committing again.
+    pub async fn commit_idempotent(
+        &self,
+        params: &Commit<'_>,
+        retries: u32,
+    ) -> Result<CommitOutcome> {
+        let intended_sha = git_blob_sha(params.bytes);
+        let mut attempt = 0;
+        loop {
+            let err = match self.commit(params).await {
+                Ok((content_sha, commit_sha)) => {
+                    return Ok(CommitOutcome::Committed(content_sha, commit_sha))
+                }
+                Err(e) => e,
+            };
+            // the response may have been lost, so the commit may have landed
+            let may_have_landed = matches!(
+                err,
+                Error::Github(..)
+                    | Error::GithubStatus {
+                        status: 500..=599,
+                        ..
+                    }
+            );
+            if !may_have_landed || attempt >= retries {
+                return Err(err);
+            }
+            tokio::time::sleep(backoff(&self.retry, attempt)).await;
+            attempt += 1;
+            // did the previous attempt land?
+            if let Ok((_, sha)) = self.get_content_by_path(params.path, params.branch).await {
+                if sha == intended_sha {
+                    return Ok(CommitOutcome::AlreadyCommitted(sha));
+                }
+            }
+        }
+    }
+
+    /// Returns the most recent commit on the branch that changed the file at path,
+    /// or None if the file has no history.
+    pub async fn last_commit(&self, path: &str, branch: &str) -> Result<Option<CommitInfo>> {
+        let base = format!(
+            "{endpoint}/repos/{owner}/{repo}/commits",
+            endpoint = &self.endpoint,
+            owner = &self.owner,
+            repo = &self.repo,
+        );
+        // path is url-encoded, since it may contain spaces, '&', or '#'
+        let url = reqwest::Url::parse_with_params(
+            &base,
+            &[("path", path), ("sha", branch), ("per_page", "1")],
+        )
+        .map_err(|e| Error::Github(base.clone(), e.to_string()))?
+        .to_string();
+        let resp: Vec<CommitListItem> = self.get(&url).await?;
+        Ok(resp.into_iter().next().map(|item| CommitInfo {
+            sha: item.sha,
+            author_name: item.commit.author.name,
+            author_email: item.commit.author.email,
+            date: item.commit.author.date,
+        }))
+    }
+
     /// Performs http GET on github url and returns deserialized object
     async fn get<Resp: DeserializeOwned>(&self, url: &str) -> Result<Resp> {
-        let obj = self.request(url, reqwest::Client::new().get(url)).await?;
+        let obj = self.request(url, self.client.get(url)).await?;
         Ok(obj)
     }
 
@@ -181,33 +596,452 @@ impl Github {
         &self,
         url: &str,
         body: &Req,
+    ) -> Result<Resp> {
+        let obj = self.request(url, self.client.put(url).json(body)).await?;
+        Ok(obj)
+    }
+
+    /// Performs http DELETE on github url and returns deserialized object
+    async fn delete<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &Req,
     ) -> Result<Resp> {
         let obj = self
-            .request(url, reqwest::Client::new().put(url).json(body))
+            .request(url, self.client.delete(url).json(body))
             .await?;
         Ok(obj)
     }
 
-    /// complete request object and deserialize result, with error handling
+    /// complete request object and deserialize result, with error handling.
+    /// If github responds with a transient error (rate limit exceeded, or a server error),
+    /// waits and repeats the request, according to the retry policy.
     async fn request<Resp: DeserializeOwned>(
         &self,
         url: &str,
         req: reqwest::RequestBuilder,
     ) -> Result<Resp> {
-        let obj = req
-            .header("Accept", "application/vnd.github.v3+json")
-            .header("Authorization", format!("token {}", self.api_token))
-            .header("User-Agent", GH_USER_AGENT)
-            .send()
-            .await
-            .map_err(|e| Error::Github(url.to_string(), e.to_string()))?
-            .error_for_status()
-            .map_err(|e| Error::Github(url.to_string(), e.to_string()))?
+        let obj = self
+            .send(url, req)
+            .await?
             .json()
             .await
             .map_err(|e| Error::Github(url.to_string(), e.to_string()))?;
         Ok(obj)
     }
+
+    /// complete request object and send it, with retries, like `request`.
+    /// Returns the response, so its status and headers can be checked, or
+    /// Error::GithubStatus if it has an error status.
+    async fn send(&self, url: &str, req: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let mut req = req
+            .header("Accept", "application/vnd.github.v3+json")
+            .header("Authorization", format!("token {}", self.api_token))
+            .header("User-Agent", GH_USER_AGENT);
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        // only reads are repeated after server errors
+        let idempotent = req
+            .try_clone()
+            .and_then(|req| req.build().ok())
+            .is_some_and(|req| req.method() == reqwest::Method::GET);
+        let mut attempt = 0;
+        let resp = loop {
+            let this_req = req
+                .try_clone()
+                .ok_or_else(|| Error::Bug(format!("request to {} can't be repeated", url)))?;
+            let resp = this_req
+                .send()
+                .await
+                .map_err(|e| Error::Github(url.to_string(), e.to_string()))?;
+            match retry_wait(&resp, attempt, &self.retry, idempotent) {
+                Some(wait) if attempt < self.retry.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(wait).await;
+                }
+                _ => break resp,
+            }
+        };
+        let status = resp.status();
+        if status.is_client_error() || status.is_server_error() {
+            return Err(Error::GithubStatus {
+                url: url.to_string(),
+                status: status.as_u16(),
+                body: resp.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(resp)
+    }
+}
+
+/// Builder for Github client, created with `Github::builder()`
+#[derive(Default)]
+pub struct GithubBuilder {
+    repo: Option<String>,
+    owner: Option<String>,
+    token: Option<String>,
+    endpoint: Option<String>,
+    client: Option<reqwest::Client>,
+    retry: RetryPolicy,
+    timeout: Option<Duration>,
+}
+
+impl GithubBuilder {
+    /// Repository name (required)
+    pub fn repo<T: Into<String>>(mut self, repo: T) -> Self {
+        self.repo = Some(repo.into());
+        self
+    }
+
+    /// Repository owner (required)
+    pub fn owner<T: Into<String>>(mut self, owner: T) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// Github personal api token (required)
+    pub fn token<T: Into<String>>(mut self, token: T) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Api endpoint, e.g., for Github Enterprise Server (see `Github::with_endpoint`)
+    pub fn endpoint<T: Into<String>>(mut self, endpoint: T) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Pre-configured http client (e.g., with a proxy)
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Max number of times a request is repeated after a transient error
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    /// Retry policy for transient errors
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Timeout for each request, from sending the request until the response body is read
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the client. Returns Error::GithubConfig if repo, owner, or token isn't set.
+    pub fn build(self) -> Result<Github> {
+        let required = |value: Option<String>, name: &str| {
+            value.ok_or_else(|| Error::GithubConfig(format!("{} is required", name)))
+        };
+        let mut github = Github::with_client(
+            required(self.repo, "repo")?,
+            required(self.owner, "owner")?,
+            required(self.token, "token")?,
+            self.client.unwrap_or_default(),
+        )
+        .with_retry_policy(self.retry);
+        if let Some(endpoint) = self.endpoint {
+            github = github.with_endpoint(endpoint);
+        }
+        github.timeout = self.timeout;
+        Ok(github)
+    }
+}
+
+/// Repository content that can be listed, read, and committed, by branch and path.
+/// Implemented by `Github`. Code that only needs these operations, such as `GithubSink`,
+/// and the provided methods `sync_markdown`, `get_many_by_sha`, and `get_contents_by_sha`,
+/// works with any `ContentSource`, so it can be used with other backends, or a mock in tests.
+/// Methods are the same as the `Github` methods with the same names.
+/// `LastUpdated` still requires `Github`, because commit history isn't part of this trait.
+///
+/// The trait isn't object-safe (methods return `impl Future`, and `list_content` is
+/// generic), so it can't be used as `dyn ContentSource`. Code is generic over the source,
+/// which is chosen at compile time.
+pub trait ContentSource: Sync {
+    /// List objects at HEAD of specified branch that match predicate
+    fn list_content<P>(
+        &self,
+        branch: &str,
+        predicate: P,
+    ) -> impl Future<Output = Result<Vec<GithubTreeItem>>> + Send
+    where
+        P: Fn(&GithubTreeItem) -> bool + Send;
+
+    /// Retrieve object by path and branch HEAD. Returns content and blob sha.
+    fn get_content_by_path(
+        &self,
+        content_path: &str,
+        branch: &str,
+    ) -> impl Future<Output = Result<(Vec<u8>, String)>> + Send;
+
+    /// Retrieves content by its SHA id
+    fn get_content_by_sha(&self, blob_id: &str) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// Commit content. Result is (content-sha, commit-sha)
+    fn commit(&self, params: &Commit<'_>) -> impl Future<Output = Result<(String, String)>> + Send;
+
+    /// Fetch markdown files (ending in ".md") under the path prefix, at HEAD of the branch.
+    /// Folders and submodules are skipped, even if their names end in ".md".
+    /// Returns (path, content) for each file, ordered by path.
+    fn sync_markdown(
+        &self,
+        branch: &str,
+        prefix: &str,
+    ) -> impl Future<Output = Result<Vec<(String, Vec<u8>)>>> + Send {
+        async move {
+            let mut items = self
+                .list_content(branch, |item| {
+                    item.kind == "blob"
+                        && item.path.starts_with(prefix)
+                        && item.path.ends_with(".md")
+                })
+                .await?;
+            items.sort_by(|a, b| a.path.cmp(&b.path));
+            let shas = items
+                .iter()
+                .map(|item| item.sha.as_str())
+                .collect::<Vec<_>>();
+            let blobs = self.get_many_by_sha(&shas, DEFAULT_CONCURRENCY).await?;
+            Ok(items.into_iter().map(|item| item.path).zip(blobs).collect())
+        }
+    }
+
+    /// Retrieves content for each SHA id, with up to `concurrency` requests
+    /// in progress at once. Results are in the same order as `blob_ids`.
+    fn get_many_by_sha(
+        &self,
+        blob_ids: &[&str],
+        concurrency: usize,
+    ) -> impl Future<Output = Result<Vec<Vec<u8>>>> + Send {
+        use futures_util::stream::{self, StreamExt, TryStreamExt};
+
+        stream::iter(blob_ids.iter().map(move |id| self.get_content_by_sha(id)))
+            .buffered(concurrency.max(1))
+            .try_collect()
+    }
+
+    /// Retrieves content for each SHA id, like `get_many_by_sha`, but returns
+    /// a result for each blob. Results are (id, content-or-error), in the same order as `ids`.
+    fn get_contents_by_sha(
+        &self,
+        ids: &[String],
+        concurrency: usize,
+    ) -> impl Future<Output = Result<Vec<(String, Result<Vec<u8>>)>>> + Send {
+        use futures_util::stream::{self, StreamExt};
+
+        let requests = ids
+            .iter()
+            .map(|id| async move { (id.clone(), self.get_content_by_sha(id).await) })
+            .collect::<Vec<_>>();
+        async move {
+            let results = stream::iter(requests)
+                .buffered(concurrency.max(1))
+                .collect()
+                .await;
+            Ok(results)
+        }
+    }
+}
+
+impl ContentSource for Github {
+    async fn list_content<P>(&self, branch: &str, predicate: P) -> Result<Vec<GithubTreeItem>>
+    where
+        P: Fn(&GithubTreeItem) -> bool + Send,
+    {
+        Github::list_content(self, branch, predicate).await
+    }
+
+    async fn get_content_by_path(
+        &self,
+        content_path: &str,
+        branch: &str,
+    ) -> Result<(Vec<u8>, String)> {
+        Github::get_content_by_path(self, content_path, branch).await
+    }
+
+    async fn get_content_by_sha(&self, blob_id: &str) -> Result<Vec<u8>> {
+        Github::get_content_by_sha(self, blob_id).await
+    }
+
+    async fn commit(&self, params: &Commit<'_>) -> Result<(String, String)> {
+        Github::commit(self, params).await
+    }
+}
+
+/// Output sink that commits generated files to a branch. Files are collected by `write`,
+/// and committed by `commit`, one commit per file.
+/// The source is usually `Github`, but can be any `ContentSource`.
+pub struct GithubSink<'gh, S: ContentSource = Github> {
+    github: &'gh S,
+    branch: String,
+    committer_name: String,
+    committer_email: String,
+    /// files written, keyed by path in repo
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl<'gh, S: ContentSource> GithubSink<'gh, S> {
+    /// Create sink for the branch
+    pub fn new<T: Into<String>>(
+        github: &'gh S,
+        branch: T,
+        committer_name: T,
+        committer_email: T,
+    ) -> Self {
+        GithubSink {
+            github,
+            branch: branch.into(),
+            committer_name: committer_name.into(),
+            committer_email: committer_email.into(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Commit the files written, with the commit message. Files whose content is already
+    /// on the branch are skipped. Returns the paths committed.
+    pub async fn commit(&self, message: &str) -> Result<Vec<String>> {
+        let files = &self.files;
+        let existing = self
+            .github
+            .list_content(&self.branch, |item| files.contains_key(&item.path))
+            .await?
+            .into_iter()
+            .map(|item| (item.path, item.sha))
+            .collect::<HashMap<_, _>>();
+        let mut committed = Vec::new();
+        for (path, bytes) in self.files.iter() {
+            let prev_sha = existing.get(path).map(String::as_str).unwrap_or_default();
+            if prev_sha == git_blob_sha(bytes) {
+                continue;
+            }
+            self.github
+                .commit(&Commit {
+                    path,
+                    bytes,
+                    branch: &self.branch,
+                    prev_sha,
+                    message,
+                    committer_name: &self.committer_name,
+                    committer_email: &self.committer_email,
+                })
+                .await?;
+            committed.push(path.clone());
+        }
+        Ok(committed)
+    }
+}
+
+impl<'gh, S: ContentSource> OutputSink for GithubSink<'gh, S> {
+    fn write(&mut self, rel_path: &Path, bytes: &[u8]) -> Result<()> {
+        self.files.insert(site_path(rel_path)?, bytes.to_vec());
+        Ok(())
+    }
+}
+
+/// Adds "last updated" render vars to pages, from each file's most recent commit.
+/// Commit history is fetched once per path, so create one of these for each build.
+pub struct LastUpdated<'gh> {
+    github: &'gh Github,
+    branch: String,
+    cache: HashMap<String, Option<CommitInfo>>,
+}
+
+impl<'gh> LastUpdated<'gh> {
+    /// Create cache for files on the branch
+    pub fn new<T: Into<String>>(github: &'gh Github, branch: T) -> Self {
+        LastUpdated {
+            github,
+            branch: branch.into(),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Returns last commit for the path, fetching it if it's not cached
+    pub async fn last_commit(&mut self, path: &str) -> Result<Option<CommitInfo>> {
+        if let Some(info) = self.cache.get(path) {
+            return Ok(info.clone());
+        }
+        let info = self.github.last_commit(path, &self.branch).await?;
+        self.cache.insert(path.to_string(), info.clone());
+        Ok(info)
+    }
+
+    /// Sets `page.updated` (RFC3339 date) and `page.updated_by` (author name) in the render
+    /// vars, from the last commit of the file at path. Other values in `page` are preserved.
+    /// If the file has no history, or `page` is not a table, the vars are not changed.
+    pub async fn add_vars(&mut self, path: &str, vars: &mut TomlMap) -> Result<()> {
+        if let Some(info) = self.last_commit(path).await? {
+            let page = vars
+                .entry("page")
+                .or_insert_with(|| TomlValue::Table(TomlMap::new()));
+            if let TomlValue::Table(page) = page {
+                page.insert("updated".into(), TomlValue::from(info.date));
+                page.insert("updated_by".into(), TomlValue::from(info.author_name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// If the response is a transient error (status 429, 403 with rate limit headers, or 5xx),
+/// returns how long to wait before repeating the request: the `Retry-After` header if present,
+/// or the time until `x-ratelimit-reset` if the rate limit is exhausted,
+/// otherwise the backoff for the attempt. The wait is limited to MAX_RETRY_WAIT.
+/// Server errors are only retried if the request is idempotent.
+fn retry_wait(
+    resp: &reqwest::Response,
+    attempt: u32,
+    policy: &RetryPolicy,
+    idempotent: bool,
+) -> Option<Duration> {
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+    };
+    let retry_after = header("retry-after")
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let rate_limited = header("x-ratelimit-remaining").as_deref() == Some("0");
+    match resp.status().as_u16() {
+        // 403 without rate limit headers is a permission error
+        403 if retry_after.is_none() && !rate_limited => return None,
+        403 | 429 => {}
+        500..=599 if idempotent => {}
+        _ => return None,
+    }
+    let reset_wait = || {
+        let reset = header("x-ratelimit-reset").and_then(|v| v.parse::<u64>().ok())?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        Some(Duration::from_secs(reset.saturating_sub(now).max(1)))
+    };
+    let wait = retry_after
+        .or_else(|| if rate_limited { reset_wait() } else { None })
+        .unwrap_or_else(|| backoff(policy, attempt));
+    Some(wait.min(MAX_RETRY_WAIT))
+}
+
+/// Wait before repeating a request: the policy's base delay, doubled for each attempt,
+/// up to MAX_RETRY_WAIT
+fn backoff(policy: &RetryPolicy, attempt: u32) -> Duration {
+    policy
+        .base_delay
+        .checked_mul(1 << attempt.min(16))
+        .unwrap_or(MAX_RETRY_WAIT)
+        .min(MAX_RETRY_WAIT)
 }
 
 /// Remove newlines from the string. The reason for this is that Github content blobs are
@@ -216,6 +1050,21 @@ fn remove_newlines(s: &str) -> String {
     s.chars().filter(|&c| c != '\n').collect()
 }
 
+/// Compute the git blob sha for content (sha1 of "blob {len}\0{content}"),
+/// which is the sha github reports for a file with that content.
+fn git_blob_sha(bytes: &[u8]) -> String {
+    use sha1::{Digest, Sha1};
+
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", bytes.len()).as_bytes());
+    hasher.update(bytes);
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
 /// base64 decode content blob
 fn decode_content(url: &str, resp: &ContentResponse) -> Result<Vec<u8>> {
     if &resp.encoding != "base64" {
@@ -228,3 +1077,852 @@ fn decode_content(url: &str, resp: &ContentResponse) -> Result<Vec<u8>> {
         .map_err(|e| Error::Base64(url.into(), e.to_string()))?;
     Ok(content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer};
+
+    #[test]
+    fn test_git_blob_sha() {
+        // same as `printf 'hello\n' | git hash-object --stdin`
+        assert_eq!(
+            git_blob_sha(b"hello\n"),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit_idempotent_after_lost_response() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let content = b"new content".to_vec();
+        let new_sha = git_blob_sha(&content);
+        let committed = Arc::new(AtomicBool::new(false));
+
+        let state = committed.clone();
+        let sha = new_sha.clone();
+        let server = MockServer::start(move |req| match req.method.as_str() {
+            // the commit is applied, but the connection drops before the response
+            "PUT" => {
+                state.store(true, Ordering::SeqCst);
+                MockResponse::Drop
+            }
+            "GET" if state.load(Ordering::SeqCst) => MockResponse::json(
+                200,
+                json!({"size": 11, "sha": sha, "content": base64::encode(b"new content"), "encoding": "base64"}),
+            ),
+            _ => MockResponse::json(404, json!({"message": "Not Found"})),
+        });
+        let mut gh = Github::init("repo", "owner", "token");
+        gh.endpoint = server.url();
+
+        let params = Commit {
+            path: "docs/page.md",
+            bytes: &content,
+            branch: "main",
+            prev_sha: "0000",
+            message: "update page",
+            committer_name: "Someone",
+            committer_email: "someone@example.com",
+        };
+        let outcome = gh.commit_idempotent(&params, 3).await.expect("commit");
+        assert_eq!(outcome, CommitOutcome::AlreadyCommitted(new_sha));
+        assert_eq!(server.count("PUT"), 1, "commit was not repeated");
+    }
+
+    #[tokio::test]
+    async fn test_commit_idempotent_permanent_error() {
+        let server =
+            MockServer::start(|_| MockResponse::json(401, json!({"message": "Bad credentials"})));
+        let mut gh = Github::init("repo", "owner", "token");
+        gh.endpoint = server.url();
+
+        let content = b"new content".to_vec();
+        let params = Commit {
+            path: "docs/page.md",
+            bytes: &content,
+            branch: "main",
+            prev_sha: "0000",
+            message: "update page",
+            committer_name: "Someone",
+            committer_email: "someone@example.com",
+        };
+        assert!(matches!(
+            gh.commit_idempotent(&params, 3).await,
+            Err(Error::GithubStatus { status: 401, .. })
+        ));
+        assert_eq!(server.count("PUT"), 1, "401 is not retried");
+        assert_eq!(server.count("GET"), 0, "no check for a landed commit");
+    }
+
+    #[tokio::test]
+    async fn test_last_updated_vars() {
+        let server = MockServer::start(|req| {
+            if req
+                .path
+                .starts_with("/repos/owner/repo/commits?path=docs%2Fa.md")
+                || req.path.contains("path=docs%2Fa+%26+b%23c.md&")
+            {
+                MockResponse::json(
+                    200,
+                    json!([{
+                        "sha": "abc123",
+                        "commit": { "author": {
+                            "name": "Alice",
+                            "email": "alice@example.com",
+                            "date": "2021-03-04T05:06:07Z"
+                        }}
+                    }]),
+                )
+            } else {
+                MockResponse::json(200, json!([]))
+            }
+        });
+        let mut gh = Github::init("repo", "owner", "token");
+        gh.endpoint = server.url();
+        let mut updated = LastUpdated::new(&gh, "main");
+
+        let mut vars = TomlMap::new();
+        updated
+            .add_vars("docs/a.md", &mut vars)
+            .await
+            .expect("vars");
+        let page = vars.get("page").and_then(|p| p.as_table()).expect("page");
+        assert_eq!(
+            page.get("updated"),
+            Some(&TomlValue::from("2021-03-04T05:06:07Z"))
+        );
+        assert_eq!(page.get("updated_by"), Some(&TomlValue::from("Alice")));
+
+        // second lookup is cached
+        let mut vars = TomlMap::new();
+        updated
+            .add_vars("docs/a.md", &mut vars)
+            .await
+            .expect("vars");
+        assert!(vars.contains_key("page"));
+        assert_eq!(server.count("GET"), 1);
+
+        // no history: vars unchanged
+        let mut vars = TomlMap::new();
+        updated
+            .add_vars("docs/new.md", &mut vars)
+            .await
+            .expect("vars");
+        assert!(vars.is_empty());
+
+        // path is encoded in the query
+        let mut vars = TomlMap::new();
+        updated
+            .add_vars("docs/a & b#c.md", &mut vars)
+            .await
+            .expect("vars");
+        assert!(vars.contains_key("page"), "encoded path found");
+
+        // page that is not a table is not replaced
+        let mut vars = TomlMap::new();
+        vars.insert("page".into(), TomlValue::from("home"));
+        updated
+            .add_vars("docs/a.md", &mut vars)
+            .await
+            .expect("vars");
+        assert_eq!(vars.get("page"), Some(&TomlValue::from("home")));
+    }
+
+    #[tokio::test]
+    async fn test_sync_markdown() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let files = [
+            ("docs/a.md", "sha-a", "# A"),
+            ("docs/b.md", "sha-b", "# B"),
+            ("docs/sub/c.md", "sha-c", "# C"),
+        ];
+        let limited = Arc::new(AtomicBool::new(false));
+        let server = MockServer::start(move |req| {
+            if req.path.starts_with("/repos/owner/repo/git/trees/main") {
+                let mut tree = files
+                    .iter()
+                    .map(|(path, sha, _)| json!({"path": path, "type": "blob", "sha": sha}))
+                    .collect::<Vec<_>>();
+                tree.push(json!({"path": "docs/image.png", "type": "blob", "sha": "sha-img"}));
+                tree.push(json!({"path": "README.md", "type": "blob", "sha": "sha-readme"}));
+                // a folder and a submodule with markdown names are skipped
+                tree.push(json!({"path": "docs/notes.md", "type": "tree", "sha": "sha-tree"}));
+                tree.push(json!({"path": "docs/lib.md", "type": "commit", "sha": "sha-sub"}));
+                return MockResponse::json(200, json!({"tree": tree, "truncated": false}));
+            }
+            // the first request for b is rate limited
+            if req.path.ends_with("/sha-b") && !limited.swap(true, Ordering::SeqCst) {
+                return MockResponse::Reply(
+                    429,
+                    vec![("Retry-After".into(), "0".into())],
+                    "{}".into(),
+                );
+            }
+            match files
+                .iter()
+                .find(|(_, sha, _)| req.path.ends_with(&format!("/{}", sha)))
+            {
+                Some((_, sha, content)) => MockResponse::json(
+                    200,
+                    json!({"size": content.len(), "sha": sha, "content": base64::encode(content), "encoding": "base64"}),
+                ),
+                None => MockResponse::json(404, json!({"message": "Not Found"})),
+            }
+        });
+        let mut gh = Github::init("repo", "owner", "token");
+        gh.endpoint = server.url();
+
+        let synced = gh.sync_markdown("main", "docs/").await.expect("sync");
+        assert_eq!(
+            synced,
+            vec![
+                ("docs/a.md".to_string(), b"# A".to_vec()),
+                ("docs/b.md".to_string(), b"# B".to_vec()),
+                ("docs/sub/c.md".to_string(), b"# C".to_vec()),
+            ]
+        );
+        // tree, 3 blobs, and one repeat after the rate limit
+        assert_eq!(server.count("GET"), 5);
+    }
+
+    #[tokio::test]
+    async fn test_list_content_truncated() {
+        let server = MockServer::start(|req| {
+            let tree = match req.path.trim_start_matches("/repos/owner/repo/git/trees/") {
+                "main?recursive=1" => {
+                    return MockResponse::json(
+                        200,
+                        json!({"tree": [{"path": "a.md", "type": "blob", "sha": "sha-a"}], "truncated": true}),
+                    )
+                }
+                "main" => json!([
+                    {"path": "a.md", "type": "blob", "sha": "sha-a"},
+                    {"path": "docs", "type": "tree", "sha": "sha-docs"},
+                ]),
+                "sha-docs" => json!([
+                    {"path": "b.md", "type": "blob", "sha": "sha-b"},
+                    {"path": "sub", "type": "tree", "sha": "sha-sub"},
+                ]),
+                "sha-sub" => json!([{"path": "c.md", "type": "blob", "sha": "sha-c"}]),
+                _ => return MockResponse::json(404, json!({"message": "Not Found"})),
+            };
+            MockResponse::json(200, json!({"tree": tree, "truncated": false}))
+        });
+        let mut gh = Github::init("repo", "owner", "token");
+        gh.endpoint = server.url();
+
+        let items = gh
+            .list_content("main", |item| item.kind == "blob")
+            .await
+            .expect("list");
+        let paths = items
+            .iter()
+            .map(|item| item.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["a.md", "docs/b.md", "docs/sub/c.md"]);
+        let items = gh.list_content("main", |_| true).await.expect("list");
+        assert_eq!(items.len(), 5, "folders included");
+    }
+
+    #[tokio::test]
+    async fn test_with_endpoint() {
+        let server = MockServer::start(|req| match req.method.as_str() {
+            "PUT" => MockResponse::json(
+                200,
+                json!({"content": {"sha": "sha-new"}, "commit": {"sha": "sha-commit"}}),
+            ),
+            _ if req.path.contains("/git/trees/") => {
+                MockResponse::json(200, json!({"tree": [], "truncated": false}))
+            }
+            _ => MockResponse::json(
+                200,
+                json!({"size": 2, "sha": "sha-a", "content": base64::encode("ok"), "encoding": "base64"}),
+            ),
+        });
+        let gh = Github::init("repo", "owner", "token")
+            .with_endpoint(format!("{}/api/v3//", server.url()));
+        assert_eq!(gh.endpoint, format!("{}/api/v3", server.url()));
+
+        gh.list_content("main", |_| true).await.expect("list");
+        gh.get_content_by_path("docs/a.md", "main")
+            .await
+            .expect("by path");
+        gh.get_content_by_sha("sha-a").await.expect("by sha");
+        let content = b"ok".to_vec();
+        gh.commit(&Commit {
+            path: "docs/a.md",
+            bytes: &content,
+            branch: "main",
+            prev_sha: "sha-a",
+            message: "update",
+            committer_name: "Someone",
+            committer_email: "someone@example.com",
+        })
+        .await
+        .expect("commit");
+
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|req| req.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "/api/v3/repos/owner/repo/git/trees/main?recursive=1",
+                "/api/v3/repos/owner/repo/contents/docs/a.md/?ref=main",
+                "/api/v3/repos/owner/repo/git/blobs/sha-a",
+                "/api/v3/repos/owner/repo/contents/docs/a.md",
+            ]
+        );
+        assert_eq!(
+            Github::init("repo", "owner", "token").endpoint,
+            GITHUB_ENDPOINT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_error() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let failures = AtomicUsize::new(0);
+        let server = MockServer::start(move |req| {
+            if req.path.ends_with("/missing") {
+                return MockResponse::json(404, json!({"message": "Not Found"}));
+            }
+            // fail twice, then succeed
+            if failures.fetch_add(1, Ordering::SeqCst) < 2 {
+                return MockResponse::json(503, json!({"message": "Service Unavailable"}));
+            }
+            MockResponse::json(
+                200,
+                json!({"size": 2, "sha": "sha-a", "content": base64::encode("ok"), "encoding": "base64"}),
+            )
+        });
+        let policy = RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+        };
+        let gh = Github::init("repo", "owner", "token")
+            .with_endpoint(server.url())
+            .with_retry_policy(policy);
+
+        let bytes = gh.get_content_by_sha("sha-a").await.expect("fetch");
+        assert_eq!(bytes, b"ok");
+        assert_eq!(server.count("GET"), 3, "two retries");
+
+        // not found is not retried
+        assert!(gh.get_content_by_sha("missing").await.is_err());
+        assert_eq!(server.count("GET"), 4);
+    }
+
+    #[tokio::test]
+    async fn test_retry_writes() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let puts = AtomicUsize::new(0);
+        let server = MockServer::start(move |req| match req.path.as_str() {
+            // rate limited once, then succeed
+            p if p.ends_with("/limited.md") => {
+                if puts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    MockResponse::json(429, json!({"message": "rate limited"}))
+                } else {
+                    MockResponse::json(
+                        200,
+                        json!({"content": {"sha": "sha-new"}, "commit": {"sha": "sha-commit"}}),
+                    )
+                }
+            }
+            _ => MockResponse::json(502, json!({"message": "Bad Gateway"})),
+        });
+        let gh = Github::init("repo", "owner", "token")
+            .with_endpoint(server.url())
+            .with_retry_policy(RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(1),
+            });
+        let bytes = b"hello".to_vec();
+        let commit = |path| Commit {
+            path,
+            bytes: &bytes,
+            branch: "main",
+            prev_sha: "",
+            message: "add",
+            committer_name: "Someone",
+            committer_email: "someone@example.com",
+        };
+
+        // github may have applied a write that failed with a server error
+        assert!(matches!(
+            gh.commit(&commit("failed.md")).await,
+            Err(Error::GithubStatus { status: 502, .. })
+        ));
+        assert_eq!(server.count("PUT"), 1, "server error not retried");
+
+        let (sha, _) = gh.commit(&commit("limited.md")).await.expect("commit");
+        assert_eq!(sha, "sha-new");
+        assert_eq!(server.count("PUT"), 3, "rate limit retried");
+    }
+
+    #[tokio::test]
+    async fn test_github_sink() {
+        let unchanged = git_blob_sha(b"<p>same</p>");
+        let server = MockServer::start(move |req| match req.method.as_str() {
+            "PUT" => MockResponse::json(
+                200,
+                json!({"content": {"sha": "sha-new"}, "commit": {"sha": "sha-commit"}}),
+            ),
+            _ => MockResponse::json(
+                200,
+                json!({"tree": [
+                    {"path": "same.html", "type": "blob", "sha": unchanged},
+                    {"path": "changed.html", "type": "blob", "sha": "sha-old"},
+                ], "truncated": false}),
+            ),
+        });
+        let gh = Github::init("repo", "owner", "token").with_endpoint(server.url());
+        let mut sink = GithubSink::new(&gh, "gh-pages", "Someone", "someone@example.com");
+        sink.write(Path::new("same.html"), b"<p>same</p>").unwrap();
+        sink.write(Path::new("changed.html"), b"<p>new</p>")
+            .unwrap();
+        sink.write(Path::new("docs/new.html"), b"<p>new</p>")
+            .unwrap();
+
+        let committed = sink.commit("build site").await.expect("commit");
+        assert_eq!(committed, vec!["changed.html", "docs/new.html"]);
+        let puts = server
+            .requests()
+            .into_iter()
+            .filter(|req| req.method == "PUT")
+            .map(|req| serde_json::from_slice::<serde_json::Value>(&req.body).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(puts[0]["sha"], "sha-old");
+        assert_eq!(puts[1].get("sha"), None, "new file has no sha");
+        assert_eq!(puts[1]["branch"], "gh-pages");
+    }
+
+    /// In-memory ContentSource: files by path, for one branch
+    #[derive(Default)]
+    struct MockSource {
+        files: std::sync::Mutex<BTreeMap<String, Vec<u8>>>,
+    }
+
+    impl MockSource {
+        fn not_found(path: &str) -> Error {
+            Error::GithubStatus {
+                url: path.to_string(),
+                status: 404,
+                body: "Not Found".into(),
+            }
+        }
+    }
+
+    impl ContentSource for MockSource {
+        async fn list_content<P>(&self, _branch: &str, predicate: P) -> Result<Vec<GithubTreeItem>>
+        where
+            P: Fn(&GithubTreeItem) -> bool + Send,
+        {
+            let files = self.files.lock().unwrap();
+            Ok(files
+                .iter()
+                .map(|(path, bytes)| GithubTreeItem {
+                    path: path.clone(),
+                    kind: "blob".into(),
+                    sha: git_blob_sha(bytes),
+                })
+                .filter(predicate)
+                .collect())
+        }
+
+        async fn get_content_by_path(
+            &self,
+            content_path: &str,
+            _branch: &str,
+        ) -> Result<(Vec<u8>, String)> {
+            let files = self.files.lock().unwrap();
+            let bytes = files
+                .get(content_path)
+                .ok_or_else(|| MockSource::not_found(content_path))?;
+            Ok((bytes.clone(), git_blob_sha(bytes)))
+        }
+
+        async fn get_content_by_sha(&self, blob_id: &str) -> Result<Vec<u8>> {
+            let files = self.files.lock().unwrap();
+            files
+                .values()
+                .find(|bytes| git_blob_sha(bytes) == blob_id)
+                .cloned()
+                .ok_or_else(|| MockSource::not_found(blob_id))
+        }
+
+        async fn commit(&self, params: &Commit<'_>) -> Result<(String, String)> {
+            let mut files = self.files.lock().unwrap();
+            let current = files.get(params.path).map(|bytes| git_blob_sha(bytes));
+            if current.as_deref().unwrap_or_default() != params.prev_sha {
+                return Err(Error::GithubStatus {
+                    url: params.path.to_string(),
+                    status: 409,
+                    body: "sha does not match".into(),
+                });
+            }
+            files.insert(params.path.to_string(), params.bytes.to_vec());
+            let sha = git_blob_sha(params.bytes);
+            Ok((sha.clone(), format!("commit-{}", sha)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_source() {
+        let source = MockSource::default();
+        source
+            .files
+            .lock()
+            .unwrap()
+            .insert("index.md".into(), b"# Home".to_vec());
+
+        let mut sink = GithubSink::new(&source, "main", "Someone", "someone@example.com");
+        sink.write(Path::new("index.md"), b"# Home").unwrap();
+        sink.write(Path::new("guide/setup.md"), b"# Setup").unwrap();
+        sink.write(Path::new("style.css"), b"body {}").unwrap();
+        let committed = sink.commit("build site").await.expect("commit");
+        assert_eq!(committed, vec!["guide/setup.md", "style.css"]);
+
+        // provided methods
+        let files = source.sync_markdown("main", "").await.expect("sync");
+        assert_eq!(
+            files,
+            vec![
+                ("guide/setup.md".to_string(), b"# Setup".to_vec()),
+                ("index.md".to_string(), b"# Home".to_vec()),
+            ]
+        );
+        let ids = vec![git_blob_sha(b"# Setup"), "missing".to_string()];
+        let results = source.get_contents_by_sha(&ids, 2).await.expect("fetch");
+        assert_eq!(results[0].1.as_deref().ok(), Some(&b"# Setup"[..]));
+        assert!(results[1].1.is_err());
+
+        let (bytes, sha) = ContentSource::get_content_by_path(&source, "style.css", "main")
+            .await
+            .expect("fetch");
+        assert_eq!(bytes, b"body {}");
+        assert_eq!(sha, git_blob_sha(b"body {}"));
+        assert!(
+            ContentSource::get_content_by_path(&source, "missing.md", "main")
+                .await
+                .is_err()
+        );
+
+        // a stale sha is rejected
+        let stale = ContentSource::commit(
+            &source,
+            &Commit {
+                path: "index.md",
+                bytes: &b"# Changed".to_vec(),
+                branch: "main",
+                prev_sha: "sha-old",
+                message: "edit",
+                committer_name: "Someone",
+                committer_email: "someone@example.com",
+            },
+        )
+        .await;
+        assert!(matches!(
+            stale,
+            Err(Error::GithubStatus { status: 409, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_error_status() {
+        let server = MockServer::start(|req| match req.header("authorization") {
+            Some("token good") => MockResponse::json(404, json!({"message": "Not Found"})),
+            _ => MockResponse::json(401, json!({"message": "Bad credentials"})),
+        });
+        let gh = Github::init("repo", "owner", "good").with_endpoint(server.url());
+        match gh.get_content_by_sha("missing").await {
+            Err(Error::GithubStatus { url, status, body }) => {
+                assert_eq!(status, 404);
+                assert!(url.ends_with("/git/blobs/missing"));
+                assert!(body.contains("Not Found"));
+            }
+            other => panic!("expected 404, got {:?}", other),
+        }
+
+        let gh = Github::init("repo", "owner", "bad").with_endpoint(server.url());
+        match gh.get_content_by_sha("sha-a").await {
+            Err(Error::GithubStatus { status, body, .. }) => {
+                assert_eq!(status, 401);
+                assert!(body.contains("Bad credentials"));
+            }
+            other => panic!("expected 401, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_content() {
+        let server = MockServer::start(|req| match req.method.as_str() {
+            "DELETE" => MockResponse::json(
+                200,
+                json!({"content": null, "commit": {"sha": "sha-commit"}}),
+            ),
+            _ => MockResponse::json(404, json!({"message": "Not Found"})),
+        });
+        let gh = Github::init("repo", "owner", "token").with_endpoint(server.url());
+        let mut params = Delete {
+            path: "docs/old.md",
+            branch: "main",
+            prev_sha: "sha-old",
+            message: "remove page",
+            committer_name: "Someone",
+            committer_email: "someone@example.com",
+        };
+        let sha = gh.delete_content(&params).await.expect("delete");
+        assert_eq!(sha, "sha-commit");
+        let req = server.requests().pop().expect("request");
+        assert_eq!(req.path, "/repos/owner/repo/contents/docs/old.md");
+        let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+        assert_eq!(body["sha"], "sha-old");
+        assert_eq!(body["message"], "remove page");
+        assert_eq!(body["committer"]["name"], "Someone");
+
+        // sha is required
+        params.prev_sha = "";
+        assert!(matches!(
+            gh.delete_content(&params).await,
+            Err(Error::GithubMissingSha(_))
+        ));
+        assert_eq!(server.count("DELETE"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_with_client() {
+        let server = MockServer::start(|req| {
+            let sha = req.path.rsplit('/').next().unwrap_or_default().to_string();
+            MockResponse::json(
+                200,
+                json!({"size": 2, "sha": sha, "content": base64::encode("ok"), "encoding": "base64"}),
+            )
+        });
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("client");
+        let mut gh = Github::with_client("repo", "owner", "token", client);
+        gh.endpoint = server.url();
+        for sha in ["sha-1", "sha-2", "sha-3"].iter() {
+            let bytes = gh.get_content_by_sha(sha).await.expect("fetch");
+            assert_eq!(bytes, b"ok");
+        }
+        assert_eq!(server.count("GET"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_contents_by_sha() {
+        let server = MockServer::start(|req| {
+            let sha = req.path.rsplit('/').next().unwrap_or_default().to_string();
+            if sha == "missing" {
+                return MockResponse::json(404, json!({"message": "Not Found"}));
+            }
+            MockResponse::json(
+                200,
+                json!({"size": 2, "sha": sha, "content": base64::encode(&sha), "encoding": "base64"}),
+            )
+        });
+        let gh = Github::init("repo", "owner", "token").with_endpoint(server.url());
+        let ids = ["sha-1", "missing", "sha-2", "sha-3"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        let results = gh
+            .get_contents_by_sha(&ids, DEFAULT_CONCURRENCY)
+            .await
+            .expect("fetch");
+        assert_eq!(results.len(), 4);
+        for ((id, result), expected) in results.iter().zip(ids.iter()) {
+            assert_eq!(id, expected, "same order as ids");
+            match result {
+                Ok(bytes) => assert_eq!(bytes, id.as_bytes()),
+                Err(Error::GithubStatus { status, .. }) => {
+                    assert_eq!(id, "missing");
+                    assert_eq!(*status, 404);
+                }
+                Err(e) => panic!("unexpected error {}", e),
+            }
+        }
+        assert!(results[1].1.is_err());
+        assert_eq!(server.count("GET"), 4);
+    }
+
+    #[tokio::test]
+    async fn test_large_file_content() {
+        let big = "x".repeat(2_000_000);
+        let blob = big.clone();
+        let server = MockServer::start(move |req| {
+            if req.path.contains("/contents/") {
+                // contents api response for a file over 1MB
+                let sha = if req.path.contains("huge") {
+                    "sha-huge"
+                } else {
+                    "sha-big"
+                };
+                MockResponse::json(
+                    200,
+                    json!({"size": 2_000_000, "sha": sha, "content": "", "encoding": "none"}),
+                )
+            } else if req.path.ends_with("/sha-big") {
+                MockResponse::json(
+                    200,
+                    json!({"size": 2_000_000, "sha": "sha-big", "content": base64::encode(&blob), "encoding": "base64"}),
+                )
+            } else {
+                MockResponse::json(
+                    403,
+                    json!({"message": "This API returns blobs up to 100 MB"}),
+                )
+            }
+        });
+        let gh = Github::init("repo", "owner", "token").with_endpoint(server.url());
+        let (bytes, sha) = gh
+            .get_content_by_path("docs/big.md", "main")
+            .await
+            .expect("fallback to blob");
+        assert_eq!(sha, "sha-big");
+        assert_eq!(bytes.len(), big.len());
+        assert_eq!(bytes, big.as_bytes());
+        assert!(server
+            .requests()
+            .iter()
+            .any(|req| req.path == "/repos/owner/repo/git/blobs/sha-big"));
+
+        match gh.get_content_by_path("docs/huge.md", "main").await {
+            Err(Error::GithubTooLarge { url, size, reason }) => {
+                assert!(url.contains("docs/huge.md"));
+                assert_eq!(size, 2_000_000);
+                assert!(reason.contains("403"), "{}", reason);
+            }
+            other => panic!(
+                "expected GithubTooLarge, got {:?}",
+                other.map(|(_, sha)| sha)
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_etag() {
+        let server = MockServer::start(|req| {
+            match req.header("if-none-match") {
+            Some("\"v2\"") => MockResponse::Reply(304, vec![("ETag".into(), "\"v2\"".into())], String::new()),
+            _ => MockResponse::Reply(
+                200,
+                vec![
+                    ("Content-Type".into(), "application/json".into()),
+                    ("ETag".into(), "\"v2\"".into()),
+                ],
+                json!({"size": 2, "sha": "sha-a", "content": base64::encode("ok"), "encoding": "base64"})
+                    .to_string(),
+            ),
+        }
+        });
+        let gh = Github::init("repo", "owner", "token").with_endpoint(server.url());
+
+        let status = gh
+            .get_content_by_path_if_changed("docs/a.md", "main", None)
+            .await
+            .expect("fetch");
+        let etag = match status {
+            ContentStatus::Changed { bytes, sha, etag } => {
+                assert_eq!(bytes, b"ok");
+                assert_eq!(sha, "sha-a");
+                etag.expect("etag")
+            }
+            ContentStatus::Unchanged => panic!("no etag sent"),
+        };
+        assert_eq!(etag, "\"v2\"");
+
+        let status = gh
+            .get_content_by_path_if_changed("docs/a.md", "main", Some(&etag))
+            .await
+            .expect("fetch");
+        assert_eq!(status, ContentStatus::Unchanged);
+
+        let status = gh
+            .get_content_by_path_if_changed("docs/a.md", "main", Some("\"v1\""))
+            .await
+            .expect("fetch");
+        assert!(
+            matches!(status, ContentStatus::Changed { .. }),
+            "stale etag"
+        );
+
+        let sent = server
+            .requests()
+            .iter()
+            .map(|req| req.header("if-none-match").map(String::from))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sent,
+            vec![None, Some("\"v2\"".to_string()), Some("\"v1\"".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                200,
+                json!({"size": 2, "sha": "sha-a", "content": base64::encode("ok"), "encoding": "base64"}),
+            )
+        });
+        let gh = Github::builder()
+            .repo("repo")
+            .owner("owner")
+            .token("token")
+            .endpoint(format!("{}/api/v3/", server.url()))
+            .timeout(Duration::from_secs(5))
+            .max_retries(1)
+            .build()
+            .expect("build");
+        assert_eq!(gh.endpoint, format!("{}/api/v3", server.url()));
+        assert_eq!(gh.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(gh.retry.max_retries, 1);
+        assert_eq!(gh.repo, "repo");
+        assert_eq!(gh.owner, "owner");
+        assert_eq!(gh.api_token, "token");
+
+        let bytes = gh.get_content_by_sha("sha-a").await.expect("fetch");
+        assert_eq!(bytes, b"ok");
+        assert_eq!(
+            server.requests()[0].path,
+            "/api/v3/repos/owner/repo/git/blobs/sha-a"
+        );
+
+        let gh = Github::builder().repo("repo").owner("owner").build();
+        assert!(matches!(gh, Err(Error::GithubConfig(msg)) if msg.contains("token")));
+        let gh = Github::builder()
+            .repo("r")
+            .owner("o")
+            .token("t")
+            .build()
+            .expect("defaults");
+        assert_eq!(gh.endpoint, GITHUB_ENDPOINT);
+        assert_eq!(gh.retry, RetryPolicy::default());
+        assert_eq!(gh.timeout, None);
+    }
+
+    #[tokio::test]
+    async fn test_sync_markdown_truncated() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, json!({"tree": [], "truncated": true})));
+        let mut gh = Github::init("repo", "owner", "token");
+        gh.endpoint = server.url();
+        assert!(matches!(
+            gh.sync_markdown("main", "").await,
+            Err(Error::GithubTruncated(_))
+        ));
+    }
+}
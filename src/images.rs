@@ -0,0 +1,132 @@
+//! Image processing for markdown documents
+//!
+use crate::resolve::{resolve_ref, RefKind};
+use pulldown_cmark::{CowStr, Event, Tag};
+use std::path::PathBuf;
+
+/// Default max size of images to inline
+pub const DEFAULT_INLINE_MAX_BYTES: u64 = 8 * 1024;
+
+/// Settings for embedding small local images as `data:` uris, for self-contained pages
+/// (offline docs, email). Remote images, and images larger than the limit, are left as links.
+#[derive(Clone, Debug)]
+pub struct InlineImages {
+    /// Directory containing the site source. Images must be inside this directory
+    pub source_dir: PathBuf,
+    /// Path of the current document, relative to source_dir (e.g., `MarkdownPath::rel_path`).
+    /// Relative image paths are resolved against this document's directory.
+    pub doc_path: PathBuf,
+    /// Max size of image file to inline, in bytes
+    pub max_bytes: u64,
+}
+
+impl InlineImages {
+    /// Settings for the document, with the default size limit
+    pub fn new<P: Into<PathBuf>>(source_dir: P, doc_path: P) -> Self {
+        Self {
+            source_dir: source_dir.into(),
+            doc_path: doc_path.into(),
+            max_bytes: DEFAULT_INLINE_MAX_BYTES,
+        }
+    }
+}
+
+/// mime type for image file extension
+fn image_mime_type(ext: &str) -> Option<&'static str> {
+    match ext.to_lowercase().as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        "webp" => Some("image/webp"),
+        "avif" => Some("image/avif"),
+        "ico" => Some("image/x-icon"),
+        _ => None,
+    }
+}
+
+/// Replace src of small local images with data uris.
+/// Images that can't be inlined because of a bad path are reported in diagnostics.
+pub(crate) fn inline_images(
+    events: &mut [Event],
+    settings: &InlineImages,
+    diagnostics: &mut Vec<String>,
+) {
+    for event in events.iter_mut() {
+        let (link_type, dest, title) = match event {
+            Event::Start(Tag::Image(link_type, dest, title)) => (*link_type, dest, title),
+            _ => continue,
+        };
+        let resolved = match resolve_ref(&settings.doc_path, dest) {
+            Ok(r) if r.kind == RefKind::Asset => r,
+            Ok(_) => continue,
+            Err(e) => {
+                diagnostics.push(format!("image not inlined: {}", e));
+                continue;
+            }
+        };
+        let path = settings.source_dir.join(&resolved.path);
+        let mime = match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(image_mime_type)
+        {
+            Some(mime) => mime,
+            None => continue,
+        };
+        match std::fs::metadata(&path) {
+            Ok(meta) if meta.len() > settings.max_bytes => continue,
+            Ok(_) => {}
+            Err(e) => {
+                diagnostics.push(format!("image '{}' not inlined: {}", dest, e));
+                continue;
+            }
+        }
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                diagnostics.push(format!("image '{}' not inlined: {}", dest, e));
+                continue;
+            }
+        };
+        let uri = format!("data:{};base64,{}", mime, base64::encode(&bytes));
+        let title = title.clone();
+        *event = Event::Start(Tag::Image(link_type, CowStr::from(uri), title));
+    }
+}
+
+#[test]
+fn test_inline_images() {
+    use crate::md_parser::{markdown_to_html, ParseOptions};
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::create_dir_all(dir.path().join("docs/img")).unwrap();
+    std::fs::write(dir.path().join("docs/img/dot.png"), b"tiny").unwrap();
+    std::fs::write(dir.path().join("docs/img/big.png"), vec![0u8; 100]).unwrap();
+
+    let mut settings = InlineImages::new(dir.path(), std::path::Path::new("docs/page.md"));
+    settings.max_bytes = 10;
+    let opt = ParseOptions {
+        inline_images: Some(settings),
+        ..Default::default()
+    };
+    let md = "![dot](img/dot.png) ![big](img/big.png) ![remote](https://example.com/x.png) ![up](../../etc/x.png)";
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(
+        html.content.contains(&format!(
+            "<img src=\"data:image/png;base64,{}\" alt=\"dot\" />",
+            base64::encode(b"tiny")
+        )),
+        "small image inlined"
+    );
+    assert!(
+        html.content
+            .contains("<img src=\"img/big.png\" alt=\"big\" />"),
+        "large image left as link"
+    );
+    assert!(html
+        .content
+        .contains("<img src=\"https://example.com/x.png\" alt=\"remote\" />"));
+    assert!(html.content.contains("<img src=\"../../etc/x.png\""));
+    assert_eq!(html.diagnostics.len(), 1, "root escape reported");
+}
@@ -1,8 +1,32 @@
+pub mod components;
+pub mod directives;
+pub mod document;
+pub mod feed;
+#[cfg(feature = "file-scan")]
 pub mod file_scan;
+#[cfg(feature = "github")]
 pub mod github;
+pub mod images;
+pub mod link_check;
 pub mod markdown;
 pub mod md_parser;
+pub mod output;
 pub mod render;
+pub mod resolve;
+pub mod search;
+#[cfg(feature = "file-scan")]
+pub mod site;
+#[cfg(feature = "file-scan")]
+pub mod site_index;
+pub mod sitemap;
+pub mod social;
+
+#[cfg(all(test, feature = "github"))]
+pub(crate) mod mock_server;
+pub(crate) mod placeholder;
+
+#[cfg(all(target_arch = "wasm32", any(feature = "github", feature = "file-scan")))]
+compile_error!("features 'github' and 'file-scan' are not supported on wasm32. Build with --no-default-features");
 
 use thiserror::Error as ThisError;
 
@@ -11,24 +35,64 @@ pub(crate) type Result<T> = std::result::Result<T, Error>;
 // Map of values that can be passed to Renderer
 pub type TomlMap = toml::value::Map<String, toml::value::Value>;
 
+/// Line ending style for generated output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    /// "\n"
+    Lf,
+    /// "\r\n"
+    CrLf,
+}
+
+impl LineEnding {
+    /// Convert all line endings in the string to this style
+    pub fn normalize(&self, s: &str) -> String {
+        let lf = s.replace("\r\n", "\n");
+        match self {
+            LineEnding::Lf => lf,
+            LineEnding::CrLf => lf.replace('\n', "\r\n"),
+        }
+    }
+}
+
 /// Errors generated by this library
 #[derive(ThisError, Debug)]
 pub enum Error {
     #[error("Parse error reading frontmatter {0}")]
     FrontmatterParse(String),
 
+    /// Handlebars errors are boxed, because they are large
     #[error("Error reading handlebars template: {0}")]
-    HandlebarsTemplate(#[from] handlebars::TemplateError),
+    HandlebarsTemplate(Box<handlebars::TemplateError>),
 
     #[error("Error processing handlebars template: {0}")]
-    HandlebarsRender(#[from] handlebars::RenderError),
+    HandlebarsRender(Box<handlebars::RenderError>),
+
+    #[error("Partial '{0}' has the same name as a template")]
+    TemplateNameCollision(String),
+
+    /// Templates in different folders have the same file name, which is used as the template name
+    #[error("Template name '{name}' is used by more than one file: {paths:?}")]
+    DuplicateTemplateName {
+        name: String,
+        paths: Vec<std::path::PathBuf>,
+    },
+
+    #[error("Template '{0}' does not reference required variables: {1}")]
+    MissingTemplateVars(String, String),
 
     #[error("Invalid scan dir :{0}")]
     InvalidScanDir(String),
 
+    #[error("Invalid scan file :{0}")]
+    InvalidScanFile(String),
+
     #[error("Scan sources parameter cannot be empty")]
     ScanNoSources,
 
+    #[error("Invalid glob pattern '{0}': {1}")]
+    InvalidGlob(String, String),
+
     #[error("Programmer error")]
     Bug(String),
 
@@ -41,12 +105,44 @@ pub enum Error {
     #[error("Toml deserialization {0}")]
     TomlDeSer(#[from] toml::de::Error),
 
+    #[error("Frontmatter serialization {0}")]
+    FrontmatterSer(String),
+
     #[error("IO Error")]
     Io(#[from] std::io::Error),
 
+    #[error("Output is not valid utf-8: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+
     #[error("Github api error for url {0}: {1}")]
     Github(String, String),
 
+    /// Github responded with an error status, e.g., 404 if the file doesn't exist,
+    /// or 401 if the api token is invalid
+    #[error("Github api error for url {url}: status {status}: {body}")]
+    GithubStatus {
+        url: String,
+        status: u16,
+        body: String,
+    },
+
+    #[error("Github client configuration: {0}")]
+    GithubConfig(String),
+
+    /// File is too large for the contents api, and couldn't be fetched with the blob api
+    #[error("Github content for url {url} is too large ({size} bytes) to download: {reason}")]
+    GithubTooLarge {
+        url: String,
+        size: u64,
+        reason: String,
+    },
+
+    #[error("Deleting {0} requires the sha of the file")]
+    GithubMissingSha(String),
+
+    #[error("Github tree listing for url {0} is truncated: the repo has too many files to list in one request")]
+    GithubTruncated(String),
+
     #[error("Decoding Github content (url {0} with base64 :{1}")]
     Base64(String, String),
 
@@ -58,4 +154,37 @@ pub enum Error {
 
     #[error("File parse error {0}: To avoid this error, add this file to a .ignore file")]
     FileParse(String),
+
+    #[error("Reference '{0}' is outside the site root")]
+    RefEscapesRoot(String),
+
+    #[error("Base url '{0}' must begin with 'http://' or 'https://'")]
+    InvalidBaseUrl(String),
+
+    #[error("Invalid component name '{0}': must begin with an upper-case letter")]
+    InvalidComponentName(String),
+
+    #[error("Unknown component '{0}'")]
+    UnknownComponent(String),
+
+    #[error("Unknown directive '{0}'")]
+    UnknownDirective(String),
+
+    #[error("Link '{0}' has empty destination")]
+    EmptyLink(String),
+
+    #[error("Frontmatter in {0} begins with '{1}' but has no closing '{1}' line. Add the closing line, or remove the opening one if it isn't frontmatter")]
+    UnterminatedFrontmatter(String, String),
+}
+
+impl From<handlebars::TemplateError> for Error {
+    fn from(e: handlebars::TemplateError) -> Self {
+        Error::HandlebarsTemplate(Box::new(e))
+    }
+}
+
+impl From<handlebars::RenderError> for Error {
+    fn from(e: handlebars::RenderError) -> Self {
+        Error::HandlebarsRender(Box::new(e))
+    }
 }
@@ -0,0 +1,196 @@
+//! Link checking - find internal links to pages or anchors that don't exist
+//!
+use crate::{
+    md_parser::ParseResult,
+    resolve::{resolve_ref, RefKind},
+};
+use pulldown_cmark::{Event, Options as MdOptions, Parser, Tag};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Page to check
+#[derive(Clone, Debug, Default)]
+pub struct Page {
+    /// Path relative to the site root, e.g., `MarkdownPath.rel_path`
+    pub rel_path: PathBuf,
+    /// Link destinations in the page
+    pub links: Vec<String>,
+    /// Anchors (element ids) in the page
+    pub anchors: Vec<String>,
+}
+
+impl Page {
+    /// Page for the markdown document, with the links in the markdown, and anchors
+    /// from the heading slugs in the parse result. Headings are only in the result if
+    /// the document has a toc, or `ParseOptions` enables `heading_anchors` or `scan_headings`.
+    pub fn from_markdown<P: Into<PathBuf>>(
+        rel_path: P,
+        markdown: &str,
+        html: &ParseResult,
+    ) -> Self {
+        let links = Parser::new_ext(markdown, MdOptions::all())
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link(_, dest, _)) => Some(dest.to_string()),
+                _ => None,
+            })
+            .collect();
+        Self {
+            rel_path: rel_path.into(),
+            links,
+            anchors: html.headings.iter().map(|h| h.slug.clone()).collect(),
+        }
+    }
+}
+
+/// Reason a link is broken
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkErrorKind {
+    /// Link target is not one of the pages
+    MissingPage,
+    /// Target page exists, but doesn't have the anchor
+    MissingAnchor,
+    /// Link target is outside the site root
+    OutsideRoot,
+}
+
+/// Broken link
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkError {
+    /// Page containing the link
+    pub page: PathBuf,
+    /// Link destination, as written
+    pub link: String,
+    /// Why the link is broken
+    pub kind: LinkErrorKind,
+}
+
+/// Check internal links and anchors in all pages, and return the broken links.
+/// A link to a page matches a page with the same path ignoring the extension, so
+/// `setup.md` and `setup.html` both match the page `setup.md`, and a link to a folder
+/// matches its `index` page. External links, and links to assets (such as images),
+/// are not checked.
+pub fn validate_links(pages: &[Page]) -> Vec<LinkError> {
+    // pages by path without extension, with '/' separators
+    let by_path = pages
+        .iter()
+        .map(|page| {
+            let anchors = page
+                .anchors
+                .iter()
+                .map(String::as_str)
+                .collect::<HashSet<_>>();
+            (page_key(&page.rel_path), anchors)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut errors = Vec::new();
+    for page in pages.iter() {
+        let key = page_key(&page.rel_path);
+        for link in page.links.iter() {
+            let error = |kind| LinkError {
+                page: page.rel_path.clone(),
+                link: link.clone(),
+                kind,
+            };
+            let resolved = match resolve_ref(&page.rel_path, link) {
+                Ok(resolved) => resolved,
+                Err(_) => {
+                    errors.push(error(LinkErrorKind::OutsideRoot));
+                    continue;
+                }
+            };
+            let target = match resolved.kind {
+                RefKind::Anchor => by_path.get(&key),
+                RefKind::Internal => {
+                    let path = Path::new(&resolved.path);
+                    let target = by_path
+                        .get(&page_key(path))
+                        .or_else(|| by_path.get(&page_key(&path.join("index"))));
+                    match target {
+                        Some(target) => Some(target),
+                        None => {
+                            errors.push(error(LinkErrorKind::MissingPage));
+                            continue;
+                        }
+                    }
+                }
+                RefKind::External | RefKind::Asset => continue,
+            };
+            if let (Some(anchors), Some(fragment)) = (target, resolved.fragment.as_deref()) {
+                if !fragment.is_empty() && !anchors.contains(fragment) {
+                    errors.push(error(LinkErrorKind::MissingAnchor));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Path without extension, with '/' separators, e.g., "guide/setup"
+fn page_key(path: &Path) -> String {
+    path.with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[test]
+fn test_validate_links() {
+    use crate::md_parser::{markdown_to_html, ParseOptions};
+
+    let opt = ParseOptions::builder().scan_headings(true).build();
+    let page = |rel_path: &str, md: &str| {
+        let html = markdown_to_html(md, &opt).expect("parse");
+        Page::from_markdown(rel_path, md, &html)
+    };
+    let pages = vec![
+        page(
+            "index.md",
+            "# Home\n\n[good](guide/setup.html#install) [dead](guide/missing.md) \
+             [dead anchor](guide/setup.md#uninstall) [self](#home) [ext](https://example.com) \
+             [folder](guide/)",
+        ),
+        page(
+            "guide/setup.md",
+            "# Setup\n\n## Install\n\n[up](../index.md) [outside](../../x.md) [nope](#nope)",
+        ),
+        page("guide/index.md", "# Guide"),
+    ];
+    let errors = validate_links(&pages);
+    let summary = errors
+        .iter()
+        .map(|e| {
+            (
+                e.page.to_string_lossy().to_string(),
+                e.link.as_str(),
+                e.kind.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "index.md".to_string(),
+                "guide/missing.md",
+                LinkErrorKind::MissingPage
+            ),
+            (
+                "index.md".to_string(),
+                "guide/setup.md#uninstall",
+                LinkErrorKind::MissingAnchor
+            ),
+            (
+                "guide/setup.md".to_string(),
+                "../../x.md",
+                LinkErrorKind::OutsideRoot
+            ),
+            (
+                "guide/setup.md".to_string(),
+                "#nope",
+                LinkErrorKind::MissingAnchor
+            ),
+        ]
+    );
+}
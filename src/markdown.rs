@@ -1,7 +1,7 @@
 //! Markdown processing
 //!
-use crate::{Error, Result, TomlMap};
-use serde::{de::DeserializeOwned, Serialize};
+use crate::{Error, LineEnding, Result, TomlMap};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use toml::value::Value;
 
 /// tokens to indicate frontmatter metadata
@@ -9,11 +9,15 @@ pub(crate) const TOML_START: &str = "+++\n";
 pub(crate) const TOML_END: &str = "\n+++\n";
 pub(crate) const YAML_START: &str = "---\n";
 pub(crate) const YAML_END: &str = "\n---\n";
+pub(crate) const JSON_START: &str = ";;;\n";
+pub(crate) const JSON_END: &str = "\n;;;\n";
 
 #[derive(Debug, PartialEq)]
 pub enum Frontmatter<'md> {
     Toml(&'md str),
     Yaml(&'md str),
+    /// Json object, between `;;;` lines
+    Json(&'md str),
     Empty,
 }
 
@@ -31,6 +35,9 @@ impl<'md> Frontmatter<'md> {
             Self::Yaml(buf) => Ok(
                 serde_yaml::from_str(buf).map_err(|e| Error::FrontmatterParse(e.to_string()))?
             ),
+            Self::Json(buf) => Ok(
+                serde_json::from_str(buf).map_err(|e| Error::FrontmatterParse(e.to_string()))?
+            ),
             Self::Empty => Err(Error::FrontmatterParse("no content".into())),
         }
     }
@@ -38,10 +45,11 @@ impl<'md> Frontmatter<'md> {
     /// parses to TomlValue
     pub fn to_toml(&self) -> Result<TomlMap> {
         let val = match self {
-            Self::Toml(toml_buf) => toml::from_str(*toml_buf)?,
-            Self::Yaml(yaml_buf) => serde_yaml::from_str::<toml::Value>(yaml_buf).map_err(|e| {
-                Error::FrontmatterParse(format!("yaml frontmatter: {}", e.to_string()))
-            })?,
+            Self::Toml(toml_buf) => toml::from_str(toml_buf)?,
+            Self::Yaml(yaml_buf) => serde_yaml::from_str::<toml::Value>(yaml_buf)
+                .map_err(|e| Error::FrontmatterParse(format!("yaml frontmatter: {}", e)))?,
+            Self::Json(json_buf) => serde_json::from_str::<toml::Value>(json_buf)
+                .map_err(|e| Error::FrontmatterParse(format!("json frontmatter: {}", e)))?,
             Self::Empty => return Ok(TomlMap::new()),
         };
         if let Value::Table(t) = val {
@@ -52,28 +60,144 @@ impl<'md> Frontmatter<'md> {
     }
 }
 
+/// Frontmatter parsed into a typed struct `T`, plus all keys that `T` doesn't declare.
+/// This lets authors add ad-hoc fields without changing the struct. Use it anywhere a
+/// frontmatter type is expected, e.g., `parse_frontmatter::<WithExtra<Page>>(front)`
+/// or `load_frontmatter::<WithExtra<Page>>(files)`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WithExtra<T> {
+    /// Fields recognized by `T`
+    #[serde(flatten)]
+    pub data: T,
+    /// Unrecognized fields
+    #[serde(flatten)]
+    pub extra: TomlMap,
+}
+
+/// Frontmatter start and end lines, e.g., "+++"
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delimiters {
+    /// start token, including the newline
+    start: String,
+    /// end token, including the newlines before and after
+    end: String,
+}
+
+impl Delimiters {
+    /// Frontmatter with the same start and end line, e.g., `Delimiters::new("+++")`
+    pub fn new(line: &str) -> Self {
+        Self::pair(line, line)
+    }
+
+    /// Frontmatter with different start and end lines
+    pub fn pair(start: &str, end: &str) -> Self {
+        Self {
+            start: format!("{}\n", start),
+            end: format!("\n{}\n", end),
+        }
+    }
+}
+
+/// Frontmatter delimiters for each format. A format whose delimiters are None is not detected.
+/// The default is `+++` for toml, `---` for yaml, and `;;;` for json.
+/// Disable yaml to treat a leading `---` as a horizontal rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrontmatterConfig {
+    pub toml: Option<Delimiters>,
+    pub yaml: Option<Delimiters>,
+    pub json: Option<Delimiters>,
+}
+
+impl Default for FrontmatterConfig {
+    fn default() -> Self {
+        Self {
+            toml: Some(Delimiters {
+                start: TOML_START.to_string(),
+                end: TOML_END.to_string(),
+            }),
+            yaml: Some(Delimiters {
+                start: YAML_START.to_string(),
+                end: YAML_END.to_string(),
+            }),
+            json: Some(Delimiters {
+                start: JSON_START.to_string(),
+                end: JSON_END.to_string(),
+            }),
+        }
+    }
+}
+
+/// Constructor for Frontmatter variant
+type MakeFrontmatter<'md> = fn(&'md str) -> Frontmatter<'md>;
+
 /// Split markdown file into Frontmatter and content.
 /// Both have leading and trailing whitespace removed
-pub fn split_markdown(markdown: &str) -> (Frontmatter, &str) {
-    if markdown.starts_with(TOML_START) {
-        let (front, body) = remove_frontmatter(markdown, TOML_START, TOML_END);
-        let front = if !front.is_empty() {
-            Frontmatter::Toml(front)
-        } else {
-            Frontmatter::Empty
-        };
-        (front, body)
-    } else if markdown.starts_with(YAML_START) {
-        let (front, body) = remove_frontmatter(markdown, YAML_START, YAML_END);
-        let front = if !front.is_empty() {
-            Frontmatter::Yaml(front)
-        } else {
-            Frontmatter::Empty
-        };
-        (front, body)
-    } else {
-        (Frontmatter::Empty, markdown)
+pub fn split_markdown(markdown: &str) -> (Frontmatter<'_>, &str) {
+    split_markdown_with(markdown, &FrontmatterConfig::default())
+}
+
+/// Split markdown file into Frontmatter and content, using the configured delimiters.
+/// Both have leading and trailing whitespace removed
+pub fn split_markdown_with<'md>(
+    markdown: &'md str,
+    config: &FrontmatterConfig,
+) -> (Frontmatter<'md>, &'md str) {
+    let formats: [(&Option<Delimiters>, MakeFrontmatter<'md>); 3] = [
+        (&config.toml, Frontmatter::Toml),
+        (&config.yaml, Frontmatter::Yaml),
+        (&config.json, Frontmatter::Json),
+    ];
+    for (delimiters, format) in formats.iter() {
+        if let Some(delim) = delimiters {
+            if markdown.starts_with(&delim.start) {
+                let (front, body) = remove_frontmatter(markdown, &delim.start, &delim.end);
+                let front = if !front.is_empty() {
+                    format(front)
+                } else {
+                    Frontmatter::Empty
+                };
+                return (front, body);
+            }
+        }
+    }
+    (Frontmatter::Empty, markdown)
+}
+
+/// Split markdown file into Frontmatter and content, like `split_markdown`, but
+/// if the file begins with a frontmatter start delimiter (`+++`, `---`, or `;;;`) that has
+/// no matching end delimiter, returns Error::UnterminatedFrontmatter instead of treating
+/// the whole file as content. `path` is used in the error message.
+pub fn split_markdown_strict<'md>(
+    markdown: &'md str,
+    path: &str,
+) -> Result<(Frontmatter<'md>, &'md str)> {
+    split_markdown_strict_with(markdown, path, &FrontmatterConfig::default())
+}
+
+/// Split markdown file into Frontmatter and content, like `split_markdown_with`, but
+/// returns Error::UnterminatedFrontmatter if the file begins with a configured start
+/// delimiter that has no matching end delimiter. `path` is used in the error message.
+pub fn split_markdown_strict_with<'md>(
+    markdown: &'md str,
+    path: &str,
+    config: &FrontmatterConfig,
+) -> Result<(Frontmatter<'md>, &'md str)> {
+    for delim in [&config.toml, &config.yaml, &config.json]
+        .iter()
+        .copied()
+        .flatten()
+    {
+        if markdown.starts_with(&delim.start)
+            && !frontmatter_rest(markdown, &delim.start)
+                .is_some_and(|rest| rest.contains(&delim.end))
+        {
+            return Err(Error::UnterminatedFrontmatter(
+                path.to_string(),
+                delim.start.trim().to_string(),
+            ));
+        }
     }
+    Ok(split_markdown_with(markdown, config))
 }
 
 /// Parse frontmatter to known data structure.
@@ -88,6 +212,11 @@ pub fn parse_frontmatter<T: DeserializeOwned>(front: Frontmatter) -> Result<T> {
                 serde_yaml::from_str(data).map_err(|e| Error::FrontmatterParse(e.to_string()))?;
             Ok(ghd)
         }
+        Frontmatter::Json(data) => {
+            let ghd =
+                serde_json::from_str(data).map_err(|e| Error::FrontmatterParse(e.to_string()))?;
+            Ok(ghd)
+        }
         Frontmatter::Empty => Err(Error::FrontmatterParse(
             "markdown file is missing header".into(),
         )),
@@ -97,19 +226,24 @@ pub fn parse_frontmatter<T: DeserializeOwned>(front: Frontmatter) -> Result<T> {
 /// Parse frontmatter to known data structure.
 pub fn parse_frontmatter_to_map(front: Frontmatter) -> Result<TomlMap> {
     match front {
-        Frontmatter::Toml(data) => {
-            match data
-                .parse::<Value>()
+        Frontmatter::Toml(data) => parse_toml(data),
+        Frontmatter::Yaml(data) => {
+            match serde_yaml::from_str::<serde_yaml::Value>(data)
                 .map_err(|e| Error::FrontmatterParse(e.to_string()))?
             {
-                Value::Table(table) => Ok(table),
-                _ => Err(Error::FrontmatterParse("Expected toml values".to_string())),
+                value @ serde_yaml::Value::Mapping(_) => serde_yaml::from_value(value)
+                    .map_err(|e| Error::FrontmatterParse(e.to_string())),
+                serde_yaml::Value::Sequence(_) => Err(Error::FrontmatterParse(
+                    "Expected yaml mapping, found a list".to_string(),
+                )),
+                _ => Err(Error::FrontmatterParse(
+                    "Expected yaml mapping, found a single value".to_string(),
+                )),
             }
         }
-        Frontmatter::Yaml(data) => {
-            // does this work?
+        Frontmatter::Json(data) => {
             let ghd =
-                serde_yaml::from_str(data).map_err(|e| Error::FrontmatterParse(e.to_string()))?;
+                serde_json::from_str(data).map_err(|e| Error::FrontmatterParse(e.to_string()))?;
             Ok(ghd)
         }
         Frontmatter::Empty => Err(Error::FrontmatterParse(
@@ -118,6 +252,18 @@ pub fn parse_frontmatter_to_map(front: Frontmatter) -> Result<TomlMap> {
     }
 }
 
+/// Parse a toml document, such as a config or data file, into a map.
+/// Returns Error::FrontmatterParse if the toml is invalid, or its top level isn't a table.
+pub fn parse_toml(s: &str) -> Result<TomlMap> {
+    match s
+        .parse::<Value>()
+        .map_err(|e| Error::FrontmatterParse(e.to_string()))?
+    {
+        Value::Table(table) => Ok(table),
+        _ => Err(Error::FrontmatterParse("Expected toml values".to_string())),
+    }
+}
+
 /// Split the markdown file into header and body strings based on start/end tags
 /// Both strings have leading and trailing whitespace removed
 fn remove_frontmatter<'md>(
@@ -125,18 +271,31 @@ fn remove_frontmatter<'md>(
     start: &'_ str,
     end: &'_ str,
 ) -> (&'md str, &'md str) {
-    if markdown.starts_with(start) {
-        // to allow "+++\n+++\n" for empty frontmatter, subtract one from start index
-        let rest = &markdown[start.len() - 1..];
+    if let Some(rest) = frontmatter_rest(markdown, start) {
         if let Some(end_ix) = rest.find(end) {
-            let front = (&rest[..end_ix]).trim();
-            let back = (&rest[end_ix + end.len()..]).trim();
-            return (front, back);
+            // find returns a char boundary, and end is a whole str, so these can't fail,
+            // but use checked slices so malformed input never panics
+            if let (Some(front), Some(back)) = (rest.get(..end_ix), rest.get(end_ix + end.len()..))
+            {
+                return (front.trim(), back.trim());
+            }
         }
     }
     ("", markdown)
 }
 
+/// If markdown begins with the start delimiter, returns the text after it,
+/// including the delimiter's last character (the newline),
+/// to allow "+++\n+++\n" for empty frontmatter.
+/// Returns None if markdown doesn't begin with start, or if start is empty.
+fn frontmatter_rest<'md>(markdown: &'md str, start: &str) -> Option<&'md str> {
+    if !markdown.starts_with(start) {
+        return None;
+    }
+    let last_len = start.chars().last()?.len_utf8();
+    markdown.get(start.len().checked_sub(last_len)?..)
+}
+
 /// Convert markdown header metadata to toml header (with +++ prefix/suffix)
 fn make_toml_frontmatter<T: Serialize>(data: &T) -> Result<String> {
     Ok(format!(
@@ -147,15 +306,75 @@ fn make_toml_frontmatter<T: Serialize>(data: &T) -> Result<String> {
     ))
 }
 
+/// Convert markdown header metadata to yaml header (with --- prefix/suffix)
+fn make_yaml_frontmatter<T: Serialize>(data: &T) -> Result<String> {
+    let yaml = serde_yaml::to_string(data).map_err(|e| Error::FrontmatterSer(e.to_string()))?;
+    // serde_yaml starts the document with "---"
+    let yaml = yaml.strip_prefix(YAML_START).unwrap_or(&yaml);
+    Ok(format!("{}{}{}", YAML_START, yaml.trim_end(), YAML_END))
+}
+
+/// Convert markdown header metadata to json header (with ;;; prefix/suffix)
+fn make_json_frontmatter<T: Serialize>(data: &T) -> Result<String> {
+    let json =
+        serde_json::to_string_pretty(data).map_err(|e| Error::FrontmatterSer(e.to_string()))?;
+    Ok(format!("{}{}{}", JSON_START, json, JSON_END))
+}
+
+/// Format of frontmatter written by write_markdown_with
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FrontmatterFormat {
+    /// toml, between `+++` lines (default)
+    #[default]
+    Toml,
+    /// yaml, between `---` lines
+    Yaml,
+    /// json, between `;;;` lines
+    Json,
+}
+
+/// Options for write_markdown_with
+#[derive(Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// Line endings of output file. If None (the default), frontmatter uses "\n"
+    /// and content is written as-is.
+    pub line_ending: Option<LineEnding>,
+    /// Frontmatter format (default toml)
+    pub format: FrontmatterFormat,
+}
+
 /// Writes toml metadata + content markdown to output file
 pub fn write_markdown<T: Serialize, W: std::io::Write>(
     data: &T,
     content: &str,
     writer: &mut W,
 ) -> Result<()> {
-    let toml_header = make_toml_frontmatter(data)?;
-    writer.write_all(toml_header.as_bytes())?;
-    writer.write_all(content.as_bytes())?;
+    write_markdown_with(data, content, &WriteOptions::default(), writer)
+}
+
+/// Writes metadata + content markdown to output file, with options.
+/// Metadata is written in the format of `opt.format`.
+pub fn write_markdown_with<T: Serialize, W: std::io::Write>(
+    data: &T,
+    content: &str,
+    opt: &WriteOptions,
+    writer: &mut W,
+) -> Result<()> {
+    let header = match opt.format {
+        FrontmatterFormat::Toml => make_toml_frontmatter(data)?,
+        FrontmatterFormat::Yaml => make_yaml_frontmatter(data)?,
+        FrontmatterFormat::Json => make_json_frontmatter(data)?,
+    };
+    match opt.line_ending {
+        Some(eol) => {
+            writer.write_all(eol.normalize(&header).as_bytes())?;
+            writer.write_all(eol.normalize(content).as_bytes())?;
+        }
+        None => {
+            writer.write_all(header.as_bytes())?;
+            writer.write_all(content.as_bytes())?;
+        }
+    }
     writer.flush()?;
     Ok(())
 }
@@ -233,6 +452,39 @@ fn test_split() {
     assert_eq!(body, "hello");
 }
 
+#[test]
+fn test_split_with() {
+    let doc = "---\ntitle: x\n---\nbody";
+    let (front, body) = split_markdown_with(doc, &FrontmatterConfig::default());
+    assert_eq!(front, Frontmatter::Yaml("title: x"));
+    assert_eq!(body, "body");
+
+    // yaml disabled: leading "---" is content (a horizontal rule)
+    let config = FrontmatterConfig {
+        yaml: None,
+        ..Default::default()
+    };
+    let (front, body) = split_markdown_with(doc, &config);
+    assert_eq!(front, Frontmatter::Empty);
+    assert_eq!(body, doc);
+
+    // custom delimiters
+    let config = FrontmatterConfig {
+        toml: Some(Delimiters::pair("<!--toml", "-->")),
+        yaml: Some(Delimiters::new("~~~yaml")),
+        json: None,
+    };
+    let (front, body) = split_markdown_with("<!--toml\ntitle = \"x\"\n-->\nbody", &config);
+    assert_eq!(front, Frontmatter::Toml("title = \"x\""));
+    assert_eq!(body, "body");
+    let (front, _) = split_markdown_with("~~~yaml\ntitle: x\n~~~yaml\nbody", &config);
+    assert_eq!(front, Frontmatter::Yaml("title: x"));
+    let (front, _) = split_markdown_with("+++\ntitle = \"x\"\n+++\nbody", &config);
+    assert_eq!(front, Frontmatter::Empty, "default toml delimiter replaced");
+    let (front, _) = split_markdown_with(";;;\n{}\n;;;\nbody", &config);
+    assert_eq!(front, Frontmatter::Empty, "json disabled");
+}
+
 #[test]
 fn test_toml_parse() {
     // parse with comments, blank lines, and variables
@@ -248,6 +500,22 @@ count = 99
     let map = front.to_toml().expect("toml parse frontmatter");
     assert_eq!(map.get("boo"), Some(Value::from("baz")).as_ref());
     assert_eq!(map.get("count"), Some(Value::from(99)).as_ref());
+
+    let map = parse_toml("# comment\nboo = \"baz\"\n\ncount = 99\n").expect("parse_toml");
+    assert_eq!(map.get("boo"), Some(Value::from("baz")).as_ref());
+    assert_eq!(map.get("count"), Some(Value::from(99)).as_ref());
+    assert!(parse_toml("").expect("empty").is_empty());
+}
+
+#[test]
+fn test_parse_toml_errors() {
+    for bad in &["boo = ", "[table\nx = 1", "x = 1\nx = 2"] {
+        assert!(
+            matches!(parse_toml(bad), Err(Error::FrontmatterParse(_))),
+            "{:?}",
+            bad
+        );
+    }
 }
 
 #[test]
@@ -266,3 +534,280 @@ count: 99
     assert_eq!(map.get("boo"), Some(Value::from("baz")).as_ref());
     assert_eq!(map.get("count"), Some(Value::from(99)).as_ref());
 }
+
+#[test]
+fn test_yaml_not_mapping() {
+    let err = |md: &str| match parse_frontmatter_to_map(split_markdown(md).0) {
+        Err(Error::FrontmatterParse(msg)) => msg,
+        other => panic!("expected parse error, got {:?}", other),
+    };
+    assert_eq!(
+        err("---\n- one\n- two\n---\nbody\n"),
+        "Expected yaml mapping, found a list"
+    );
+    assert_eq!(
+        err("---\njust text\n---\nbody\n"),
+        "Expected yaml mapping, found a single value"
+    );
+    let map =
+        parse_frontmatter_to_map(split_markdown("---\ntitle: Hi\n---\nbody\n").0).expect("mapping");
+    assert_eq!(map.get("title"), Some(&Value::from("Hi")));
+}
+
+#[test]
+fn test_json_parse() {
+    let file = ";;;\n{\n  \"boo\": \"baz\",\n  \"count\": 99\n}\n;;;\n# Title\n";
+    let (front, body) = split_markdown(file);
+    assert_eq!(
+        front,
+        Frontmatter::Json("{\n  \"boo\": \"baz\",\n  \"count\": 99\n}")
+    );
+    assert_eq!(body, "# Title");
+    let map = front.to_toml().expect("json parse frontmatter");
+    assert_eq!(map.get("boo"), Some(Value::from("baz")).as_ref());
+    assert_eq!(map.get("count"), Some(Value::from(99)).as_ref());
+    let map = parse_frontmatter_to_map(split_markdown(file).0).expect("map");
+    assert_eq!(map.get("count"), Some(Value::from(99)).as_ref());
+
+    // empty
+    let (front, body) = split_markdown(";;;\n;;;\nhello");
+    assert_eq!(front, Frontmatter::Empty);
+    assert_eq!(body, "hello");
+    let (front, _) = split_markdown(";;;\n{}\n;;;\nhello");
+    assert_eq!(front.to_toml().expect("empty object"), TomlMap::new());
+
+    // no frontmatter: documents starting with a code fence or brace are content
+    for doc in ["```\n;;;\ncode\n```\n", "{ not frontmatter }\n"].iter() {
+        let (front, body) = split_markdown(doc);
+        assert_eq!(front, Frontmatter::Empty);
+        assert_eq!(&body, doc);
+    }
+}
+
+#[test]
+fn test_parse_with_extra() {
+    #[derive(Deserialize)]
+    struct Page {
+        title: String,
+    }
+
+    let (front, _) = split_markdown("+++\ntitle = \"Hello\"\ncolor = \"blue\"\n+++\nbody");
+    let page = parse_frontmatter::<WithExtra<Page>>(front).expect("parse toml with extra");
+    assert_eq!(page.data.title, "Hello");
+    assert_eq!(page.extra.get("color"), Some(&Value::from("blue")));
+    assert!(
+        page.extra.get("title").is_none(),
+        "known fields aren't duplicated"
+    );
+
+    let (front, _) = split_markdown("---\ntitle: Hello\ncolor: blue\n---\nbody");
+    let page = parse_frontmatter::<WithExtra<Page>>(front).expect("parse yaml with extra");
+    assert_eq!(page.data.title, "Hello");
+    assert_eq!(page.extra.get("color"), Some(&Value::from("blue")));
+}
+
+#[test]
+fn test_write_formats() {
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Page {
+        title: String,
+        tags: Vec<String>,
+        weight: i64,
+    }
+    let page = Page {
+        title: "Hi: there".into(),
+        tags: vec!["a".into(), "b".into()],
+        weight: 2,
+    };
+
+    let mut buf = Vec::new();
+    let opt = WriteOptions {
+        format: FrontmatterFormat::Yaml,
+        ..Default::default()
+    };
+    write_markdown_with(&page, "# Hello\n", &opt, &mut buf).expect("write");
+    let doc = String::from_utf8(buf).unwrap();
+    assert!(doc.starts_with("---\ntitle:"), "{}", doc);
+    let (front, body) = split_markdown(&doc);
+    assert!(matches!(front, Frontmatter::Yaml(_)));
+    assert_eq!(body, "# Hello");
+    assert_eq!(parse_frontmatter::<Page>(front).expect("parse"), page);
+
+    let mut buf = Vec::new();
+    let opt = WriteOptions {
+        format: FrontmatterFormat::Json,
+        ..Default::default()
+    };
+    write_markdown_with(&page, "# Hello\n", &opt, &mut buf).expect("write");
+    let doc = String::from_utf8(buf).unwrap();
+    let (front, body) = split_markdown(&doc);
+    assert!(matches!(front, Frontmatter::Json(_)));
+    assert_eq!(body, "# Hello");
+    assert_eq!(parse_frontmatter::<Page>(front).expect("parse"), page);
+}
+
+#[test]
+fn test_write_line_ending() {
+    #[derive(Serialize)]
+    struct Page {
+        title: String,
+    }
+    let page = Page { title: "Hi".into() };
+
+    let mut buf = Vec::new();
+    let opt = WriteOptions {
+        line_ending: Some(LineEnding::CrLf),
+        ..Default::default()
+    };
+    write_markdown_with(&page, "one\ntwo\r\n", &opt, &mut buf).expect("write");
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        "+++\r\ntitle = \"Hi\"\r\n\r\n+++\r\none\r\ntwo\r\n"
+    );
+
+    let mut buf = Vec::new();
+    let opt = WriteOptions {
+        line_ending: Some(LineEnding::Lf),
+        ..Default::default()
+    };
+    write_markdown_with(&page, "one\r\ntwo\r\n", &opt, &mut buf).expect("write");
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        "+++\ntitle = \"Hi\"\n\n+++\none\ntwo\n"
+    );
+}
+
+#[test]
+fn test_split_strict() {
+    // lenient: unterminated frontmatter is body
+    let (front, body) = split_markdown("+++\nhello");
+    assert_eq!(front, Frontmatter::Empty);
+    assert_eq!(body, "+++\nhello");
+
+    // strict: unterminated frontmatter is an error
+    assert!(matches!(
+        split_markdown_strict("+++\nhello", "page.md"),
+        Err(Error::UnterminatedFrontmatter(path, delim)) if path == "page.md" && delim == "+++"
+    ));
+    assert!(matches!(
+        split_markdown_strict("---\ntitle: x\n", "page.md"),
+        Err(Error::UnterminatedFrontmatter(_, delim)) if delim == "---"
+    ));
+
+    // strict: terminated and empty frontmatter, and no frontmatter, behave as lenient
+    let (front, body) = split_markdown_strict("+++\nthing = \"one\"\n+++\nhello", "x").unwrap();
+    assert_eq!(front, Frontmatter::Toml("thing = \"one\""));
+    assert_eq!(body, "hello");
+    let (front, body) = split_markdown_strict("+++\n+++\nhello", "x").unwrap();
+    assert_eq!(front, Frontmatter::Empty);
+    assert_eq!(body, "hello");
+    let (front, body) = split_markdown_strict("hello", "x").unwrap();
+    assert_eq!(front, Frontmatter::Empty);
+    assert_eq!(body, "hello");
+
+    // configured delimiters
+    let config = FrontmatterConfig {
+        toml: Some(Delimiters::pair("<<<", ">>>")),
+        yaml: None,
+        ..Default::default()
+    };
+    assert!(matches!(
+        split_markdown_strict_with("<<<\na = 1\n", "page.md", &config),
+        Err(Error::UnterminatedFrontmatter(_, ref d)) if d == "<<<"
+    ));
+    let (front, body) = split_markdown_strict_with("---\nhello", "x", &config).unwrap();
+    assert_eq!(front, Frontmatter::Empty, "yaml disabled");
+    assert_eq!(body, "---\nhello");
+    let (front, _) = split_markdown_strict_with("<<<\na = 1\n>>>\nhi", "x", &config).unwrap();
+    assert_eq!(front, Frontmatter::Toml("a = 1"));
+}
+
+#[test]
+fn test_split_no_panic() {
+    // multibyte characters adjacent to delimiters
+    let md = "+++\ntitle = \"é\"\n+++\n日本語";
+    assert_eq!(
+        split_markdown(md),
+        (Frontmatter::Toml("title = \"é\""), "日本語")
+    );
+    let md = "---\n日: 本\n---\n語";
+    assert_eq!(split_markdown(md), (Frontmatter::Yaml("日: 本"), "語"));
+    assert_eq!(split_markdown("+++\n+++\né"), (Frontmatter::Empty, "é"));
+    assert_eq!(split_markdown("++é\n"), (Frontmatter::Empty, "++é\n"));
+
+    // every prefix of documents with multibyte text and delimiters must split without panic
+    for md in [
+        "+++\nt = \"日本\"\n+++\n語é",
+        "---\né: ü\n---\n💖\n",
+        "+++\n+++\n",
+        "---\n\n---\n",
+    ]
+    .iter()
+    {
+        for (ix, _) in md.char_indices() {
+            let (_, body) = split_markdown(&md[..ix]);
+            assert!(md.contains(body));
+            let _ = split_markdown_strict(&md[..ix], "test.md");
+        }
+    }
+
+    // truncated delimiters are content, or unterminated in strict mode
+    assert_eq!(split_markdown("+++"), (Frontmatter::Empty, "+++"));
+    assert_eq!(
+        split_markdown("+++\na = 1\n++"),
+        (Frontmatter::Empty, "+++\na = 1\n++")
+    );
+    assert!(matches!(
+        split_markdown_strict("---\na: 1\n--", "x.md"),
+        Err(Error::UnterminatedFrontmatter(..))
+    ));
+    assert_eq!(remove_frontmatter("日本", "", "\n"), ("", "日本"));
+}
+
+#[test]
+fn test_split_random_inputs() {
+    // property test in place of a fuzz target: documents built from random sequences of
+    // delimiters, partial delimiters, newlines, and multibyte characters always split
+    // without panicking, and the body is a slice of the document, starting and ending
+    // at char boundaries. (It isn't always a suffix, because trailing whitespace after
+    // frontmatter is trimmed.)
+    const PARTS: &[&str] = &[
+        "+++", "---", ";;;", "++", "-", "\n", "\r\n", " ", "a = 1", "é", "日本", "💖", "{}",
+    ];
+    let config = FrontmatterConfig {
+        toml: Some(Delimiters::pair("<<<", ">>>")),
+        ..Default::default()
+    };
+    let is_slice = |md: &str, body: &str| {
+        let start = (body.as_ptr() as usize).wrapping_sub(md.as_ptr() as usize);
+        start <= md.len() && md.is_char_boundary(start) && md[start..].starts_with(body)
+    };
+    let (_, body) = split_markdown("+++\n+++\né ");
+    assert_eq!(body, "é");
+    assert!(is_slice("+++\n+++\né ", body));
+
+    // xorshift, so failures are reproducible
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..5000 {
+        let len = (next() % 12) as usize;
+        let md = (0..len)
+            .map(|_| PARTS[(next() % PARTS.len() as u64) as usize])
+            .collect::<String>();
+        let (_, body) = split_markdown(&md);
+        assert!(is_slice(&md, body), "{:?}", md);
+        let (_, body) = split_markdown_with(&md, &config);
+        assert!(is_slice(&md, body), "{:?}", md);
+        if let Ok((_, body)) = split_markdown_strict(&md, "random.md") {
+            assert!(is_slice(&md, body), "{:?}", md);
+        }
+        if let Ok((_, body)) = split_markdown_strict_with(&md, "random.md", &config) {
+            assert!(is_slice(&md, body), "{:?}", md);
+        }
+    }
+}
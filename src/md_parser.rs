@@ -1,28 +1,784 @@
 //! Markdown parser - parses markdown and generates html
 //! Also generates TOC if the markdown contains a toc-generation flag
 //!
-use crate::Result;
-use pulldown_cmark::{Event, Options as MdOptions, Parser, Tag};
+//! The flag `<!-- toc -->` generates the TOC. A page may request additional TOCs,
+//! each of which is generated separately, with flags such as `<!-- toc-sidebar -->`.
+//! A flag may limit the TOC depth, e.g., `<!-- toc depth=2 -->`.
+//! `ParseOptions.toc_marker` replaces `<!-- toc -->` with another marker, such as
+//! `[[TOC]]`, for documents written for other tools. With `ParseOptions.inline_toc`,
+//! the TOC also replaces the flag (or marker) in the content.
+//!
+//! The flag `<!-- toc-section -->` is replaced, in place, by a TOC of the
+//! sub-headings of the h1 section containing it. Its depth starts at h2, or at
+//! `ParseOptions.min_toc_depth` if that is deeper.
+//!
+//! With `ParseOptions.admonitions`, a blockquote starting with a github-style alert
+//! marker, such as `> [!NOTE]`, is converted to
+//! `<div class="admonition note"><p class="admonition-title">Note</p>...</div>`.
+//!
+//! A heading may set its id explicitly, with a trailing attribute: `## Setup {#install}`.
+//! Explicit ids take precedence over generated slugs: if a generated slug is the same
+//! as an explicit id, the slug gets a numeric suffix. Collisions involving explicit ids
+//! are reported in the diagnostics.
+//!
+use crate::{images::InlineImages, Error, LineEnding, Result};
+use pulldown_cmark::{CodeBlockKind, Event, Options as MdOptions, Parser, Tag};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 
-/// Max depth of generated TOC: 3 is usually enough, 4 is bordering on excessive
+/// Default max depth of generated TOC: 3 is usually enough, 4 is bordering on excessive
 const MAX_TOC_DEPTH: u8 = 4;
-/// Flag in markdown to generate TOC
+/// Default min depth of generated TOC
+const MIN_TOC_DEPTH: u8 = 1;
+/// Start of flag in markdown to generate TOC
+const TOC_FLAG_START: &str = "<!-- toc";
+/// Name of the default TOC
+const TOC_NAME: &str = "toc";
+/// Flag for the default TOC, without parameters, and the default `toc_marker`
 const TOC_FLAG: &str = "<!-- toc -->";
+/// Name of flag that takes the place of a custom toc marker, for inline tocs
+const MARKER_TOC_NAME: &str = "toc-marker";
+/// Name of flag for TOC of the enclosing h1 section
+const SECTION_TOC_NAME: &str = "toc-section";
+/// Admonition types recognized by default, from github alerts
+const ADMONITION_TYPES: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+/// Default reading speed, for ParseResult.reading_time_minutes
+const WORDS_PER_MINUTE: u32 = 200;
+/// Default marker separating the excerpt from the rest of the document
+const EXCERPT_MARKER: &str = "<!-- more -->";
+const COMMENT_END: &str = "-->";
 
-// use div and p instead of ul and li - better typography
+// TocStyle::DivP uses div and p instead of ul and li - better typography
 // stylesheet adds a left margin to each div to make it indented
 const TOC_INDENT: &str = "<div>";
 const TOC_END_INDENT: &str = "</div>";
 const TOC_ITEM: &str = "<p>";
 const TOC_END_ITEM: &str = "</p>";
+const TOC_LIST: &str = "<ul>";
+const TOC_END_LIST: &str = "</ul>";
+const TOC_LIST_ITEM: &str = "<li>";
+const TOC_END_LIST_ITEM: &str = "</li>";
+
+/// Options for markdown parsing.
+/// Create with `ParseOptions::default()`, or with the builder:
+/// `ParseOptions::builder().toc_depth(2).rewrite_md_links(true).build()`
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// Line endings of generated html. If None (the default), line endings are
+    /// as generated by the parser.
+    pub line_ending: Option<LineEnding>,
+    /// Html inserted before and after generated content, for example,
+    /// `("<article class=\"prose\">", "</article>")`. Default is no wrapper.
+    pub content_wrapper: Option<(String, String)>,
+    /// How heading anchors are generated (default: `id` attribute)
+    pub anchor_style: AnchorStyle,
+    /// Handling of links with empty destinations, like `[text]()` (default: use "#")
+    pub empty_links: EmptyLinks,
+    /// If set, small local images are embedded as data uris (default: None)
+    pub inline_images: Option<InlineImages>,
+    /// Fenced code blocks whose language is a key in this map are emitted with the wrapper,
+    /// instead of `<pre><code>`, so that client-side renderers (e.g., mermaid, graphviz)
+    /// can process them. Other languages are emitted as normal code blocks.
+    pub code_block_wrappers: HashMap<String, CodeBlockWrapper>,
+    /// Classes added to generated elements, keyed by element name, for theming frameworks.
+    /// Supported elements are `p`, `h1`..`h6`, `ul`, `ol`, `table`, `blockquote`, and `a`.
+    /// Elements without an entry are unchanged.
+    pub element_classes: HashMap<String, String>,
+    /// Convert blockquotes that start with an alert marker, such as `> [!NOTE]`,
+    /// to admonitions. The marker must be alone on the first line. Recognized types are
+    /// NOTE, TIP, IMPORTANT, WARNING, CAUTION, and those in `admonition_classes`.
+    /// Blockquotes with other markers are unchanged. Default false.
+    pub admonitions: bool,
+    /// Class for each admonition type, keyed by lower case type, e.g., ("warning", "is-danger"),
+    /// used in place of the type name in `class="admonition warning"`. Keys that aren't
+    /// standard types add new types.
+    pub admonition_classes: HashMap<String, String>,
+    /// Deepest heading level included in TOCs. Default is 4.
+    /// A `depth=N` parameter in a toc flag overrides this for that TOC.
+    pub max_toc_depth: Option<u8>,
+    /// Shallowest heading level included in TOCs. Default is 1.
+    /// Use 2 to omit an h1 page title from the TOC.
+    pub min_toc_depth: Option<u8>,
+    /// Rewrite links to `.md` files to `.html`, keeping any query and fragment,
+    /// e.g., `./a/b.md#sec` becomes `./a/b.html#sec`.
+    /// Absolute links (with a url scheme, such as `https:` or `mailto:`, or beginning
+    /// with '/'), and anchors, are not changed. Default false.
+    pub rewrite_md_links: bool,
+    /// Add `target="_blank" rel="noopener noreferrer"` to links to external sites
+    /// (destinations with a url scheme, such as `https:` or `mailto:`, or protocol-relative
+    /// `//host/...`, as classified by `resolve`), so they open in a new tab.
+    /// Default false.
+    pub external_links_new_tab: bool,
+    /// Url prefixes of the site itself, e.g., `https://example.com/`. Links starting with
+    /// one of these are internal, and are not changed by `external_links_new_tab`.
+    pub internal_hosts: Vec<String>,
+    /// Add a link to each heading's anchor inside the heading, using `heading_anchor` markup.
+    /// When enabled, headings get ids even if the page has no toc flag. Default false.
+    pub heading_anchors: bool,
+    /// Markup for heading anchor links, used if `heading_anchors` is true
+    pub heading_anchor: HeadingAnchor,
+    /// Scan headings into `ParseResult.headings` even if the document has no toc flag,
+    /// e.g., for building navigation. This doesn't change the generated html. Default false.
+    pub scan_headings: bool,
+    /// Keep non-ascii letters and digits in heading slugs, so "日本語" has the anchor
+    /// "#日本語". Default false: slugs are ascii, and "Привет" has the anchor "#privet".
+    pub unicode_slugs: bool,
+    /// Treat a trailing `{#id}` in heading text as text, instead of as the heading's id.
+    /// Default false: `## Setup {#install}` has the id "install".
+    pub ignore_heading_ids: bool,
+    /// Footnotes: references `[^note]` and definitions `[^note]: text`.
+    /// Default: definitions are collected in a section at the end of the document.
+    pub footnotes: Footnotes,
+    /// Convert straight quotes to curly quotes, `--` and `---` to en and em dashes,
+    /// and `...` to an ellipsis. Code is not changed. Default false.
+    pub smart_punctuation: bool,
+    /// Convert bare urls starting with `http://` or `https://` in text to links.
+    /// Urls in code, and in link text, are not changed. Default false.
+    pub autolink: bool,
+    /// Markup for generated tocs. Default `<div>` and `<p>`.
+    pub toc_style: TocStyle,
+    /// Replace the `<!-- toc -->` flag (or the configured `toc_marker`) in the content with the TOC,
+    /// so the TOC appears where the author placed it. `ParseResult.toc` is still set.
+    /// Default false: the flag is removed, and templates place the TOC.
+    pub inline_toc: bool,
+    /// Marker line that ends the excerpt (`ParseResult.excerpt`). Default `<!-- more -->`.
+    pub excerpt_marker: Option<String>,
+    /// Marker line that requests the TOC. Default `<!-- toc -->`, which may have
+    /// parameters, e.g., `<!-- toc depth=2 -->`. Another marker, such as `[[TOC]]` or
+    /// `{:toc}`, replaces it: the marker must be on a line by itself, and `<!-- toc -->`
+    /// is left in the content. Named toc flags, such as `<!-- toc-sidebar -->`,
+    /// work with any marker.
+    pub toc_marker: String,
+    /// Reading speed used for `ParseResult.reading_time_minutes`. Default 200.
+    pub words_per_minute: Option<u32>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            line_ending: None,
+            content_wrapper: None,
+            anchor_style: AnchorStyle::default(),
+            empty_links: EmptyLinks::default(),
+            inline_images: None,
+            code_block_wrappers: HashMap::new(),
+            element_classes: HashMap::new(),
+            admonitions: false,
+            admonition_classes: HashMap::new(),
+            max_toc_depth: None,
+            min_toc_depth: None,
+            rewrite_md_links: false,
+            external_links_new_tab: false,
+            internal_hosts: Vec::new(),
+            heading_anchors: false,
+            heading_anchor: HeadingAnchor::default(),
+            scan_headings: false,
+            unicode_slugs: false,
+            ignore_heading_ids: false,
+            footnotes: Footnotes::default(),
+            smart_punctuation: false,
+            autolink: false,
+            toc_style: TocStyle::default(),
+            inline_toc: false,
+            excerpt_marker: None,
+            toc_marker: TOC_FLAG.to_string(),
+            words_per_minute: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Returns true if the toc marker is the default `<!-- toc -->` flag
+    fn default_toc_marker(&self) -> bool {
+        self.toc_marker.trim() == TOC_FLAG
+    }
+}
+
+impl ParseOptions {
+    /// Builder for options, starting from the defaults
+    pub fn builder() -> ParseOptionsBuilder {
+        ParseOptionsBuilder::default()
+    }
+}
+
+/// Builder for `ParseOptions`. Fields not set keep their default values.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptionsBuilder {
+    opt: ParseOptions,
+}
+
+impl ParseOptionsBuilder {
+    /// Line endings of generated html
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.opt.line_ending = Some(line_ending);
+        self
+    }
+
+    /// Html inserted before and after generated content
+    pub fn content_wrapper<T: Into<String>>(mut self, before: T, after: T) -> Self {
+        self.opt.content_wrapper = Some((before.into(), after.into()));
+        self
+    }
+
+    /// How heading anchors are generated
+    pub fn anchor_style(mut self, anchor_style: AnchorStyle) -> Self {
+        self.opt.anchor_style = anchor_style;
+        self
+    }
+
+    /// Handling of links with empty destinations
+    pub fn empty_links(mut self, empty_links: EmptyLinks) -> Self {
+        self.opt.empty_links = empty_links;
+        self
+    }
+
+    /// Embed small local images as data uris
+    pub fn inline_images(mut self, settings: InlineImages) -> Self {
+        self.opt.inline_images = Some(settings);
+        self
+    }
+
+    /// Emit fenced code blocks in the language with the wrapper. May be called
+    /// once for each language.
+    pub fn code_block_wrapper<T: Into<String>>(
+        mut self,
+        lang: T,
+        wrapper: CodeBlockWrapper,
+    ) -> Self {
+        self.opt.code_block_wrappers.insert(lang.into(), wrapper);
+        self
+    }
+
+    /// Add class to generated elements, e.g., ("table", "table is-striped").
+    /// May be called once for each element.
+    pub fn element_class<T: Into<String>>(mut self, element: T, class: T) -> Self {
+        self.opt
+            .element_classes
+            .insert(element.into(), class.into());
+        self
+    }
+
+    /// Convert `> [!NOTE]` style blockquotes to admonitions
+    pub fn admonitions(mut self, enable: bool) -> Self {
+        self.opt.admonitions = enable;
+        self
+    }
+
+    /// Set class for an admonition type, e.g., ("warning", "is-danger").
+    /// May be called once for each type.
+    pub fn admonition_class<T: Into<String>>(mut self, kind: T, class: T) -> Self {
+        self.opt
+            .admonition_classes
+            .insert(kind.into().to_lowercase(), class.into());
+        self
+    }
+
+    /// Deepest heading level included in TOCs (sets `max_toc_depth`)
+    pub fn toc_depth(mut self, depth: u8) -> Self {
+        self.opt.max_toc_depth = Some(depth);
+        self
+    }
+
+    /// Shallowest heading level included in TOCs
+    pub fn min_toc_depth(mut self, depth: u8) -> Self {
+        self.opt.min_toc_depth = Some(depth);
+        self
+    }
+
+    /// Rewrite links to `.md` files to `.html`
+    pub fn rewrite_md_links(mut self, enable: bool) -> Self {
+        self.opt.rewrite_md_links = enable;
+        self
+    }
+
+    /// Open external links in a new tab
+    pub fn external_links_new_tab(mut self, enable: bool) -> Self {
+        self.opt.external_links_new_tab = enable;
+        self
+    }
+
+    /// Add url prefix of the site, for links that are not external
+    pub fn internal_host<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.opt.internal_hosts.push(prefix.into());
+        self
+    }
+
+    /// Add anchor links to headings
+    pub fn heading_anchors(mut self, enable: bool) -> Self {
+        self.opt.heading_anchors = enable;
+        self
+    }
+
+    /// Markup for heading anchor links. Implies `heading_anchors(true)`
+    pub fn heading_anchor(mut self, anchor: HeadingAnchor) -> Self {
+        self.opt.heading_anchors = true;
+        self.opt.heading_anchor = anchor;
+        self
+    }
+
+    /// Scan headings into `ParseResult.headings` even if the document has no toc flag
+    pub fn scan_headings(mut self, enable: bool) -> Self {
+        self.opt.scan_headings = enable;
+        self
+    }
+
+    /// Keep non-ascii letters in heading slugs
+    pub fn unicode_slugs(mut self, enable: bool) -> Self {
+        self.opt.unicode_slugs = enable;
+        self
+    }
+
+    /// Use a trailing `{#id}` in heading text as the heading's id (enabled by default)
+    pub fn heading_ids(mut self, enable: bool) -> Self {
+        self.opt.ignore_heading_ids = !enable;
+        self
+    }
+
+    /// Replace the toc flag in the content with the TOC
+    pub fn inline_toc(mut self, enable: bool) -> Self {
+        self.opt.inline_toc = enable;
+        self
+    }
+
+    /// Markup for generated tocs
+    pub fn toc_style(mut self, style: TocStyle) -> Self {
+        self.opt.toc_style = style;
+        self
+    }
+
+    /// Footnote handling
+    pub fn footnotes(mut self, footnotes: Footnotes) -> Self {
+        self.opt.footnotes = footnotes;
+        self
+    }
+
+    /// Convert quotes, dashes, and ellipses to typographic punctuation
+    pub fn smart_punctuation(mut self, enable: bool) -> Self {
+        self.opt.smart_punctuation = enable;
+        self
+    }
+
+    /// Convert bare urls to links
+    pub fn autolink(mut self, enable: bool) -> Self {
+        self.opt.autolink = enable;
+        self
+    }
+
+    /// Reading speed, for estimating reading time
+    pub fn words_per_minute(mut self, wpm: u32) -> Self {
+        self.opt.words_per_minute = Some(wpm);
+        self
+    }
+
+    /// Marker line that ends the excerpt
+    pub fn excerpt_marker<T: Into<String>>(mut self, marker: T) -> Self {
+        self.opt.excerpt_marker = Some(marker.into());
+        self
+    }
+
+    /// Marker line that requests the TOC, in place of `<!-- toc -->`
+    pub fn toc_marker<T: Into<String>>(mut self, marker: T) -> Self {
+        self.opt.toc_marker = marker.into();
+        self
+    }
+
+    /// Returns the options
+    pub fn build(self) -> ParseOptions {
+        self.opt
+    }
+}
+
+/// Position of anchor link inside the heading element
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AnchorPosition {
+    /// Before the heading text (default)
+    #[default]
+    Start,
+    /// After the heading text
+    End,
+}
+
+/// Markup for heading anchor links
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeadingAnchor {
+    /// Html for the link. `{slug}` is replaced with the heading's id.
+    /// Default: `<a class="anchor" href="#{slug}" aria-hidden="true">#</a>`
+    pub template: String,
+    /// Position of link inside heading
+    pub position: AnchorPosition,
+}
+
+impl Default for HeadingAnchor {
+    fn default() -> Self {
+        Self {
+            template: r##"<a class="anchor" href="#{slug}" aria-hidden="true">#</a>"##.to_string(),
+            position: AnchorPosition::Start,
+        }
+    }
+}
+
+impl HeadingAnchor {
+    /// Html of link for heading
+    fn html(&self, slug: &str) -> String {
+        self.template.replace("{slug}", slug)
+    }
+}
+
+/// Element and class that enclose a code block's source, e.g., `<pre class="mermaid">`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeBlockWrapper {
+    /// html element name, e.g., "pre" or "div"
+    pub element: String,
+    /// value of class attribute
+    pub class: String,
+    /// If true, the source is inserted as-is, without html escaping, for renderers that
+    /// read the element's html rather than its text. The source should not contain
+    /// the element's closing tag. Default false.
+    pub raw: bool,
+}
+
+impl CodeBlockWrapper {
+    /// Create wrapper with element and class. The source is html-escaped.
+    pub fn new<T: Into<String>>(element: T, class: T) -> Self {
+        Self {
+            element: element.into(),
+            class: class.into(),
+            raw: false,
+        }
+    }
+
+    /// Create wrapper with element and class, that passes the source through without escaping,
+    /// e.g., `CodeBlockWrapper::raw("div", "mermaid")`
+    pub fn raw<T: Into<String>>(element: T, class: T) -> Self {
+        Self {
+            raw: true,
+            ..Self::new(element, class)
+        }
+    }
+}
+
+/// Handling of links with an empty destination
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EmptyLinks {
+    /// Replace destination with "#"
+    #[default]
+    Hash,
+    /// Leave destination empty
+    Empty,
+    /// Replace destination with "#", and add a message to `ParseResult.diagnostics`
+    Diagnostic,
+    /// Fail with Error::EmptyLink
+    Error,
+}
+
+/// Handling of footnotes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Footnotes {
+    /// Footnote definitions are moved, in order of first reference, to the end of
+    /// the document, in `<section class="footnotes">`
+    #[default]
+    Section,
+    /// Footnote definitions are left where they appear in the source
+    Inline,
+    /// Footnote syntax is not recognized, and `[^note]` is plain text
+    Disabled,
+}
+
+/// Markup for generated tocs
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TocStyle {
+    /// Nested `<div>` for each level, with `<p>` items. The stylesheet adds
+    /// a left margin to each div to indent it.
+    #[default]
+    DivP,
+    /// Nested `<ul>` lists, with `<li>` items, for list semantics in screen readers
+    /// and other accessibility tools. A skipped heading level (h2 followed by h4)
+    /// is nested one level deeper.
+    UnorderedList,
+}
+
+/// Markup for heading anchors
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AnchorStyle {
+    /// `<h2 id="slug">`
+    #[default]
+    Id,
+    /// `<a name="slug"></a><h2>`, for older browsers and readers
+    Name,
+    /// `<a name="slug"></a><h2 id="slug">`
+    Both,
+}
 
 /// html result from markdown parser
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ParseResult {
     /// markdown content converted to html
     pub content: String,
     /// table of contents, if toc flag was found in source
     pub toc: Option<String>,
+    /// All tables of contents requested by flags in the source, keyed by flag name
+    /// (e.g., "toc", "toc-sidebar"). Includes the default toc, if present.
+    pub tocs: HashMap<String, String>,
+    /// Document headings, in document order. Headings are scanned if the document
+    /// has a toc flag, or if heading anchors or `scan_headings` are enabled,
+    /// otherwise this is empty.
+    pub headings: Vec<HeadingInfo>,
+    /// Problems found in the markdown that didn't prevent conversion
+    pub diagnostics: Vec<String>,
+    /// Source and html byte ranges of top-level blocks, used by `markdown_to_html_incremental`.
+    /// Empty if the block ranges aren't known, e.g., if line endings were normalized.
+    pub blocks: Vec<BlockSpan>,
+    /// Html of the document before the excerpt marker (`<!-- more -->`, on its own line),
+    /// or None if the document has no marker. The marker is removed from `content`.
+    pub excerpt: Option<String>,
+    /// Number of words in the document text, not including code or html
+    pub word_count: usize,
+    /// Estimated reading time, in minutes (rounded up), at `ParseOptions.words_per_minute`
+    pub reading_time_minutes: u32,
+}
+
+/// Byte ranges of a top-level block (paragraph, list, etc.) in markdown source and html content
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockSpan {
+    /// range in markdown source
+    pub source: Range<usize>,
+    /// range in html content
+    pub html: Range<usize>,
+}
+
+/// Document heading (h1, h2, ...)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HeadingInfo {
+    /// heading level (1-6)
+    pub level: u8,
+    /// heading text
+    pub text: String,
+    /// anchor slug, the heading element's id
+    pub slug: String,
+}
+
+impl From<&Heading> for HeadingInfo {
+    fn from(h: &Heading) -> Self {
+        HeadingInfo {
+            level: h.level,
+            text: h.text.clone(),
+            slug: h.slug.clone(),
+        }
+    }
+}
+
+/// TOC requested by a flag in the markdown
+#[derive(Debug, PartialEq)]
+struct TocFlag {
+    /// flag name, e.g., "toc" or "toc-sidebar"
+    name: String,
+    /// max heading level, if specified
+    depth: Option<u8>,
+}
+
+/// Remove TOC flags from html markup. Returns the remaining markup and the flags found.
+/// Flags have the form `<!-- toc[-name] [depth=N] -->`.
+/// Section toc flags, and default toc flags if inline_toc is true, are returned,
+/// but left in the markup, to be replaced by the toc.
+/// If default_flag is false (another toc marker is used), default toc flags are
+/// left in the markup, and not returned.
+fn take_toc_flags(markup: &str, inline_toc: bool, default_flag: bool) -> (String, Vec<TocFlag>) {
+    let mut remaining = String::with_capacity(markup.len());
+    let mut flags = Vec::new();
+    let mut rest = markup;
+    while let Some(start) = rest.find(TOC_FLAG_START) {
+        let after = &rest[start + TOC_FLAG_START.len()..];
+        let flag = after
+            .find(COMMENT_END)
+            .and_then(|end| parse_toc_flag(&after[..end]).map(|flag| (flag, end)));
+        match flag {
+            Some((flag, end)) if !default_flag && flag.name == TOC_NAME => {
+                let flag_end = start + TOC_FLAG_START.len() + end + COMMENT_END.len();
+                remaining.push_str(&rest[..flag_end]);
+                rest = &rest[flag_end..];
+            }
+            Some((flag, end))
+                if flag.name == SECTION_TOC_NAME || (inline_toc && flag.name == TOC_NAME) =>
+            {
+                flags.push(flag);
+                let flag_end = start + TOC_FLAG_START.len() + end + COMMENT_END.len();
+                remaining.push_str(&rest[..flag_end]);
+                rest = &rest[flag_end..];
+            }
+            Some((flag, end)) => {
+                flags.push(flag);
+                remaining.push_str(&rest[..start]);
+                rest = &after[end + COMMENT_END.len()..];
+            }
+            None => {
+                // not a toc flag (e.g., "<!-- today -->"): keep it
+                remaining.push_str(&rest[..start + TOC_FLAG_START.len()]);
+                rest = after;
+            }
+        }
+    }
+    remaining.push_str(rest);
+    (remaining, flags)
+}
+
+/// Remove top-level paragraphs and html blocks whose text is the toc marker.
+/// If inline_toc is true, they are replaced by a flag named MARKER_TOC_NAME.
+/// Returns true if the marker was found.
+fn take_toc_marker(events: &mut [Event], marker: &str, inline_toc: bool) -> bool {
+    // html blocks end with a newline
+    let replacement = if inline_toc {
+        format!("<!-- {} -->\n", MARKER_TOC_NAME)
+    } else {
+        String::new()
+    };
+    let marker = marker.trim();
+    let mut found = false;
+    let mut depth = 0;
+    let mut paragraph_start = None;
+    for ix in 0..events.len() {
+        match &events[ix] {
+            Event::Start(Tag::Paragraph) if depth == 0 => paragraph_start = Some(ix),
+            Event::End(Tag::Paragraph) if depth == 1 => {
+                if let Some(start) = paragraph_start.take() {
+                    // marker may be split into several text events, e.g., "[[TOC]]"
+                    let mut text = String::new();
+                    let only_text = events[start + 1..ix].iter().all(|e| match e {
+                        Event::Text(t) => {
+                            text.push_str(t);
+                            true
+                        }
+                        _ => false,
+                    });
+                    if only_text && text.trim() == marker {
+                        // keep the number of top-level events
+                        events[start] = Event::Html(replacement.clone().into());
+                        for event in events[start + 1..=ix].iter_mut() {
+                            *event = Event::Text("".into());
+                        }
+                        found = true;
+                    }
+                }
+            }
+            Event::Html(markup) if depth == 0 && markup.trim() == marker => {
+                events[ix] = Event::Html(replacement.clone().into());
+                found = true;
+            }
+            _ => {}
+        }
+        match &events[ix] {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Replace toc flags in html markup with the toc generated for each flag.
+/// Flags for which toc returns None are kept.
+fn replace_toc_flags<F: Fn(&TocFlag) -> Option<String>>(markup: &str, toc: F) -> String {
+    let mut replaced = String::with_capacity(markup.len());
+    let mut rest = markup;
+    while let Some(start) = rest.find(TOC_FLAG_START) {
+        let after = &rest[start + TOC_FLAG_START.len()..];
+        let flag = after
+            .find(COMMENT_END)
+            .and_then(|end| parse_toc_flag(&after[..end]).map(|flag| (flag, end)));
+        match flag.and_then(|(flag, end)| toc(&flag).map(|html| (html, end))) {
+            Some((html, end)) => {
+                replaced.push_str(&rest[..start]);
+                replaced.push_str(&html);
+                rest = &after[end + COMMENT_END.len()..];
+            }
+            _ => {
+                replaced.push_str(&rest[..start + TOC_FLAG_START.len()]);
+                rest = after;
+            }
+        }
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+/// Replace section toc flags with tocs of the sub-headings of the enclosing h1 section.
+/// Flags before the first h1 get a toc of the headings before the first h1.
+/// The toc starts at min_depth, which is at least 2, since h1 bounds the section.
+fn add_section_tocs(
+    events: &mut [Event],
+    headings: &[Heading],
+    min_depth: u8,
+    max_depth: u8,
+    style: TocStyle,
+) {
+    let min_depth = min_depth.max(2);
+    for (ix, event) in events.iter_mut().enumerate() {
+        let markup = match event {
+            Event::Html(markup) if markup.contains(TOC_FLAG_START) => markup,
+            _ => continue,
+        };
+        // section is from the last h1 before the flag, to the next h1
+        let section_start = headings
+            .iter()
+            .rposition(|h| h.level == 1 && h.index.0 < ix)
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        let section = headings[section_start..]
+            .iter()
+            .take_while(|h| h.level != 1)
+            .cloned()
+            .collect::<Vec<_>>();
+        let replaced = replace_toc_flags(markup, |flag| {
+            (flag.name == SECTION_TOC_NAME).then(|| {
+                generate_toc_html(&section, min_depth, flag.depth.unwrap_or(max_depth), style)
+            })
+        });
+        *event = Event::Html(replaced.into());
+    }
+}
+
+/// Replace toc flags named `name` with the toc
+fn add_inline_toc(events: &mut [Event], toc: &str, name: &str) {
+    for event in events.iter_mut() {
+        if let Event::Html(markup) = event {
+            if markup.contains(TOC_FLAG_START) {
+                let replaced =
+                    replace_toc_flags(markup, |flag| (flag.name == name).then(|| toc.to_string()));
+                *event = Event::Html(replaced.into());
+            }
+        }
+    }
+}
+
+/// Parse the part of the flag after "<!-- toc", e.g., "-sidebar depth=2 "
+fn parse_toc_flag(s: &str) -> Option<TocFlag> {
+    let (suffix, params) = if let Some(named) = s.strip_prefix('-') {
+        let end = named.find(char::is_whitespace).unwrap_or(named.len());
+        (&named[..end], &named[end..])
+    } else {
+        ("", s)
+    };
+    if !suffix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        || !(params.is_empty() || params.starts_with(char::is_whitespace))
+    {
+        return None;
+    }
+    let mut depth = None;
+    for param in params.split_whitespace() {
+        match param.split_once('=') {
+            Some(("depth", val)) => depth = Some(val.parse::<u8>().ok()?),
+            _ => return None,
+        }
+    }
+    let name = if suffix.is_empty() {
+        TOC_NAME.to_string()
+    } else {
+        format!("{}-{}", TOC_NAME, suffix)
+    };
+    Some(TocFlag { name, depth })
 }
 
 /// State machine for parsing markdown headings (h1, h2, ...)
@@ -42,7 +798,7 @@ enum HeadingParseState {
 }
 
 /// Result of parsing document headings (h1, h2, ...)
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Heading {
     // indices for event objects: heading-start, text, heading-end
     index: (usize, usize, usize),
@@ -52,30 +808,194 @@ struct Heading {
     text: String,
     // anchor slug
     slug: String,
+    // index of text event ending with an explicit id attribute, e.g., "{#intro}"
+    attr_ix: Option<usize>,
 }
 
 impl Heading {
-    /// Generate html start tag, "<h_ id="slug">"
-    fn html_start_element(&self) -> String {
+    /// Generate html start tag, "<h_ id="slug">", or with a named anchor, depending on style,
+    /// and with the class attribute, if any
+    fn html_start_element(&self, style: AnchorStyle, class: Option<&str>) -> String {
+        let anchor = match style {
+            AnchorStyle::Name | AnchorStyle::Both => format!("<a name=\"{}\"></a>", &self.slug),
+            AnchorStyle::Id => String::new(),
+        };
+        let id = match style {
+            AnchorStyle::Id | AnchorStyle::Both => format!(" id=\"{}\"", &self.slug),
+            AnchorStyle::Name => String::new(),
+        };
         format!(
-            "<h{level} id=\"{slug}\">",
+            "{anchor}<h{level}{id}{class}>",
+            anchor = anchor,
             level = self.level,
-            slug = &self.slug,
+            id = id,
+            class = class_attr(class),
         )
     }
 }
 
+/// Options for slugify
+#[derive(Clone, Debug)]
+pub struct SlugOptions {
+    /// Separator between words (default '-')
+    pub separator: char,
+    /// Maximum length of slug in characters, or None for no limit (the default).
+    /// Slugs are shortened at a word boundary if possible.
+    pub max_len: Option<usize>,
+    /// Keep non-ascii letters and digits, e.g., "Привет мир" -> "привет-мир",
+    /// instead of converting them to ascii ("privet-mir"). Default false.
+    pub unicode: bool,
+}
+
+impl Default for SlugOptions {
+    fn default() -> Self {
+        Self {
+            separator: '-',
+            max_len: None,
+            unicode: false,
+        }
+    }
+}
+
+/// Turn string into slug, e.g. "Where am I?" -> "where-am-i", with the same rules used
+/// for heading anchors. Useful for generating file names, tag urls, and anchors.
+/// Result is lower case ascii, with runs of other characters replaced by a separator.
+/// With `SlugOptions.unicode`, letters and digits in any script are kept.
+pub fn slugify(s: &str, opts: &SlugOptions) -> String {
+    let mut slug = if opts.unicode {
+        unicode_slug(s)
+    } else {
+        slug::slugify(s)
+    };
+    if let Some(max_len) = opts.max_len {
+        if let Some((ix, c)) = slug.char_indices().nth(max_len) {
+            // slug is longer than max_len: cut at the last dash in the first max_len + 1 chars
+            let cut = match slug[..ix + c.len_utf8()].rfind('-') {
+                Some(dash) if dash > 0 => dash,
+                _ => ix,
+            };
+            slug.truncate(cut);
+            while slug.ends_with('-') {
+                slug.pop();
+            }
+        }
+    }
+    if opts.separator != '-' {
+        slug = slug.replace('-', &opts.separator.to_string());
+    }
+    slug
+}
+
+/// Lower case slug of letters and digits in any script, with runs of other characters
+/// replaced by '-'
+fn unicode_slug(s: &str) -> String {
+    let mut slug = String::with_capacity(s.len());
+    let mut dash = false;
+    for c in s.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            if dash && !slug.is_empty() {
+                slug.push('-');
+            }
+            dash = false;
+            slug.push(c);
+        } else {
+            dash = true;
+        }
+    }
+    slug
+}
+
 /// Turn heading into anchor slug, e.g. "Where am I?" -> "where-am-i"
 fn slugify_heading_for_anchor(s: &str) -> String {
-    slug::slugify(s)
+    slugify(s, &SlugOptions::default())
+}
+
+/// Make slug unique within the document by appending "-1", "-2", ... to duplicates,
+/// as github does, and add it to the used set.
+fn unique_slug(slug: String, used: &mut HashSet<String>) -> String {
+    let mut unique = slug.clone();
+    let mut n = 0;
+    while used.contains(&unique) {
+        n += 1;
+        unique = format!("{}-{}", slug, n);
+    }
+    used.insert(unique.clone());
+    unique
+}
+
+/// Split explicit id attribute from end of heading text: "Setup {#install}" returns
+/// ("Setup", "install"). Ids may contain letters, digits, '-', and '_'.
+fn explicit_id(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_end().strip_suffix('}')?;
+    let start = text.rfind("{#")?;
+    let id = &text[start + 2..];
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    Some((text[..start].trim_end(), id))
+}
+
+/// Returns true if any heading has an explicit id attribute
+fn has_explicit_ids(events: &[Event]) -> bool {
+    events.windows(2).any(|pair| match pair {
+        [Event::Text(text), Event::End(Tag::Heading(_))] => explicit_id(text).is_some(),
+        _ => false,
+    })
 }
 
 /// Gather headings for inserting into toc, and give heading nodes an id
+fn fix_headings(
+    events: &mut [Event],
+    opt: &ParseOptions,
+    diagnostics: &mut Vec<String>,
+) -> Vec<Heading> {
+    let headings = scan_headings(events, opt, diagnostics);
+    // Replace all start heading element Events to write <h_ id="slug"> instead of <h_>
+    for h in headings.iter() {
+        if let Some(attr_ix) = h.attr_ix {
+            // remove id attribute from heading text
+            if let Event::Text(text) = &events[attr_ix] {
+                let stripped = explicit_id(text).map(|(t, _)| t.to_string());
+                if let Some(stripped) = stripped {
+                    events[attr_ix] = Event::Text(stripped.into());
+                }
+            }
+        }
+        let (start_ix, _text_ix, end_ix) = h.index;
+        let class = opt.element_classes.get(&format!("h{}", h.level));
+        let mut start = h.html_start_element(opt.anchor_style, class.map(|c| c.as_str()));
+        if opt.heading_anchors {
+            let anchor = opt.heading_anchor.html(&h.slug);
+            match opt.heading_anchor.position {
+                AnchorPosition::Start => start.push_str(&anchor),
+                AnchorPosition::End => {
+                    events[end_ix] = Event::Html(format!("{}</h{}>\n", anchor, h.level).into())
+                }
+            }
+        }
+        events[start_ix] = Event::Html(start.into());
+    }
+    headings
+}
+
+/// Gather headings, with unique slugs. Explicit ids are reserved first, so generated
+/// slugs don't collide with them. Collisions with explicit ids are added to diagnostics.
+/// Explicit ids are not parsed if `opt.ignore_heading_ids` is set.
 /// Using a mini-state machine to track start of heading, heading text, end of heading
-fn fix_headings(events: &mut [Event]) -> Vec<Heading> {
+fn scan_headings(
+    events: &[Event],
+    opt: &ParseOptions,
+    diagnostics: &mut Vec<String>,
+) -> Vec<Heading> {
     use HeadingParseState::{HeadingStarted, HeadingTextParsed, Idle};
     let mut state: HeadingParseState = Idle;
     let mut headings = Vec::new();
+    // explicit id of each heading, if any
+    let mut explicit = Vec::new();
 
     for (i, event) in events.iter().enumerate() {
         match (event, &state) {
@@ -92,49 +1012,186 @@ fn fix_headings(events: &mut [Event]) -> Vec<Heading> {
                 Event::End(Tag::Heading(end_level)),
                 HeadingTextParsed((start_ix, text_ix), start_level, text),
             ) if *end_level as u8 == *start_level => {
+                let mut text = text.clone();
+                let mut attr_ix = None;
+                let id = match &events[i - 1] {
+                    Event::Text(last) if !opt.ignore_heading_ids => {
+                        explicit_id(last).map(|(before, id)| {
+                            attr_ix = Some(i - 1);
+                            if i - 1 == *text_ix {
+                                text = before.to_string();
+                            }
+                            id.to_string()
+                        })
+                    }
+                    _ => None,
+                };
+                explicit.push(id);
                 headings.push(Heading {
                     index: (*start_ix, *text_ix, i),
                     level: *start_level,
-                    text: text.clone(),
-                    slug: slugify_heading_for_anchor(text),
+                    text,
+                    slug: String::new(),
+                    attr_ix,
                 });
                 state = Idle;
             }
             _ => {}
         }
     }
-    // Replace all start heading element Events to write <h_ id="slug"> instead of <h_>
-    for h in headings.iter() {
-        let (start_ix, _text_ix, _end_ix) = h.index;
-        events[start_ix] = Event::Html(h.html_start_element().into());
+
+    let mut used_slugs = HashSet::new();
+    for (h, id) in headings.iter_mut().zip(explicit.iter()) {
+        if let Some(id) = id {
+            h.slug = unique_slug(id.clone(), &mut used_slugs);
+            if &h.slug != id {
+                diagnostics.push(format!(
+                    "heading id '{}' is used by more than one heading: '{}' has id '{}'",
+                    id, h.text, h.slug
+                ));
+            }
+        }
+    }
+    let explicit_ids = used_slugs.clone();
+    for (h, id) in headings.iter_mut().zip(explicit.iter()) {
+        if id.is_none() {
+            let slug = if opt.unicode_slugs {
+                slugify(
+                    &h.text,
+                    &SlugOptions {
+                        unicode: true,
+                        ..Default::default()
+                    },
+                )
+            } else {
+                slugify_heading_for_anchor(&h.text)
+            };
+            h.slug = unique_slug(slug.clone(), &mut used_slugs);
+            if explicit_ids.contains(&slug) {
+                diagnostics.push(format!(
+                    "heading '{}' has the same slug as an explicit id: it has id '{}'",
+                    h.text, h.slug
+                ));
+            }
+        }
     }
     headings
 }
 
-/// Parse content markdown and generate html, with optional generation of TOC
-/// Markdown parameter should not have frontmatter
-pub fn markdown_to_html(markdown_in: &str) -> Result<ParseResult> {
-    use pulldown_cmark::CowStr;
-    let mut enable_toc = false;
-
+/// Markdown extensions enabled for the options
+fn parser_options(opt: &ParseOptions) -> MdOptions {
     let mut options = MdOptions::empty();
     // enable the following extensions: strikethrough, git tables, task lists
     options.insert(MdOptions::ENABLE_STRIKETHROUGH);
     options.insert(MdOptions::ENABLE_TABLES);
     options.insert(MdOptions::ENABLE_TASKLISTS);
+    if opt.footnotes != Footnotes::Disabled {
+        options.insert(MdOptions::ENABLE_FOOTNOTES);
+    }
+    if opt.smart_punctuation {
+        options.insert(MdOptions::ENABLE_SMART_PUNCTUATION);
+    }
+    options
+}
+
+/// Count words in text events, not including code, html, or image alt text.
+/// Text in adjacent inline events (e.g., `a*b*`) is one word.
+fn count_words(events: &[Event]) -> usize {
+    events_text(events, false).split_whitespace().count()
+}
+
+/// Text of events, with blocks separated by whitespace. Html and images are skipped,
+/// and code blocks and inline code are skipped unless include_code is true.
+fn events_text(events: &[Event], include_code: bool) -> String {
+    let mut text = String::new();
+    let mut skip = 0;
+    for event in events.iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) if include_code => {}
+            Event::End(Tag::CodeBlock(_)) if include_code => {}
+            Event::Start(Tag::CodeBlock(_)) | Event::Start(Tag::Image(..)) => skip += 1,
+            Event::End(Tag::CodeBlock(_)) | Event::End(Tag::Image(..)) => skip -= 1,
+            Event::Text(t) if skip == 0 => text.push_str(t),
+            Event::Code(t) if skip == 0 && include_code => text.push_str(t),
+            // inline markup doesn't separate words
+            Event::Start(Tag::Emphasis)
+            | Event::End(Tag::Emphasis)
+            | Event::Start(Tag::Strong)
+            | Event::End(Tag::Strong)
+            | Event::Start(Tag::Strikethrough)
+            | Event::End(Tag::Strikethrough)
+            | Event::Start(Tag::Link(..))
+            | Event::End(Tag::Link(..)) => {}
+            _ => text.push(' '),
+        }
+    }
+    text
+}
+
+/// Extract plain text from markdown, for search indexes: the text of all blocks,
+/// separated by single spaces. Code blocks, inline code, html, and images are skipped.
+/// The text is extracted like `ParseResult.word_count`, so it has the words that are
+/// counted. For readable text that keeps block structure, code, and link urls,
+/// use `markdown_to_text`. Markdown parameter should not have frontmatter.
+pub fn plain_text(markdown: &str) -> String {
+    plain_text_with(markdown, false)
+}
+
+/// Extract plain text from markdown, like `plain_text`. If include_code is true,
+/// code blocks and inline code are included.
+pub fn plain_text_with(markdown: &str, include_code: bool) -> String {
+    let events =
+        Parser::new_ext(markdown, parser_options(&ParseOptions::default())).collect::<Vec<_>>();
+    events_text(&events, include_code)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reading time in minutes, rounded up
+fn reading_time(word_count: usize, opt: &ParseOptions) -> u32 {
+    let wpm = opt.words_per_minute.unwrap_or(WORDS_PER_MINUTE).max(1) as usize;
+    word_count.div_ceil(wpm) as u32
+}
+
+/// Parse content markdown and generate html, with default options.
+/// Markdown parameter should not have frontmatter
+pub fn markdown_to_html_default(markdown_in: &str) -> Result<ParseResult> {
+    markdown_to_html(markdown_in, &ParseOptions::default())
+}
+
+/// Parse content markdown and generate html, with options, and optional generation of TOC.
+/// Markdown parameter should not have frontmatter
+pub fn markdown_to_html(markdown_in: &str, opt: &ParseOptions) -> Result<ParseResult> {
+    use pulldown_cmark::CowStr;
+    let mut toc_flags = Vec::new();
+
+    let options = parser_options(opt);
 
-    // Parse markdown into array of events, so we can do multiple passes
+    // Parse markdown into array of events, so we can do multiple passes,
+    // and note the source range of each top-level block
+    let mut source_blocks = Vec::new();
+    let mut depth = 0;
     let mut events = Parser::new_ext(markdown_in, options)
-        .enumerate()
-        .map(|(_i, event)| match event {
-            // Do some simple link checking/fixing
-            Event::Start(Tag::Link(link_type, dest, title)) if dest.is_empty() => {
-                Event::Start(Tag::Link(link_type, "#".into(), title))
+        .into_offset_iter()
+        .map(|(event, range)| {
+            if depth == 0 {
+                source_blocks.push(range);
             }
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => {}
+            }
+            event
+        })
+        .map(|event| match event {
             Event::Html(markup) => {
-                if markup.contains(TOC_FLAG) {
-                    enable_toc = true;
-                    Event::Html(CowStr::from(markup.replacen(TOC_FLAG, "", 1)))
+                if markup.contains(TOC_FLAG_START) {
+                    let (markup, flags) =
+                        take_toc_flags(&markup, opt.inline_toc, opt.default_toc_marker());
+                    toc_flags.extend(flags);
+                    Event::Html(CowStr::from(markup))
                 } else {
                     Event::Html(markup)
                 }
@@ -142,49 +1199,982 @@ pub fn markdown_to_html(markdown_in: &str) -> Result<ParseResult> {
             _ => event,
         })
         .collect::<Vec<_>>(); // collect events for additional passes;
+    if !opt.default_toc_marker() && take_toc_marker(&mut events, &opt.toc_marker, opt.inline_toc) {
+        toc_flags.push(TocFlag {
+            name: TOC_NAME.to_string(),
+            depth: None,
+        });
+    }
+    let word_count = count_words(&events);
 
-    // If there was a flag requesting toc, generate toc and add anchor tags to headings
-    let toc = if enable_toc {
-        let headings = fix_headings(&mut events);
-        Some(generate_toc_html(&headings, MAX_TOC_DEPTH))
-    } else {
-        None
-    };
+    if !opt.code_block_wrappers.is_empty() {
+        events = wrap_code_blocks(events, &opt.code_block_wrappers);
+    }
 
-    let mut content = String::with_capacity(markdown_in.len());
-    pulldown_cmark::html::push_html(&mut content, events.into_iter());
-    Ok(ParseResult { content, toc })
-}
+    if opt.autolink {
+        events = autolink_urls(events);
+    }
 
-/// Generate TOC item: html link inside a list item tag
-fn toc_item_html(href: &str, text: &str) -> String {
-    format!(
-        "{begin}<a href=\"#{href}\">{text}</a>{end}",
-        begin = TOC_ITEM,
-        href = href,
-        text = text,
-        end = TOC_END_ITEM,
-    )
-}
+    // Do some simple link checking/fixing
+    let mut diagnostics = fix_empty_links(&mut events, opt.empty_links)?;
+    if opt.rewrite_md_links {
+        rewrite_md_links(&mut events);
+    }
+    if opt.external_links_new_tab {
+        let class = opt.element_classes.get("a").map(|c| c.as_str());
+        external_links_new_tab(&mut events, &opt.internal_hosts, class);
+    }
+    if let Some(settings) = &opt.inline_images {
+        crate::images::inline_images(&mut events, settings, &mut diagnostics);
+    }
 
-/// Use headings array to generate TOC in HTML
-fn generate_toc_html(headings: &[Heading], max_depth: u8) -> String {
+    // If there were flags requesting toc, or heading anchors are enabled,
+    // add anchor tags to headings, and generate tocs
+    let mut tocs = HashMap::new();
+    let mut heading_info = Vec::new();
+    if !toc_flags.is_empty()
+        || opt.heading_anchors
+        || (!opt.ignore_heading_ids && has_explicit_ids(&events))
+    {
+        let headings = fix_headings(&mut events, opt, &mut diagnostics);
+        heading_info = headings.iter().map(HeadingInfo::from).collect();
+        let min_depth = opt.min_toc_depth.unwrap_or(MIN_TOC_DEPTH);
+        let max_depth = opt.max_toc_depth.unwrap_or(MAX_TOC_DEPTH);
+        if toc_flags.iter().any(|flag| flag.name == SECTION_TOC_NAME) {
+            add_section_tocs(&mut events, &headings, min_depth, max_depth, opt.toc_style);
+        }
+        for flag in toc_flags
+            .iter()
+            .filter(|flag| flag.name != SECTION_TOC_NAME)
+        {
+            // if a flag is repeated, the first one wins
+            tocs.entry(flag.name.clone()).or_insert_with(|| {
+                generate_toc_html(
+                    &headings,
+                    min_depth,
+                    flag.depth.unwrap_or(max_depth),
+                    opt.toc_style,
+                )
+            });
+        }
+        if let (true, Some(toc)) = (opt.inline_toc, tocs.get(TOC_NAME)) {
+            let name = if opt.default_toc_marker() {
+                TOC_NAME
+            } else {
+                MARKER_TOC_NAME
+            };
+            add_inline_toc(&mut events, toc, name);
+        }
+    } else if opt.scan_headings {
+        heading_info = scan_headings(&events, opt, &mut diagnostics)
+            .iter()
+            .map(HeadingInfo::from)
+            .collect();
+    }
+
+    if opt.admonitions {
+        add_admonitions(&mut events, &opt.admonition_classes);
+    }
+    if !opt.element_classes.is_empty() {
+        add_element_classes(&mut events, &opt.element_classes);
+    }
+
+    if opt.footnotes == Footnotes::Section {
+        events = collect_footnotes(events);
+    }
+
+    let marker = opt.excerpt_marker.as_deref().unwrap_or(EXCERPT_MARKER);
+    let mut excerpt = None;
+    if let Some(ix) = find_top_level_html(&events, marker) {
+        events.remove(ix);
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events[..ix].iter().cloned());
+        excerpt = Some(match opt.line_ending {
+            Some(eol) => eol.normalize(&html),
+            None => html,
+        });
+    }
+
+    let mut content = String::with_capacity(markdown_in.len());
+    if let Some((start, _)) = &opt.content_wrapper {
+        content.push_str(start);
+    }
+    let html_blocks = push_html_blocks(&mut content, events)?;
+    if let Some((_, end)) = &opt.content_wrapper {
+        content.push_str(end);
+    }
+    // block ranges are only known if passes didn't add or remove top-level events
+    let mut blocks = Vec::new();
+    if html_blocks.len() == source_blocks.len() {
+        blocks = source_blocks
+            .into_iter()
+            .zip(html_blocks)
+            .map(|(source, html)| BlockSpan { source, html })
+            .collect();
+    }
+    if let Some(eol) = opt.line_ending {
+        content = eol.normalize(&content);
+        blocks.clear();
+        for toc in tocs.values_mut() {
+            *toc = eol.normalize(toc);
+        }
+    }
+    let toc = tocs.get(TOC_NAME).cloned();
+    Ok(ParseResult {
+        content,
+        toc,
+        tocs,
+        headings: heading_info,
+        diagnostics,
+        blocks,
+        excerpt,
+        word_count,
+        reading_time_minutes: reading_time(word_count, opt),
+    })
+}
+
+/// Move top-level footnote definitions to the end of the document, in a
+/// `<section class="footnotes">`. Definitions are ordered by their first reference;
+/// unreferenced definitions are last, in source order.
+fn collect_footnotes(events: Vec<Event>) -> Vec<Event> {
+    use pulldown_cmark::CowStr;
+    let mut body = Vec::with_capacity(events.len());
+    let mut definitions: Vec<(CowStr, Vec<Event>)> = Vec::new();
+    let mut references: Vec<CowStr> = Vec::new();
+    let mut depth = 0;
+    let mut in_definition = false;
+    for event in events.into_iter() {
+        if let Event::FootnoteReference(name) = &event {
+            if !references.contains(name) {
+                references.push(name.clone());
+            }
+        }
+        match &event {
+            Event::Start(Tag::FootnoteDefinition(name)) if depth == 0 => {
+                definitions.push((name.clone(), Vec::new()));
+                in_definition = true;
+            }
+            _ => {}
+        }
+        match &event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+        if in_definition {
+            // definitions is non-empty while in_definition is set
+            definitions.last_mut().unwrap().1.push(event);
+            if depth == 0 {
+                in_definition = false;
+            }
+        } else {
+            body.push(event);
+        }
+    }
+    if definitions.is_empty() {
+        return body;
+    }
+    definitions.sort_by_key(|(name, _)| {
+        references
+            .iter()
+            .position(|r| r == name)
+            .unwrap_or(references.len())
+    });
+    body.push(Event::Html(CowStr::from("<section class=\"footnotes\">\n")));
+    body.extend(definitions.into_iter().flat_map(|(_, events)| events));
+    body.push(Event::Html(CowStr::from("</section>\n")));
+    body
+}
+
+/// Returns index of top-level html block event whose text is the markup (ignoring whitespace)
+fn find_top_level_html(events: &[Event], markup: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (ix, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Html(html) if depth == 0 && html.trim() == markup => return Some(ix),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Marker separating fragments in the combined document
+const FRAGMENT_MARKER: &str = "<!-- mdsite-fragment -->";
+
+/// Result of `fragments_to_html`
+#[derive(Clone, Debug)]
+pub struct FragmentsResult {
+    /// Html for each fragment, in the same order as the fragments
+    pub fragments: Vec<String>,
+    /// Result for the page of all fragments: its content is the html of all fragments,
+    /// and tocs and headings include all fragments.
+    pub page: ParseResult,
+}
+
+/// Convert fragments of a page (for example, sections placed separately in a template)
+/// to html. The fragments are converted as one document, so link reference definitions and
+/// footnotes defined in one fragment can be used in the others, footnotes are numbered
+/// in order across the page, and heading anchors are unique in the page.
+/// If a fragment ends inside an unclosed block (e.g., a code fence), each fragment is converted
+/// separately, and the problem is reported in the page diagnostics.
+pub fn fragments_to_html(fragments: &[&str], opt: &ParseOptions) -> Result<FragmentsResult> {
+    let separator = format!("\n\n{}\n\n", FRAGMENT_MARKER);
+    let page_opt = ParseOptions {
+        content_wrapper: None,
+        ..opt.clone()
+    };
+    let mut page = markdown_to_html(&fragments.join(&separator), &page_opt)?;
+    page.blocks.clear();
+    let mut parts = page
+        .content
+        .split(FRAGMENT_MARKER)
+        .enumerate()
+        .map(|(ix, part)| {
+            // remove the line ending after the marker
+            let part = if ix == 0 {
+                part
+            } else {
+                part.strip_prefix("\r\n")
+                    .or_else(|| part.strip_prefix('\n'))
+                    .unwrap_or(part)
+            };
+            part.to_string()
+        })
+        .collect::<Vec<_>>();
+    if parts.len() != fragments.len() {
+        page.diagnostics.push(
+            "a fragment has an unclosed block: fragments were converted separately".to_string(),
+        );
+        parts = fragments
+            .iter()
+            .map(|fragment| Ok(markdown_to_html(fragment, &page_opt)?.content))
+            .collect::<Result<Vec<_>>>()?;
+    }
+    if let Some((start, end)) = &opt.content_wrapper {
+        for part in parts.iter_mut() {
+            *part = format!("{}{}{}", start, part, end);
+        }
+    }
+    page.content = parts.concat();
+    Ok(FragmentsResult {
+        fragments: parts,
+        page,
+    })
+}
+
+/// Append html for events to content. Returns the byte range in content of each
+/// top-level block.
+fn push_html_blocks(content: &mut String, events: Vec<Event>) -> Result<Vec<Range<usize>>> {
+    use std::{cell::Cell, rc::Rc};
+
+    /// Writer that keeps count of bytes written
+    struct CountingWriter<'buf> {
+        buf: &'buf mut Vec<u8>,
+        len: Rc<Cell<usize>>,
+    }
+    impl<'buf> std::io::Write for CountingWriter<'buf> {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.buf.extend_from_slice(data);
+            self.len.set(self.buf.len());
+            Ok(data.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let offset = content.len();
+    let len = Rc::new(Cell::new(0));
+    let mut buf = Vec::with_capacity(content.capacity() - offset);
+    let mut block_starts = Vec::new();
+    let mut depth = 0;
+    // The writer has written all previous events when it takes the next one,
+    // so the output length at that point is where the event's html starts
+    let counted = events.into_iter().inspect(|event| {
+        if depth == 0 {
+            block_starts.push(offset + len.get());
+        }
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    });
+    pulldown_cmark::html::write_html(
+        CountingWriter {
+            buf: &mut buf,
+            len: len.clone(),
+        },
+        counted,
+    )?;
+    content.push_str(&String::from_utf8(buf)?);
+    let mut ranges = Vec::with_capacity(block_starts.len());
+    for (ix, start) in block_starts.iter().enumerate() {
+        let end = block_starts.get(ix + 1).copied().unwrap_or(content.len());
+        ranges.push(*start..end);
+    }
+    Ok(ranges)
+}
+
+/// Convert edited markdown to html, reusing the html of unchanged top-level blocks from
+/// `old`, the result of converting `old_markdown` with the same options. This is intended
+/// for live previews, where small edits are made to a large document.
+/// The changed region is found by comparing old and new markdown, and only the
+/// top-level blocks it touches are converted. The whole document is converted if the edit
+/// may change document structure: if it touches headings or toc flags, if the old or new
+/// document has link reference definitions (any block may use them), or if the document
+/// has tocs, heading anchors, or diagnostics.
+pub fn markdown_to_html_incremental(
+    old_markdown: &str,
+    new_markdown: &str,
+    old: &ParseResult,
+    opt: &ParseOptions,
+) -> Result<ParseResult> {
+    match splice_changed_blocks(old_markdown, new_markdown, old, opt)? {
+        Some(result) => Ok(result),
+        None => markdown_to_html(new_markdown, opt),
+    }
+}
+
+/// Convert the changed region of markdown and splice it into the old html.
+/// Returns None if the whole document needs to be converted.
+fn splice_changed_blocks(
+    old_markdown: &str,
+    new_markdown: &str,
+    old: &ParseResult,
+    opt: &ParseOptions,
+) -> Result<Option<ParseResult>> {
+    use dissimilar::Chunk;
+
+    if old_markdown == new_markdown {
+        return Ok(Some(old.clone()));
+    }
+    if old.blocks.is_empty()
+        || !old.tocs.is_empty()
+        || !old.diagnostics.is_empty()
+        || opt.heading_anchors
+        || (opt.footnotes != Footnotes::Disabled
+            && (old_markdown.contains("[^") || new_markdown.contains("[^")))
+        // reference definitions apply to links anywhere in the document
+        || old_markdown.contains("]:")
+        || new_markdown.contains("]:")
+    {
+        return Ok(None);
+    }
+    // changed range is between the unchanged prefix and suffix
+    let chunks = dissimilar::diff(old_markdown, new_markdown);
+    let prefix = match chunks.first() {
+        Some(Chunk::Equal(s)) => s.len(),
+        _ => 0,
+    };
+    let suffix = match chunks.last() {
+        Some(Chunk::Equal(s)) if chunks.len() > 1 => s.len(),
+        _ => 0,
+    };
+    let changed_end = old_markdown.len() - suffix;
+
+    // blocks touching the changed range; touching counts, so that merged or split
+    // blocks are converted together
+    let first = old.blocks.iter().position(|b| b.source.end >= prefix);
+    let last = old
+        .blocks
+        .iter()
+        .rposition(|b| b.source.start <= changed_end);
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) if first <= last => (first, last),
+        _ => return Ok(None),
+    };
+    let old_region =
+        old.blocks[first].source.start.min(prefix)..old.blocks[last].source.end.max(changed_end);
+    let new_region = old_region.start..(old_region.end + new_markdown.len() - old_markdown.len());
+    let marker = opt.excerpt_marker.as_deref().unwrap_or(EXCERPT_MARKER);
+    let has_toc_marker = |region: &str| region.contains(opt.toc_marker.trim());
+    if !is_simple_region(&old_markdown[old_region.clone()])
+        || !is_simple_region(&new_markdown[new_region.clone()])
+        || new_markdown[new_region.clone()].contains(marker)
+        || has_toc_marker(&old_markdown[old_region.clone()])
+        || has_toc_marker(&new_markdown[new_region.clone()])
+    {
+        return Ok(None);
+    }
+
+    let html_region = old.blocks[first].html.start..old.blocks[last].html.end;
+    // the html writer adds a newline before some blocks if the previous output didn't end
+    // with one, so the region must begin and end on a line boundary
+    if html_region.start > 0 && !old.content[..html_region.start].ends_with('\n') {
+        return Ok(None);
+    }
+    let region_opt = ParseOptions {
+        content_wrapper: None,
+        ..opt.clone()
+    };
+    let part = markdown_to_html(&new_markdown[new_region.clone()], &region_opt)?;
+    if !part.diagnostics.is_empty()
+        || (part.blocks.is_empty() && !part.content.is_empty())
+        || (!part.content.is_empty() && !part.content.ends_with('\n'))
+    {
+        return Ok(None);
+    }
+
+    let mut content = String::with_capacity(old.content.len() + part.content.len());
+    content.push_str(&old.content[..html_region.start]);
+    content.push_str(&part.content);
+    content.push_str(&old.content[html_region.end..]);
+
+    let source_delta = new_markdown.len() as isize - old_markdown.len() as isize;
+    let html_delta = part.content.len() as isize - html_region.len() as isize;
+    let shift = |r: &Range<usize>, delta: isize| {
+        (r.start as isize + delta) as usize..(r.end as isize + delta) as usize
+    };
+    // blocks are separate, so word counts of the regions can be added and subtracted
+    let old_words = count_words(
+        &Parser::new_ext(&old_markdown[old_region], parser_options(opt)).collect::<Vec<_>>(),
+    );
+    let word_count = (old.word_count + part.word_count).saturating_sub(old_words);
+    let mut blocks = old.blocks[..first].to_vec();
+    blocks.extend(part.blocks.iter().map(|b| BlockSpan {
+        source: shift(&b.source, new_region.start as isize),
+        html: shift(&b.html, html_region.start as isize),
+    }));
+    blocks.extend(old.blocks[last + 1..].iter().map(|b| BlockSpan {
+        source: shift(&b.source, source_delta),
+        html: shift(&b.html, html_delta),
+    }));
+
+    Ok(Some(ParseResult {
+        content,
+        toc: None,
+        tocs: HashMap::new(),
+        headings: old.headings.clone(),
+        diagnostics: Vec::new(),
+        blocks,
+        excerpt: None,
+        word_count,
+        reading_time_minutes: reading_time(word_count, opt),
+    }))
+}
+
+/// Returns true if the markdown can be converted apart from the rest of the document:
+/// it has no headings or toc flags. Reference definitions are checked by the caller.
+fn is_simple_region(markdown: &str) -> bool {
+    !markdown.contains(TOC_FLAG_START)
+        && !Parser::new(markdown).any(|e| matches!(e, Event::Start(Tag::Heading(_))))
+}
+
+/// Escape html special characters
+pub(crate) fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    // writing to a String can't fail
+    let _ = pulldown_cmark::escape::escape_html(&mut escaped, s);
+    escaped
+}
+
+/// Language of fenced code block: the first word of the info string
+fn code_block_lang<'a>(kind: &'a CodeBlockKind) -> Option<&'a str> {
+    match kind {
+        CodeBlockKind::Fenced(info) => info.split_whitespace().next(),
+        CodeBlockKind::Indented => None,
+    }
+}
+
+/// Replace code blocks for languages in the map with the language's wrapper.
+/// The source is html-escaped, unless the wrapper is raw.
+fn wrap_code_blocks<'a>(
+    events: Vec<Event<'a>>,
+    wrappers: &HashMap<String, CodeBlockWrapper>,
+) -> Vec<Event<'a>> {
+    let mut out = Vec::with_capacity(events.len());
+    // wrapper and source, while inside a wrapped code block
+    let mut current: Option<(&CodeBlockWrapper, String)> = None;
+    for event in events.into_iter() {
+        match (&event, current.as_mut()) {
+            (Event::Start(Tag::CodeBlock(kind)), None) => {
+                match code_block_lang(kind).and_then(|lang| wrappers.get(lang)) {
+                    Some(wrapper) => current = Some((wrapper, String::new())),
+                    None => out.push(event),
+                }
+            }
+            (Event::Text(text), Some((_, source))) => source.push_str(text),
+            (Event::End(Tag::CodeBlock(_)), Some((wrapper, source))) => {
+                out.push(Event::Html(
+                    format!(
+                        "<{element} class=\"{class}\">{source}</{element}>\n",
+                        element = wrapper.element,
+                        class = escape_html(&wrapper.class),
+                        source = if wrapper.raw {
+                            source.clone()
+                        } else {
+                            escape_html(source)
+                        },
+                    )
+                    .into(),
+                ));
+                current = None;
+            }
+            _ => out.push(event),
+        }
+    }
+    out
+}
+
+/// class attribute with leading space, or empty string if there is no class
+fn class_attr(class: Option<&str>) -> String {
+    match class {
+        Some(class) => format!(" class=\"{}\"", escape_html(class)),
+        None => String::new(),
+    }
+}
+
+/// Convert blockquotes that start with an alert marker (`[!NOTE]`) to admonitions
+fn add_admonitions(events: &mut [Event], classes: &HashMap<String, String>) {
+    for ix in 0..events.len() {
+        if !matches!(events[ix], Event::Start(Tag::BlockQuote)) {
+            continue;
+        }
+        let (kind, marker_end) = match admonition_marker(&events[ix..], classes) {
+            Some((kind, len)) => (kind, ix + len),
+            None => continue,
+        };
+        // matching end of the blockquote
+        let mut depth = 0;
+        let end = events[ix..].iter().position(|e| match e {
+            Event::Start(Tag::BlockQuote) => {
+                depth += 1;
+                false
+            }
+            Event::End(Tag::BlockQuote) => {
+                depth -= 1;
+                depth == 0
+            }
+            _ => false,
+        });
+        let end = match end {
+            Some(end) => ix + end,
+            None => continue,
+        };
+        let class = classes.get(&kind).unwrap_or(&kind);
+        let mut chars = kind.chars();
+        let title = chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+            .unwrap_or_default();
+        let start = Event::Html(
+            format!(
+                "<div class=\"admonition {}\">\n<p class=\"admonition-title\">{}</p>\n",
+                escape_html(class),
+                escape_html(&title)
+            )
+            .into(),
+        );
+        events[end] = Event::Html("</div>\n".into());
+        // remove the marker text, and the line break after it. If the marker is alone
+        // in its paragraph, the paragraph is removed, and replaced by the start html
+        // so the html writer doesn't add a newline
+        for event in events[ix + 2..marker_end].iter_mut() {
+            *event = Event::Text("".into());
+        }
+        if matches!(events[marker_end], Event::End(Tag::Paragraph)) {
+            events[ix] = Event::Html("".into());
+            events[ix + 1] = Event::Html("".into());
+            events[marker_end] = start;
+        } else {
+            events[ix] = start;
+            events[marker_end] = Event::Text("".into());
+        }
+    }
+}
+
+/// If the blockquote events start with an admonition marker paragraph line,
+/// returns the lower case type, and index of the event after the marker text
+/// (a line break, or the end of the paragraph).
+fn admonition_marker(
+    quote: &[Event],
+    classes: &HashMap<String, String>,
+) -> Option<(String, usize)> {
+    if !matches!(quote.get(1), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    // the parser may split the marker into several text events, e.g., "[", "!NOTE", "]"
+    let mut text = String::new();
+    let mut ix = 2;
+    while let Some(Event::Text(t)) = quote.get(ix) {
+        text.push_str(t);
+        ix += 1;
+    }
+    match quote.get(ix) {
+        Some(Event::SoftBreak) | Some(Event::HardBreak) | Some(Event::End(Tag::Paragraph)) => {}
+        _ => return None,
+    }
+    let kind = text
+        .trim()
+        .strip_prefix("[!")?
+        .strip_suffix(']')?
+        .to_lowercase();
+    if kind.is_empty()
+        || !(ADMONITION_TYPES.contains(&kind.as_str()) || classes.contains_key(&kind))
+    {
+        return None;
+    }
+    Some((kind, ix))
+}
+
+/// Replace start tags of elements that have a class in the map with html that
+/// includes the class attribute. Headings already rewritten by fix_headings are skipped.
+fn add_element_classes(events: &mut [Event], classes: &HashMap<String, String>) {
+    use pulldown_cmark::Alignment;
+
+    let class_for = |name: &str| classes.get(name).map(|c| c.as_str());
+    // state of current table, if its class was added: alignments, in-head, cell index
+    let mut table: Option<(Vec<Alignment>, bool, usize)> = None;
+    for event in events.iter_mut() {
+        let html = match &*event {
+            Event::Start(Tag::Paragraph) => {
+                class_for("p").map(|c| format!("<p{}>", class_attr(Some(c))))
+            }
+            Event::Start(Tag::Heading(level)) => class_for(&format!("h{}", level))
+                .map(|c| format!("<h{}{}>", level, class_attr(Some(c)))),
+            Event::Start(Tag::BlockQuote) => {
+                class_for("blockquote").map(|c| format!("<blockquote{}>\n", class_attr(Some(c))))
+            }
+            Event::Start(Tag::List(None)) => {
+                class_for("ul").map(|c| format!("<ul{}>\n", class_attr(Some(c))))
+            }
+            Event::Start(Tag::List(Some(start))) => class_for("ol").map(|c| {
+                if *start == 1 {
+                    format!("<ol{}>\n", class_attr(Some(c)))
+                } else {
+                    format!("<ol start=\"{}\"{}>\n", start, class_attr(Some(c)))
+                }
+            }),
+            Event::Start(Tag::Link(link_type, dest, title)) => class_for("a")
+                .map(|c| link_start_html(*link_type, dest, title, Some(c), LINK_SAME_TAB)),
+            Event::Start(Tag::Table(alignments)) => class_for("table").map(|c| {
+                // the html writer needs the table start event for cell alignments,
+                // so cells are written here too
+                table = Some((alignments.clone(), false, 0));
+                format!("<table{}>", class_attr(Some(c)))
+            }),
+            Event::Start(Tag::TableHead) => {
+                if let Some((_, in_head, ix)) = table.as_mut() {
+                    *in_head = true;
+                    *ix = 0;
+                }
+                None
+            }
+            Event::End(Tag::TableHead) => {
+                if let Some((_, in_head, _)) = table.as_mut() {
+                    *in_head = false;
+                }
+                None
+            }
+            Event::Start(Tag::TableRow) => {
+                if let Some((_, _, ix)) = table.as_mut() {
+                    *ix = 0;
+                }
+                None
+            }
+            Event::Start(Tag::TableCell) => table.as_mut().map(|(alignments, in_head, ix)| {
+                let align = match alignments.get(*ix) {
+                    Some(Alignment::Left) => " align=\"left\"",
+                    Some(Alignment::Center) => " align=\"center\"",
+                    Some(Alignment::Right) => " align=\"right\"",
+                    _ => "",
+                };
+                *ix += 1;
+                format!("<{}{}>", if *in_head { "th" } else { "td" }, align)
+            }),
+            Event::End(Tag::Table(_)) => {
+                table = None;
+                None
+            }
+            _ => None,
+        };
+        if let Some(html) = html {
+            *event = Event::Html(html.into());
+        }
+    }
+}
+
+/// Extra attributes of links that open in the same tab
+const LINK_SAME_TAB: &str = "";
+/// Extra attributes of links that open in a new tab
+const LINK_NEW_TAB: &str = " target=\"_blank\" rel=\"noopener noreferrer\"";
+
+/// Html for link start tag, with optional class, followed by extra attributes
+fn link_start_html(
+    link_type: pulldown_cmark::LinkType,
+    dest: &str,
+    title: &str,
+    class: Option<&str>,
+    attrs: &str,
+) -> String {
+    use pulldown_cmark::{escape::escape_href, LinkType};
+
+    let mut href = String::new();
+    if link_type == LinkType::Email {
+        href.push_str("mailto:");
+    }
+    let _ = escape_href(&mut href, dest);
+    let title = if title.is_empty() {
+        String::new()
+    } else {
+        format!(" title=\"{}\"", escape_html(title))
+    };
+    format!(
+        "<a href=\"{}\"{}{}{}>",
+        href,
+        title,
+        class_attr(class),
+        attrs
+    )
+}
+
+/// Replace start tags of links to external sites with html that opens them in a new tab.
+/// Links starting with one of the internal host prefixes are not changed.
+fn external_links_new_tab(events: &mut [Event], internal_hosts: &[String], class: Option<&str>) {
+    for event in events.iter_mut() {
+        if let Event::Start(Tag::Link(link_type, dest, title)) = &*event {
+            if crate::resolve::is_external(dest)
+                && !internal_hosts
+                    .iter()
+                    .any(|host| dest.starts_with(host.as_str()))
+            {
+                let html = link_start_html(*link_type, dest, title, class, LINK_NEW_TAB);
+                *event = Event::Html(html.into());
+            }
+        }
+    }
+}
+
+/// Handle links with empty destinations. Returns diagnostic messages
+fn fix_empty_links(events: &mut [Event], mode: EmptyLinks) -> Result<Vec<String>> {
+    let mut diagnostics = Vec::new();
+    for ix in 0..events.len() {
+        let (link_type, title) = match &events[ix] {
+            Event::Start(Tag::Link(link_type, dest, title)) if dest.is_empty() => {
+                (*link_type, title.clone())
+            }
+            _ => continue,
+        };
+        match mode {
+            EmptyLinks::Empty => continue,
+            EmptyLinks::Error => return Err(Error::EmptyLink(link_text(&events[ix + 1..]))),
+            EmptyLinks::Diagnostic => diagnostics.push(format!(
+                "link '{}' has empty destination",
+                link_text(&events[ix + 1..])
+            )),
+            EmptyLinks::Hash => {}
+        }
+        events[ix] = Event::Start(Tag::Link(link_type, "#".into(), title));
+    }
+    Ok(diagnostics)
+}
+
+/// Convert bare urls in text to links. Text in code blocks, links, and images is unchanged.
+fn autolink_urls(events: Vec<Event>) -> Vec<Event> {
+    let mut linked = Vec::with_capacity(events.len());
+    // depth of links (including raw html anchors), images, and code blocks
+    let mut skip_depth: usize = 0;
+    // adjacent text events are joined, since the parser may split a url
+    let mut text = String::new();
+    for event in events.into_iter() {
+        match &event {
+            Event::Text(t) if skip_depth == 0 => {
+                text.push_str(t);
+                continue;
+            }
+            Event::Start(Tag::Link(..))
+            | Event::Start(Tag::Image(..))
+            | Event::Start(Tag::CodeBlock(_)) => skip_depth += 1,
+            Event::End(Tag::Link(..))
+            | Event::End(Tag::Image(..))
+            | Event::End(Tag::CodeBlock(_)) => skip_depth -= 1,
+            Event::Html(html) => {
+                let (opens, closes) = count_html_anchors(html);
+                skip_depth = (skip_depth + opens).saturating_sub(closes);
+            }
+            _ => {}
+        }
+        if !text.is_empty() {
+            push_autolinked(&mut linked, &std::mem::take(&mut text));
+        }
+        linked.push(event);
+    }
+    if !text.is_empty() {
+        push_autolinked(&mut linked, &text);
+    }
+    linked
+}
+
+/// Count anchor start tags (`<a ...>`) and end tags (`</a>`) in raw html
+fn count_html_anchors(html: &str) -> (usize, usize) {
+    let html = html.to_ascii_lowercase();
+    let count = |tag: &str| {
+        html.match_indices(tag)
+            .filter(|(pos, _)| {
+                html[pos + tag.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c == '>' || c.is_ascii_whitespace())
+            })
+            .count()
+    };
+    (count("<a"), count("</a"))
+}
+
+/// Add text events, with urls replaced by links
+fn push_autolinked(events: &mut Vec<Event>, text: &str) {
+    use pulldown_cmark::LinkType;
+
+    let mut rest = text;
+    while let Some((start, end)) = find_url(rest) {
+        if start > 0 {
+            events.push(Event::Text(rest[..start].to_string().into()));
+        }
+        let url = rest[start..end].to_string();
+        events.push(Event::Start(Tag::Link(
+            LinkType::Autolink,
+            url.clone().into(),
+            "".into(),
+        )));
+        events.push(Event::Text(url.clone().into()));
+        events.push(Event::End(Tag::Link(
+            LinkType::Autolink,
+            url.into(),
+            "".into(),
+        )));
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        events.push(Event::Text(rest.to_string().into()));
+    }
+}
+
+/// Find the first url in text, starting with "http://" or "https://" at the start of a word.
+/// The url ends at whitespace or '<'. Trailing punctuation, and a ')' without a matching
+/// '(' in the url, are not part of the url. Returns the start and end byte offsets.
+fn find_url(text: &str) -> Option<(usize, usize)> {
+    let mut from = 0;
+    while let Some(pos) = text[from..].find("http") {
+        let start = from + pos;
+        from = start + 4;
+        let word_start = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || "([{\"'".contains(c));
+        let after_scheme = ["https://", "http://"]
+            .iter()
+            .find(|scheme| text[start..].starts_with(*scheme))
+            .map(|scheme| start + scheme.len());
+        let after_scheme = match (word_start, after_scheme) {
+            (true, Some(ix)) => ix,
+            _ => continue,
+        };
+        let mut end = text[after_scheme..]
+            .find(|c: char| c.is_whitespace() || c == '<')
+            .map_or(text.len(), |ix| after_scheme + ix);
+        loop {
+            let url = &text[start..end];
+            match url.chars().next_back() {
+                Some(c) if ".,:;!?'\"".contains(c) => end -= 1,
+                Some(')') if url.matches(')').count() > url.matches('(').count() => end -= 1,
+                _ => break,
+            }
+        }
+        if end > after_scheme {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+/// Change destination of links to `.md` files to `.html`
+fn rewrite_md_links(events: &mut [Event]) {
+    for event in events.iter_mut() {
+        if let Event::Start(Tag::Link(link_type, dest, title)) = event {
+            if let Some(new_dest) = md_link_to_html(dest) {
+                *event = Event::Start(Tag::Link(*link_type, new_dest.into(), title.clone()));
+            }
+        }
+    }
+}
+
+/// If the link is to a `.md` file, returns the link with the extension changed to `.html`.
+/// Links are classified with `resolve::ref_kind`, like the link checker, and only
+/// relative internal links are changed.
+fn md_link_to_html(dest: &str) -> Option<String> {
+    use crate::resolve::{ref_kind, split_ref, RefKind};
+
+    // root-relative links are absolute
+    if dest.starts_with('/') || ref_kind(dest) != RefKind::Internal {
+        return None;
+    }
+    let (path, _, _) = split_ref(dest);
+    let stem = path.strip_suffix(".md")?;
+    if stem.is_empty() || stem.ends_with('/') {
+        return None;
+    }
+    Some(format!("{}.html{}", stem, &dest[path.len()..]))
+}
+
+/// Text of link, from events following the link start, through the link end
+fn link_text(events: &[Event]) -> String {
+    events
+        .iter()
+        .take_while(|e| !matches!(e, Event::End(Tag::Link(..))))
+        .filter_map(|e| match e {
+            Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Generate TOC item: html link inside a list item tag
+fn toc_item_html(href: &str, text: &str) -> String {
+    format!(
+        "{begin}<a href=\"#{href}\">{text}</a>{end}",
+        begin = TOC_ITEM,
+        href = href,
+        text = text,
+        end = TOC_END_ITEM,
+    )
+}
+
+/// Use headings array to generate TOC in HTML.
+/// Headings at min_depth are at the top level of the TOC.
+fn generate_toc_html(
+    headings: &[Heading],
+    min_depth: u8,
+    max_depth: u8,
+    style: TocStyle,
+) -> String {
     use std::cmp::Ordering;
 
+    let min_depth = min_depth.max(1);
+    if style == TocStyle::UnorderedList {
+        return generate_toc_list(
+            headings
+                .iter()
+                .filter(|h| h.level >= min_depth && h.level <= max_depth),
+        );
+    }
     let mut html = String::with_capacity(headings.len() * 15);
     let mut indent: u8 = 0;
     for h in headings
         .iter()
-        .filter(|h| h.level >= 1 && h.level <= max_depth)
+        .filter(|h| h.level >= min_depth && h.level <= max_depth)
     {
-        match h.level.cmp(&indent) {
+        let level = h.level - min_depth + 1;
+        match level.cmp(&indent) {
             Ordering::Greater => {
-                html.push_str(&TOC_INDENT.repeat((h.level - indent) as usize));
-                indent = h.level;
+                html.push_str(&TOC_INDENT.repeat((level - indent) as usize));
+                indent = level;
             }
             Ordering::Less => {
-                html.push_str(&TOC_END_INDENT.repeat((indent - h.level) as usize));
-                indent = h.level;
+                html.push_str(&TOC_END_INDENT.repeat((indent - level) as usize));
+                indent = level;
             }
             Ordering::Equal => {}
         }
@@ -194,6 +2184,148 @@ fn generate_toc_html(headings: &[Heading], max_depth: u8) -> String {
     html
 }
 
+/// Generate TOC as nested `<ul>` lists. Each nested list is inside the `<li>` of
+/// its parent heading, and a heading more than one level below the previous
+/// heading is nested one level deeper, so the lists are always well-formed.
+/// A heading between the levels of the parent and the current list (h3 after
+/// h2, h4) joins the current list.
+fn generate_toc_list<'h>(headings: impl Iterator<Item = &'h Heading>) -> String {
+    let mut html = String::new();
+    // heading level of the last item in each open list
+    let mut open: Vec<u8> = Vec::new();
+    for h in headings {
+        while open.len() > 1 && h.level <= open[open.len() - 2] {
+            open.pop();
+            html.push_str(TOC_END_LIST_ITEM);
+            html.push_str(TOC_END_LIST);
+        }
+        match open.last_mut() {
+            Some(level) if h.level > *level => {
+                html.push_str(TOC_LIST);
+                open.push(h.level);
+            }
+            Some(level) => {
+                html.push_str(TOC_END_LIST_ITEM);
+                *level = h.level;
+            }
+            None => {
+                html.push_str(TOC_LIST);
+                open.push(h.level);
+            }
+        }
+        html.push_str(&format!(
+            "{}<a href=\"#{}\">{}</a>",
+            TOC_LIST_ITEM, h.slug, h.text
+        ));
+    }
+    for _ in open.iter() {
+        html.push_str(TOC_END_LIST_ITEM);
+        html.push_str(TOC_END_LIST);
+    }
+    html
+}
+
+/// Convert markdown to plain text, for example, for email or notifications.
+/// Block structure is kept with blank lines between blocks and "- " list bullets,
+/// code is kept verbatim, html is removed, and link urls follow the link text
+/// in parentheses. This is formatted for reading, unlike `plain_text`, which is for
+/// search indexes and word counts. Both parse markdown with the same extensions.
+/// Markdown parameter should not have frontmatter.
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    // next number for each open list, or None for unordered lists
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut link_dests: Vec<String> = Vec::new();
+    let mut link_text_start = 0;
+    for event in Parser::new_ext(markdown, parser_options(&ParseOptions::default())) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::TaskListMarker(checked) => text.push_str(if checked { "[x] " } else { "[ ] " }),
+            Event::FootnoteReference(label) => text.push_str(&format!("[{}]", label)),
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                text.push_str(&format!("[{}]: ", label))
+            }
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Start(Tag::List(start)) => {
+                if !lists.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                lists.push(start)
+            }
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        text.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::End(Tag::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::End(Tag::Paragraph) if !lists.is_empty() => {}
+            Event::End(Tag::Paragraph) | Event::End(Tag::Heading(_)) => text.push_str("\n\n"),
+            Event::End(Tag::CodeBlock(_)) => text.push('\n'),
+            Event::End(Tag::TableRow) | Event::End(Tag::TableHead) => text.push('\n'),
+            Event::End(Tag::TableCell) => text.push('\t'),
+            Event::End(Tag::Table(_)) => text.push('\n'),
+            Event::Rule => text.push_str("---\n\n"),
+            Event::Start(Tag::Link(_, dest, _)) => {
+                link_dests.push(dest.to_string());
+                link_text_start = text.len();
+            }
+            Event::End(Tag::Link(..)) => {
+                if let Some(dest) = link_dests.pop() {
+                    if !dest.is_empty() && text[link_text_start..] != dest {
+                        text.push_str(&format!(" ({})", dest));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let len = text.trim_end().len();
+    text.truncate(len);
+    text.push('\n');
+    text
+}
+
+/// Generate TOC as a nested tree, for client-side navigation. Each node is an object
+/// `{level, text, slug, children}`, where children is an array of nodes.
+/// Headings deeper than max_depth are omitted. If levels are skipped (e.g., h2 followed
+/// by h4), the deeper heading is a child of the nearest preceding shallower heading.
+pub fn toc_json(headings: &[HeadingInfo], max_depth: u8) -> JsonValue {
+    let items = headings
+        .iter()
+        .filter(|h| h.level >= 1 && h.level <= max_depth)
+        .collect::<Vec<_>>();
+    let mut ix = 0;
+    JsonValue::Array(toc_tree(&items, &mut ix, 0))
+}
+
+/// Collect nodes deeper than parent_level, starting at ix
+fn toc_tree(items: &[&HeadingInfo], ix: &mut usize, parent_level: u8) -> Vec<JsonValue> {
+    let mut nodes = Vec::new();
+    while *ix < items.len() && items[*ix].level > parent_level {
+        let h = items[*ix];
+        *ix += 1;
+        let children = toc_tree(items, ix, h.level);
+        nodes.push(json!({
+            "level": h.level,
+            "text": h.text,
+            "slug": h.slug,
+            "children": children,
+        }));
+    }
+    nodes
+}
+
 #[test]
 fn test_slugify() {
     assert_eq!(slugify_heading_for_anchor("a b c"), "a-b-c", "spaces");
@@ -221,3 +2353,1053 @@ fn test_slugify() {
     assert_eq!(slugify_heading_for_anchor("a-b"), "a-b", "dash ok");
     assert_eq!(slugify_heading_for_anchor("α-ω"), "a-o", "no non-ascii");
 }
+
+#[test]
+fn test_line_ending() {
+    let opt = ParseOptions {
+        line_ending: Some(LineEnding::CrLf),
+        ..Default::default()
+    };
+    let html = markdown_to_html("one\n\ntwo\n", &opt).expect("parse");
+    assert_eq!(html.content, "<p>one</p>\r\n<p>two</p>\r\n");
+
+    let opt = ParseOptions {
+        line_ending: Some(LineEnding::Lf),
+        ..Default::default()
+    };
+    let html = markdown_to_html("one\r\n\r\n```\ncode\r\n```\r\n", &opt).expect("parse");
+    assert!(!html.content.contains('\r'), "no carriage returns");
+    assert_eq!(html.content, "<p>one</p>\n<pre><code>code\n</code></pre>\n");
+}
+
+#[test]
+fn test_unicode_slugs() {
+    let md = "<!-- toc -->\n# Привет, мир!\n\n## 日本語\n\n## 日本語\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert!(
+        html.content.contains("<h1 id=\"privet-mir\">"),
+        "{}",
+        html.content
+    );
+    let slugs = html
+        .headings
+        .iter()
+        .map(|h| h.slug.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(slugs, vec!["privet-mir", "ri-ben-yu", "ri-ben-yu-1"]);
+
+    let opt = ParseOptions::builder().unicode_slugs(true).build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    let slugs = html
+        .headings
+        .iter()
+        .map(|h| h.slug.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(slugs, vec!["привет-мир", "日本語", "日本語-1"]);
+    assert!(html.content.contains("<h1 id=\"привет-мир\">"));
+    assert!(html.content.contains("<h2 id=\"日本語-1\">"));
+    let toc = html.toc.expect("toc");
+    assert!(toc.contains("href=\"#привет-мир\""), "{}", toc);
+    assert!(toc.contains("href=\"#日本語\""));
+
+    let opts = SlugOptions {
+        unicode: true,
+        max_len: Some(8),
+        ..Default::default()
+    };
+    assert_eq!(slugify("Ärger über Öl", &opts), "ärger", "cut at word");
+    assert_eq!(slugify("東京タワーの高さ", &opts), "東京タワーの高さ");
+    assert_eq!(
+        slugify("東京タワーの高さは", &opts),
+        "東京タワーの高さ",
+        "cut chars"
+    );
+}
+
+#[test]
+fn test_slugify_options() {
+    let opts = SlugOptions::default();
+    assert_eq!(slugify("a b c", &opts), "a-b-c", "spaces");
+    assert_eq!(slugify("  a  ", &opts), "a", "trim spaces");
+    assert_eq!(slugify("-a-b-", &opts), "a-b", "trim dashes");
+    assert_eq!(slugify("\ta*/+()b", &opts), "a-b", "dashes coalesce");
+    assert_eq!(slugify("a__b", &opts), "a-b", "replace underscore");
+    assert_eq!(slugify("a.b", &opts), "a-b", "replace period");
+    assert_eq!(slugify("α-ω", &opts), "a-o", "no non-ascii");
+    assert_eq!(slugify("Where am I?", &opts), "where-am-i", "lower case");
+
+    let opts = SlugOptions {
+        separator: '_',
+        ..Default::default()
+    };
+    assert_eq!(slugify("Hello, World", &opts), "hello_world", "separator");
+
+    let opts = SlugOptions {
+        max_len: Some(12),
+        ..Default::default()
+    };
+    assert_eq!(
+        slugify("one two three four", &opts),
+        "one-two",
+        "word boundary"
+    );
+    assert_eq!(
+        slugify("abcdefghijklmnop", &opts),
+        "abcdefghijkl",
+        "long word"
+    );
+    assert_eq!(slugify("short", &opts), "short", "under limit");
+}
+
+#[test]
+fn test_toc_flags() {
+    let (markup, flags) = take_toc_flags("<!-- toc -->\n", false, true);
+    assert_eq!(markup, "\n");
+    assert_eq!(
+        flags,
+        vec![TocFlag {
+            name: "toc".into(),
+            depth: None
+        }]
+    );
+
+    let (markup, flags) = take_toc_flags(
+        "<p><!-- toc-sidebar depth=2 --></p><!-- today -->",
+        false,
+        true,
+    );
+    assert_eq!(markup, "<p></p><!-- today -->", "non-toc comment kept");
+    assert_eq!(
+        flags,
+        vec![TocFlag {
+            name: "toc-sidebar".into(),
+            depth: Some(2)
+        }]
+    );
+
+    let (markup, flags) = take_toc_flags("<!-- toc size=2 -->", false, true);
+    assert_eq!(markup, "<!-- toc size=2 -->", "unknown param: not a flag");
+    assert!(flags.is_empty());
+}
+
+#[test]
+fn test_named_tocs() {
+    let md = "<!-- toc -->\n\n<!-- toc-sidebar depth=2 -->\n\n# One\n\n## Two\n\n### Three\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert!(!html.content.contains("<!--"), "flags removed");
+
+    let full = html.tocs.get("toc").expect("full toc");
+    assert_eq!(html.toc.as_ref(), Some(full));
+    assert!(full.contains("href=\"#one\""));
+    assert!(full.contains("href=\"#three\""));
+
+    let sidebar = html.tocs.get("toc-sidebar").expect("sidebar toc");
+    assert!(sidebar.contains("href=\"#one\""));
+    assert!(sidebar.contains("href=\"#two\""));
+    assert!(!sidebar.contains("href=\"#three\""), "depth limited");
+
+    let html = markdown_to_html_default("# One\n").expect("parse");
+    assert!(html.toc.is_none());
+    assert!(html.tocs.is_empty());
+}
+
+#[test]
+fn test_toc_marker() {
+    let md = "[[TOC]]\n\n# One\n\n## Two\n\nSee [[TOC]] above.\n";
+    let opt = ParseOptions::builder().toc_marker("[[TOC]]").build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    let toc = html.toc.expect("toc");
+    assert!(toc.contains("href=\"#one\""));
+    assert!(toc.contains("href=\"#two\""));
+    assert!(
+        html.content.starts_with("<h1 id=\"one\">"),
+        "marker removed: {}",
+        html.content
+    );
+    assert!(
+        html.content.contains("<p>See [[TOC]] above.</p>"),
+        "marker must be on its own line"
+    );
+
+    let opt = ParseOptions::builder().toc_marker("{:toc}").build();
+    let html = markdown_to_html("# One\n\n{:toc}\n\n## Two\n", &opt).expect("parse");
+    assert!(html.toc.is_some());
+    assert!(!html.content.contains("{:toc}"));
+
+    // a custom marker replaces the default flag, which is left in the content
+    let html = markdown_to_html("<!-- toc -->\n# One\n", &opt).expect("parse");
+    assert!(html.toc.is_none());
+    assert!(html.content.contains("<!-- toc -->"));
+    // named tocs work with any marker
+    let html = markdown_to_html("<!-- toc-sidebar -->\n# One\n", &opt).expect("parse");
+    assert!(html.tocs.contains_key("toc-sidebar"));
+
+    // default marker
+    assert_eq!(ParseOptions::default().toc_marker, "<!-- toc -->");
+    let html = markdown_to_html_default("<!-- toc -->\n# One\n").expect("parse");
+    assert!(html.toc.expect("toc").contains("href=\"#one\""));
+    assert!(!html.content.contains("<!--"));
+    let html = markdown_to_html_default("[[TOC]]\n\n# One\n").expect("parse");
+    assert!(html.toc.is_none());
+    assert!(html.content.contains("<p>[[TOC]]</p>"));
+}
+
+#[test]
+fn test_inline_toc() {
+    let md = "# Guide\n\nIntro.\n\n<!-- toc depth=2 -->\n\n## Setup\n\n### Details\n";
+    let opt = ParseOptions::builder().inline_toc(true).build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    let toc = html.toc.expect("toc field is still set");
+    assert!(toc.contains("href=\"#setup\""));
+    assert!(!toc.contains("href=\"#details\""), "flag depth");
+    assert_eq!(
+        html.content,
+        format!(
+            "<h1 id=\"guide\">Guide</h1>\n<p>Intro.</p>\n{}\n<h2 id=\"setup\">Setup</h2>\n\
+             <h3 id=\"details\">Details</h3>\n",
+            toc
+        ),
+        "toc at the flag position"
+    );
+
+    // custom marker, with a named toc that isn't inlined
+    let opt = ParseOptions::builder()
+        .inline_toc(true)
+        .toc_marker("[[TOC]]")
+        .build();
+    let md = "<!-- toc-sidebar -->\n\n# One\n\n[[TOC]]\n\n## Two\n";
+    let html = markdown_to_html(md, &opt).expect("parse");
+    let toc = html.toc.expect("toc");
+    assert!(html.tocs.contains_key("toc-sidebar"));
+    assert_eq!(
+        html.content,
+        format!(
+            "\n<h1 id=\"one\">One</h1>\n{}\n<h2 id=\"two\">Two</h2>\n",
+            toc
+        )
+    );
+
+    // default: flag is removed from the content
+    let html = markdown_to_html_default("<!-- toc -->\n\n# One\n").expect("parse");
+    assert_eq!(html.content, "\n<h1 id=\"one\">One</h1>\n");
+}
+
+#[test]
+fn test_admonitions() {
+    let md = "> [!NOTE]\n> Read **this** first.\n\n> Just a quote.\n";
+    let opt = ParseOptions::builder().admonitions(true).build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        concat!(
+            "<div class=\"admonition note\">\n<p class=\"admonition-title\">Note</p>\n",
+            "<p>Read <strong>this</strong> first.</p>\n</div>\n",
+            "<blockquote>\n<p>Just a quote.</p>\n</blockquote>\n"
+        )
+    );
+
+    // marker in its own paragraph, mapped class, nested blockquote
+    let md = "> [!warning]\n>\n> Careful.\n>\n> > inner\n";
+    let opt = ParseOptions::builder()
+        .admonitions(true)
+        .admonition_class("WARNING", "is-danger")
+        .build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        concat!(
+            "<div class=\"admonition is-danger\">\n<p class=\"admonition-title\">Warning</p>\n",
+            "<p>Careful.</p>\n<blockquote>\n<p>inner</p>\n</blockquote>\n</div>\n"
+        )
+    );
+
+    // unknown type, marker not alone on its line, and default options: plain blockquotes
+    let enabled = ParseOptions::builder().admonitions(true).build();
+    let cases = [
+        ("> [!BOGUS]\n> text\n", &enabled),
+        ("> [!NOTE] text\n", &enabled),
+        ("> [!TIP]\n> text\n", &ParseOptions::default()),
+    ];
+    for (md, opt) in cases.iter() {
+        let html = markdown_to_html(md, opt).expect("parse");
+        assert!(html.content.starts_with("<blockquote>"), "{}", html.content);
+    }
+}
+
+#[test]
+fn test_autolink() {
+    let opt = ParseOptions::builder().autolink(true).build();
+    let md = "See https://example.com/a_b?x=1&y=2. Or (http://example.com/wiki/Rust_(lang)).\n\n\
+              Code `https://example.com/code` and [text https://example.com/in](https://x.org)\n\n\
+              ```\nhttps://example.com/block\n```\n";
+    let html = markdown_to_html(md, &opt).expect("parse").content;
+    assert!(
+        html.contains(
+            "See <a href=\"https://example.com/a_b?x=1&amp;y=2\">\
+             https://example.com/a_b?x=1&amp;y=2</a>. Or "
+        ),
+        "{}",
+        html
+    );
+    assert!(html.contains(
+        "(<a href=\"http://example.com/wiki/Rust_(lang)\">http://example.com/wiki/Rust_(lang)</a>)."
+    ));
+    assert!(
+        html.contains("<code>https://example.com/code</code>"),
+        "code span"
+    );
+    assert!(html.contains("<a href=\"https://x.org\">text https://example.com/in</a>"));
+    assert!(
+        html.contains("<code>https://example.com/block\n</code>"),
+        "code block"
+    );
+    assert!(!html.contains("href=\"https://example.com/code"));
+
+    // text inside raw html anchors is already linked
+    assert_eq!(
+        markdown_to_html(
+            "<a href=\"https://a.com\">https://a.com</a> then https://b.com",
+            &opt
+        )
+        .expect("parse")
+        .content,
+        "<p><a href=\"https://a.com\">https://a.com</a> then \
+         <a href=\"https://b.com\">https://b.com</a></p>\n"
+    );
+
+    assert_eq!(
+        markdown_to_html("xhttps://a.com and http:// alone", &opt)
+            .expect("parse")
+            .content,
+        "<p>xhttps://a.com and http:// alone</p>\n"
+    );
+    let html = markdown_to_html_default("See https://example.com").expect("parse");
+    assert_eq!(
+        html.content, "<p>See https://example.com</p>\n",
+        "off by default"
+    );
+}
+
+#[test]
+fn test_content_wrapper() {
+    let opt = ParseOptions {
+        content_wrapper: Some(("<article class=\"prose\">".into(), "</article>".into())),
+        ..Default::default()
+    };
+    let html = markdown_to_html("hello", &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        "<article class=\"prose\"><p>hello</p>\n</article>"
+    );
+
+    let html = markdown_to_html_default("hello").expect("parse");
+    assert_eq!(html.content, "<p>hello</p>\n", "no wrapper by default");
+}
+
+#[test]
+fn test_toc_json() {
+    let html =
+        markdown_to_html_default("<!-- toc -->\n# Top\n## First\n## Second\n").expect("parse");
+    assert_eq!(html.headings.len(), 3);
+    assert_eq!(
+        toc_json(&html.headings, MAX_TOC_DEPTH),
+        json!([{
+            "level": 1, "text": "Top", "slug": "top", "children": [
+                { "level": 2, "text": "First", "slug": "first", "children": [] },
+                { "level": 2, "text": "Second", "slug": "second", "children": [] },
+            ]
+        }])
+    );
+    assert_eq!(
+        toc_json(&html.headings, 1),
+        json!([{ "level": 1, "text": "Top", "slug": "top", "children": [] }])
+    );
+}
+
+#[test]
+fn test_anchor_style() {
+    let md = "<!-- toc -->\n## Hello\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert!(
+        html.content.contains("<h2 id=\"hello\">Hello</h2>"),
+        "id only"
+    );
+    assert!(!html.content.contains("<a name"));
+
+    let opt = ParseOptions {
+        anchor_style: AnchorStyle::Name,
+        ..Default::default()
+    };
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(
+        html.content
+            .contains("<a name=\"hello\"></a><h2>Hello</h2>"),
+        "name only"
+    );
+
+    let opt = ParseOptions {
+        anchor_style: AnchorStyle::Both,
+        ..Default::default()
+    };
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(
+        html.content
+            .contains("<a name=\"hello\"></a><h2 id=\"hello\">Hello</h2>"),
+        "both"
+    );
+}
+
+#[test]
+fn test_markdown_to_text() {
+    let md = "# Title\n\nSome *emphasis* and a [link](https://example.com).\n\n- one\n- two\n\n1. first\n2. second\n\n<div>html</div>\n\n```\nlet x = 1;\n```\n";
+    assert_eq!(
+        markdown_to_text(md),
+        "Title\n\nSome emphasis and a link (https://example.com).\n\n- one\n- two\n\n1. first\n2. second\n\nlet x = 1;\n"
+    );
+    assert_eq!(
+        markdown_to_text("- [x] ~~done~~ see[^1]\n\n[^1]: Note.\n"),
+        "- [x] done see[1]\n\n[1]: Note.\n"
+    );
+}
+
+#[test]
+fn test_empty_links() {
+    let md = "see [the docs]()";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert_eq!(
+        html.content, "<p>see <a href=\"#\">the docs</a></p>\n",
+        "default"
+    );
+    assert!(html.diagnostics.is_empty());
+
+    let opt = ParseOptions {
+        empty_links: EmptyLinks::Empty,
+        ..Default::default()
+    };
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert_eq!(
+        html.content, "<p>see <a href=\"\">the docs</a></p>\n",
+        "empty"
+    );
+
+    let opt = ParseOptions {
+        empty_links: EmptyLinks::Diagnostic,
+        ..Default::default()
+    };
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert_eq!(html.content, "<p>see <a href=\"#\">the docs</a></p>\n");
+    assert_eq!(
+        html.diagnostics,
+        vec!["link 'the docs' has empty destination"]
+    );
+
+    let opt = ParseOptions {
+        empty_links: EmptyLinks::Error,
+        ..Default::default()
+    };
+    assert!(matches!(
+        markdown_to_html(md, &opt),
+        Err(Error::EmptyLink(text)) if text == "the docs"
+    ));
+}
+
+#[test]
+fn test_code_block_wrappers() {
+    let mut opt = ParseOptions::default();
+    opt.code_block_wrappers
+        .insert("mermaid".into(), CodeBlockWrapper::new("pre", "mermaid"));
+    opt.code_block_wrappers
+        .insert("dot".into(), CodeBlockWrapper::new("div", "graphviz"));
+
+    let md = "```mermaid\ngraph TD;\n  A-->B;\n```\n\n```dot\ndigraph { a -> b }\n```\n\n```rust\nlet x = 1;\n```\n";
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(
+        html.content
+            .contains("<pre class=\"mermaid\">graph TD;\n  A--&gt;B;\n</pre>"),
+        "mermaid wrapped: {}",
+        html.content
+    );
+    assert!(html
+        .content
+        .contains("<div class=\"graphviz\">digraph { a -&gt; b }\n</div>"));
+    assert!(
+        html.content
+            .contains("<pre><code class=\"language-rust\">let x = 1;\n</code></pre>"),
+        "rust block normal"
+    );
+
+    // raw passthrough
+    let opt = ParseOptions::builder()
+        .code_block_wrapper("mermaid", CodeBlockWrapper::raw("div", "mermaid"))
+        .build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(
+        html.content
+            .contains("<div class=\"mermaid\">graph TD;\n  A-->B;\n</div>"),
+        "mermaid source not escaped: {}",
+        html.content
+    );
+    assert!(html
+        .content
+        .contains("<pre><code class=\"language-rust\">let x = 1;\n</code></pre>"));
+    assert!(html
+        .content
+        .contains("<pre><code class=\"language-dot\">digraph { a -&gt; b }\n</code></pre>"));
+}
+
+#[test]
+fn test_duplicate_heading_slugs() {
+    let html = markdown_to_html_default("<!-- toc -->\n## Foo\n## Foo\n## Foo\n").expect("parse");
+    let slugs = html
+        .headings
+        .iter()
+        .map(|h| h.slug.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(slugs, vec!["foo", "foo-1", "foo-2"]);
+    for slug in slugs.iter() {
+        assert!(html
+            .content
+            .contains(&format!("<h2 id=\"{}\">Foo</h2>", slug)));
+        assert!(html
+            .toc
+            .as_ref()
+            .unwrap()
+            .contains(&format!("<a href=\"#{}\">Foo</a>", slug)));
+    }
+
+    // a generated suffix doesn't collide with a heading's own slug
+    let html = markdown_to_html_default("<!-- toc -->\n## Foo 1\n## Foo\n## Foo\n").expect("parse");
+    let slugs = html
+        .headings
+        .iter()
+        .map(|h| h.slug.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(slugs, vec!["foo-1", "foo", "foo-2"]);
+}
+
+#[test]
+fn test_element_classes() {
+    let mut opt = ParseOptions::default();
+    opt.element_classes.insert("p".into(), "lead".into());
+    opt.element_classes.insert("h2".into(), "title is-2".into());
+    opt.element_classes.insert("table".into(), "table".into());
+
+    let md = "# One\n\n## Two\n\ntext\n\n| a | b |\n|:--|--:|\n| 1 | 2 |\n";
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        "<h1>One</h1>\n<h2 class=\"title is-2\">Two</h2>\n<p class=\"lead\">text</p>\n\
+         <table class=\"table\"><thead><tr><th align=\"left\">a</th><th align=\"right\">b</th></tr></thead>\
+         <tbody>\n<tr><td align=\"left\">1</td><td align=\"right\">2</td></tr>\n</tbody></table>\n"
+    );
+
+    // classes combine with heading ids
+    let html = markdown_to_html("<!-- toc -->\n## Two\n", &opt).expect("parse");
+    assert!(html
+        .content
+        .contains("<h2 id=\"two\" class=\"title is-2\">Two</h2>"));
+}
+
+#[test]
+fn test_toc_depth_options() {
+    let md = "<!-- toc -->\n# Title\n\n## Two\n\n### Three\n\n#### Four\n";
+    let opt = ParseOptions {
+        min_toc_depth: Some(2),
+        max_toc_depth: Some(3),
+        ..Default::default()
+    };
+    let toc = markdown_to_html(md, &opt).expect("parse").toc.unwrap();
+    assert!(!toc.contains("href=\"#title\""), "h1 excluded");
+    assert!(toc.contains("href=\"#two\""));
+    assert!(toc.contains("href=\"#three\""));
+    assert!(!toc.contains("href=\"#four\""), "h4 excluded");
+    assert!(
+        toc.starts_with(TOC_INDENT) && !toc[TOC_INDENT.len()..].starts_with(TOC_INDENT),
+        "h2 at top level"
+    );
+
+    // default includes h1..h4
+    let toc = markdown_to_html_default(md).expect("parse").toc.unwrap();
+    assert!(toc.contains("href=\"#title\""));
+    assert!(toc.contains("href=\"#four\""));
+}
+
+#[test]
+fn test_toc_style() {
+    let md = "<!-- toc -->\n# A\n\n## B\n\n#### C\n\n### D\n\n## E\n\n# F\n";
+    let toc = markdown_to_html_default(md).expect("parse").toc.unwrap();
+    assert_eq!(
+        toc,
+        concat!(
+            "<div><p><a href=\"#a\">A</a></p>",
+            "<div><p><a href=\"#b\">B</a></p>",
+            "<div><div><p><a href=\"#c\">C</a></p></div>",
+            "<p><a href=\"#d\">D</a></p></div>",
+            "<p><a href=\"#e\">E</a></p></div>",
+            "<p><a href=\"#f\">F</a></p></div>"
+        )
+    );
+
+    let opt = ParseOptions::builder()
+        .toc_style(TocStyle::UnorderedList)
+        .build();
+    let toc = markdown_to_html(md, &opt).expect("parse").toc.unwrap();
+    assert_eq!(
+        toc,
+        concat!(
+            "<ul><li><a href=\"#a\">A</a>",
+            "<ul><li><a href=\"#b\">B</a>",
+            // h4 after h2 is nested one level, and the h3 after it is a sibling
+            "<ul><li><a href=\"#c\">C</a></li>",
+            "<li><a href=\"#d\">D</a></li></ul></li>",
+            "<li><a href=\"#e\">E</a></li></ul></li>",
+            "<li><a href=\"#f\">F</a></li></ul>"
+        )
+    );
+
+    // first heading below the top level
+    let toc = markdown_to_html("<!-- toc -->\n### A\n\n## B\n\n### C\n", &opt)
+        .expect("parse")
+        .toc
+        .unwrap();
+    assert_eq!(
+        toc,
+        concat!(
+            "<ul><li><a href=\"#a\">A</a></li>",
+            "<li><a href=\"#b\">B</a><ul><li><a href=\"#c\">C</a></li></ul></li></ul>"
+        )
+    );
+}
+
+#[test]
+fn test_rewrite_md_links() {
+    let opt = ParseOptions {
+        rewrite_md_links: true,
+        ..Default::default()
+    };
+    let md =
+        "[rel](./a/b.md#sec) [up](../c.md?x=1) [root](/d/e.md) [abs](https://example.com/f.md) \
+              [anchor](#g.md) [mail](mailto:me@example.com) [asset](h.mdx)";
+    let html = markdown_to_html(md, &opt).expect("parse").content;
+    assert!(
+        html.contains("<a href=\"./a/b.html#sec\">rel</a>"),
+        "{}",
+        html
+    );
+    assert!(html.contains("<a href=\"../c.html?x=1\">up</a>"));
+    assert!(
+        html.contains("<a href=\"/d/e.md\">root</a>"),
+        "root-relative is absolute"
+    );
+    assert!(html.contains("<a href=\"https://example.com/f.md\">abs</a>"));
+    assert!(html.contains("<a href=\"#g.md\">anchor</a>"));
+    assert!(html.contains("<a href=\"mailto:me@example.com\">mail</a>"));
+    assert!(html.contains("<a href=\"h.mdx\">asset</a>"));
+
+    // off by default
+    let html = markdown_to_html_default("[rel](./a/b.md)")
+        .expect("parse")
+        .content;
+    assert!(html.contains("<a href=\"./a/b.md\">rel</a>"));
+}
+
+#[test]
+fn test_heading_anchors() {
+    let mut opt = ParseOptions {
+        heading_anchors: true,
+        ..Default::default()
+    };
+    let html = markdown_to_html("## Getting Started\n", &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        "<h2 id=\"getting-started\"><a class=\"anchor\" href=\"#getting-started\" aria-hidden=\"true\">#</a>Getting Started</h2>\n"
+    );
+    assert!(html.toc.is_none());
+
+    opt.heading_anchor = HeadingAnchor {
+        template: r##"<a class="link" href="#{slug}">¶</a>"##.to_string(),
+        position: AnchorPosition::End,
+    };
+    opt.element_classes.insert("h2".into(), "title".into());
+    let html = markdown_to_html("<!-- toc -->\n## Getting Started\n", &opt).expect("parse");
+    assert!(html.content.contains(
+        "<h2 id=\"getting-started\" class=\"title\">Getting Started<a class=\"link\" href=\"#getting-started\">¶</a></h2>\n"
+    ), "{}", html.content);
+}
+
+#[test]
+fn test_incremental() {
+    let opt = ParseOptions::default();
+    let old_md = "# Title\n\nFirst paragraph.\n\nSecond paragraph.\n\n- a\n- b\n";
+    let mut old = markdown_to_html(old_md, &opt).expect("parse");
+    assert_eq!(old.blocks.len(), 4);
+    // mark cached html of other blocks, to check that it's reused
+    old.content = old.content.replace("First", "FIRST");
+
+    let new_md = "# Title\n\nFirst paragraph.\n\nSecond *edited* paragraph.\n\n- a\n- b\n";
+    let html = markdown_to_html_incremental(old_md, new_md, &old, &opt).expect("incremental");
+    let full = markdown_to_html(new_md, &opt).expect("full");
+    assert_eq!(html.content, full.content.replace("First", "FIRST"));
+    assert_eq!(html.blocks, full.blocks);
+
+    // later edits can use the incremental result
+    let newer_md = new_md.replace("- b", "- b\n- c");
+    let html = markdown_to_html_incremental(new_md, &newer_md, &html, &opt).expect("again");
+    let full = markdown_to_html(&newer_md, &opt).expect("full");
+    assert_eq!(html.content, full.content.replace("First", "FIRST"));
+    assert_eq!(html.word_count, full.word_count);
+
+    // editing a heading converts the whole document
+    let new_md = old_md.replace("# Title", "# Retitled");
+    let html = markdown_to_html_incremental(old_md, &new_md, &old, &opt).expect("incremental");
+    assert_eq!(
+        html.content,
+        markdown_to_html(&new_md, &opt).unwrap().content
+    );
+
+    // an edited block can use a reference definition elsewhere in the document
+    let old_md = "See foo there.\n\nOther.\n\n[foo]: https://example.com\n";
+    let new_md = old_md.replace("See foo", "See [foo]");
+    let old = markdown_to_html(old_md, &opt).expect("parse");
+    let html = markdown_to_html_incremental(old_md, &new_md, &old, &opt).expect("incremental");
+    assert!(html
+        .content
+        .contains("<p>See <a href=\"https://example.com\">foo</a> there.</p>"));
+    assert_eq!(
+        html.content,
+        markdown_to_html(&new_md, &opt).unwrap().content
+    );
+}
+
+#[test]
+fn test_scan_headings() {
+    let md = "# Guide\n\n## Install\n\ntext\n\n### From source\n";
+    assert!(markdown_to_html_default(md).unwrap().headings.is_empty());
+
+    let opt = ParseOptions {
+        scan_headings: true,
+        ..Default::default()
+    };
+    let html = markdown_to_html(md, &opt).expect("parse");
+    let expected = [
+        (1, "Guide", "guide"),
+        (2, "Install", "install"),
+        (3, "From source", "from-source"),
+    ];
+    assert_eq!(html.headings.len(), 3);
+    for (h, (level, text, slug)) in html.headings.iter().zip(expected.iter()) {
+        assert_eq!(h.level, *level);
+        assert_eq!(h.text, *text);
+        assert_eq!(h.slug, *slug);
+    }
+    assert!(html.content.contains("<h2>Install</h2>"), "html unchanged");
+}
+
+#[test]
+fn test_section_tocs() {
+    let md = "# One\n\n<!-- toc-section -->\n\n## One A\n\n### One A i\n\n## One B\n\n\
+              # Two\n\n<!-- toc-section depth=2 -->\n\n## Two A\n\n### Two A i\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert!(html.tocs.is_empty(), "section tocs are inline");
+
+    let (one, two) = html
+        .content
+        .split_at(html.content.find("<h1 id=\"two\"").unwrap());
+    assert!(one.contains("href=\"#one-a\""), "{}", one);
+    assert!(one.contains("href=\"#one-a-i\""));
+    assert!(one.contains("href=\"#one-b\""));
+    assert!(!one.contains("href=\"#two-a\""), "scoped to section");
+    assert!(!one.contains("href=\"#one\""), "h1 not included");
+
+    assert!(two.contains("href=\"#two-a\""));
+    assert!(!two.contains("href=\"#two-a-i\""), "depth limited");
+    assert!(!two.contains("href=\"#one-a\""), "scoped to section");
+    assert!(!html.content.contains("<!-- toc"), "flags replaced");
+
+    let opt = ParseOptions::builder().min_toc_depth(3).build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(html.content.contains("href=\"#one-a-i\""));
+    assert!(!html.content.contains("href=\"#one-a\""), "min depth");
+}
+
+#[test]
+fn test_parse_options_builder() {
+    let opt = ParseOptions::builder().build();
+    assert_eq!(opt.max_toc_depth, None);
+    assert!(!opt.rewrite_md_links);
+
+    let opt = ParseOptions::builder()
+        .toc_depth(2)
+        .rewrite_md_links(true)
+        .element_class("p", "lead")
+        .build();
+    assert_eq!(opt.max_toc_depth, Some(2));
+    let html = markdown_to_html(
+        "<!-- toc -->\n# Top\n## Second\n### Third\n\n[next](next.md)\n",
+        &opt,
+    )
+    .expect("parse");
+    let toc = html.toc.expect("toc");
+    assert!(toc.contains("#second"));
+    assert!(!toc.contains("#third"), "toc depth");
+    assert!(html
+        .content
+        .contains("<p class=\"lead\"><a href=\"next.html\">next</a></p>"));
+
+    let opt = ParseOptions::builder()
+        .heading_anchor(HeadingAnchor {
+            template: "<a href=\"#{slug}\">§</a>".into(),
+            position: AnchorPosition::End,
+        })
+        .build();
+    assert!(opt.heading_anchors);
+    let html = markdown_to_html("## Sec\n", &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        "<h2 id=\"sec\">Sec<a href=\"#sec\">§</a></h2>\n"
+    );
+}
+
+#[test]
+fn test_fragments_to_html() {
+    let fragments = [
+        "Intro[^a] with [ref].\n\n[ref]: https://example.com/ref \"Ref\"\n\n[^a]: note a",
+        "Uses [ref] and [^b], and [^a] again.\n\n[^b]: note b",
+    ];
+    let opt = ParseOptions::builder().footnotes(Footnotes::Inline).build();
+    let html = fragments_to_html(&fragments, &opt).expect("parse");
+    assert_eq!(html.fragments.len(), 2);
+    let (a, b) = (&html.fragments[0], &html.fragments[1]);
+    assert!(a.contains("<a href=\"https://example.com/ref\" title=\"Ref\">ref</a>"));
+    assert!(
+        b.contains("<a href=\"https://example.com/ref\" title=\"Ref\">ref</a>"),
+        "definition from other fragment: {}",
+        b
+    );
+    // footnotes numbered across fragments
+    assert!(a.contains("<a href=\"#a\">1</a>"));
+    assert!(b.contains("<a href=\"#b\">2</a>"));
+    assert!(
+        b.contains("<a href=\"#a\">1</a>"),
+        "same number for same note"
+    );
+    assert!(!b.contains(FRAGMENT_MARKER));
+    assert!(html.page.diagnostics.is_empty());
+    assert_eq!(html.page.content, html.fragments.concat());
+
+    // unclosed fence: converted separately
+    let html = fragments_to_html(&["```\ncode", "[ref]"], &opt).expect("parse");
+    assert_eq!(html.fragments.len(), 2);
+    assert_eq!(html.fragments[1], "<p>[ref]</p>\n");
+    assert_eq!(html.page.diagnostics.len(), 1);
+}
+
+#[test]
+fn test_explicit_ids() {
+    assert_eq!(explicit_id("Setup {#install}"), Some(("Setup", "install")));
+    assert_eq!(explicit_id("Setup {#}"), None);
+    assert_eq!(explicit_id("Set {up}"), None);
+
+    let md = "## Overview\n\n## Details {#overview}\n\n## Overview\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert_eq!(
+        html.content,
+        "<h2 id=\"overview-1\">Overview</h2>\n\
+         <h2 id=\"overview\">Details</h2>\n\
+         <h2 id=\"overview-2\">Overview</h2>\n"
+    );
+    assert_eq!(html.diagnostics.len(), 2, "{:?}", html.diagnostics);
+    // deterministic
+    assert_eq!(markdown_to_html_default(md).unwrap().content, html.content);
+
+    // duplicate explicit ids: the first one wins
+    let md = "<!-- toc -->\n# A {#x}\n# B {#x}\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    let ids = html
+        .headings
+        .iter()
+        .map(|h| (h.text.as_str(), h.slug.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![("A", "x"), ("B", "x-1")]);
+    assert_eq!(html.diagnostics.len(), 1);
+    assert!(html.toc.unwrap().contains("href=\"#x-1\""));
+
+    // id parsing disabled: the attribute stays in the text
+    let opt = ParseOptions::builder().heading_ids(false).build();
+    let html = markdown_to_html("## Setup {#install}\n", &opt).expect("parse");
+    assert_eq!(html.content, "<h2>Setup {#install}</h2>\n");
+    let opt = ParseOptions::builder()
+        .heading_ids(false)
+        .scan_headings(true)
+        .build();
+    let html = markdown_to_html("## Setup {#install}\n", &opt).expect("parse");
+    assert_eq!(html.headings[0].slug, "setup-install");
+
+    // toc links use the explicit id, or the slug for headings without one
+    let md = "<!-- toc -->\n## Quick Start {#start}\n## Next Steps\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert!(html.content.contains("<h2 id=\"start\">Quick Start</h2>"));
+    assert!(html
+        .content
+        .contains("<h2 id=\"next-steps\">Next Steps</h2>"));
+    let toc = html.toc.unwrap();
+    assert!(
+        toc.contains("<a href=\"#start\">Quick Start</a>"),
+        "{}",
+        toc
+    );
+    assert!(
+        toc.contains("<a href=\"#next-steps\">Next Steps</a>"),
+        "{}",
+        toc
+    );
+}
+
+#[test]
+fn test_external_links_new_tab() {
+    let md = "[ext](https://rust-lang.org) [home](https://example.com/about) \
+              [rel](./guide.html) [anchor](#top) [mail](mailto:a@example.com) \
+              [cdn](//cdn.example.org/x)";
+    let new_tab = "target=\"_blank\" rel=\"noopener noreferrer\"";
+
+    // not enabled by default
+    let html = markdown_to_html(md, &ParseOptions::default()).expect("parse");
+    assert!(!html.content.contains(new_tab));
+
+    let opt = ParseOptions::builder()
+        .external_links_new_tab(true)
+        .internal_host("https://example.com/")
+        .element_class("a", "link")
+        .build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        "<p><a href=\"https://rust-lang.org\" class=\"link\" target=\"_blank\" \
+         rel=\"noopener noreferrer\">ext</a> \
+         <a href=\"https://example.com/about\" class=\"link\">home</a> \
+         <a href=\"./guide.html\" class=\"link\">rel</a> \
+         <a href=\"#top\" class=\"link\">anchor</a> \
+         <a href=\"mailto:a@example.com\" class=\"link\" target=\"_blank\" \
+         rel=\"noopener noreferrer\">mail</a> \
+         <a href=\"//cdn.example.org/x\" class=\"link\" target=\"_blank\" \
+         rel=\"noopener noreferrer\">cdn</a></p>\n"
+    );
+}
+
+#[test]
+fn test_smart_punctuation() {
+    let md =
+        "\"Quoted\" and 'single' -- en --- em... `\"code\" -- ...`\n\n```\n\"block\" ...\n```\n";
+    let html = markdown_to_html(md, &ParseOptions::default()).expect("parse");
+    assert!(html
+        .content
+        .starts_with("<p>&quot;Quoted&quot; and 'single' -- en --- em..."));
+
+    let opt = ParseOptions::builder().smart_punctuation(true).build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert_eq!(
+        html.content,
+        "<p>“Quoted” and ‘single’ – en — em… <code>&quot;code&quot; -- ...</code></p>\n\
+         <pre><code>&quot;block&quot; ...\n</code></pre>\n"
+    );
+}
+
+#[test]
+fn test_footnotes() {
+    let md = "Hello[^1].\n\n[^1]: The note.\n\nMore text.\n";
+    let html = markdown_to_html(md, &ParseOptions::default()).expect("parse");
+    assert_eq!(
+        html.content,
+        "<p>Hello<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup>.</p>\n\
+         <p>More text.</p>\n\
+         <section class=\"footnotes\">\n\
+         <div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n\
+         <p>The note.</p>\n\
+         </div>\n\
+         </section>\n"
+    );
+
+    // inline: definition stays where it is
+    let opt = ParseOptions::builder().footnotes(Footnotes::Inline).build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(!html.content.contains("<section"));
+    assert!(html.content.ends_with("<p>More text.</p>\n"));
+
+    // disabled: footnote syntax is plain text
+    let opt = ParseOptions::builder()
+        .footnotes(Footnotes::Disabled)
+        .build();
+    let html = markdown_to_html(md, &opt).expect("parse");
+    assert!(html.content.starts_with("<p>Hello[^1].</p>"));
+}
+
+#[test]
+fn test_excerpt() {
+    let md = "# Post\n\nFirst *paragraph*.\n\n<!-- more -->\n\nThe rest.\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert_eq!(
+        html.excerpt.as_deref(),
+        Some("<h1>Post</h1>\n<p>First <em>paragraph</em>.</p>\n")
+    );
+    assert_eq!(
+        html.content,
+        "<h1>Post</h1>\n<p>First <em>paragraph</em>.</p>\n<p>The rest.</p>\n"
+    );
+
+    // excerpt is standalone html: each element is closed
+    let md = "> quote\n>\n> more\n\n<!-- more -->\n- rest\n";
+    let excerpt = markdown_to_html_default(md).unwrap().excerpt.unwrap();
+    assert_eq!(excerpt.matches("<blockquote>").count(), 1);
+    assert!(excerpt.trim_end().ends_with("</blockquote>"));
+
+    // no marker, or marker inside code
+    assert_eq!(markdown_to_html_default("hello\n").unwrap().excerpt, None);
+    let md = "```\n<!-- more -->\n```\n";
+    assert_eq!(markdown_to_html_default(md).unwrap().excerpt, None);
+
+    // custom marker
+    let opt = ParseOptions::builder()
+        .excerpt_marker("<!--fold-->")
+        .build();
+    let html = markdown_to_html("a\n\n<!--fold-->\n\nb\n", &opt).unwrap();
+    assert_eq!(html.excerpt.as_deref(), Some("<p>a</p>\n"));
+    assert_eq!(html.content, "<p>a</p>\n<p>b</p>\n");
+}
+
+#[test]
+fn test_plain_text() {
+    let md = "# Title\n\nSome **bold** [link](x.html) and `code`.\n\n```\nfn main() {}\n```\n\n![alt](i.png) <b>tag</b>\n";
+    assert_eq!(plain_text(md), "Title Some bold link and . tag");
+    assert_eq!(
+        plain_text_with(md, true),
+        "Title Some bold link and code. fn main() {} tag"
+    );
+    // same words as the word count
+    assert_eq!(
+        plain_text(md).split_whitespace().count(),
+        markdown_to_html_default(md).unwrap().word_count
+    );
+}
+
+#[test]
+fn test_word_count() {
+    // 10 words of text, plus code and html that aren't counted
+    let md = "# Title here\n\nOne *two* three [four](x.md) fi**ve**.\n\n\
+              ```\nlet code = not_counted;\n```\n\n\
+              <div>html block</div>\n\n- six `code` seven\n- eight ![alt text](a.png)\n";
+    let html = markdown_to_html_default(md).expect("parse");
+    assert_eq!(html.word_count, 10);
+    assert_eq!(html.reading_time_minutes, 1);
+
+    let md = "word ".repeat(450);
+    let html = markdown_to_html_default(&md).expect("parse");
+    assert_eq!(html.word_count, 450);
+    assert_eq!(html.reading_time_minutes, 3, "rounded up at 200 wpm");
+    let opt = ParseOptions::builder().words_per_minute(150).build();
+    assert_eq!(markdown_to_html(&md, &opt).unwrap().reading_time_minutes, 3);
+
+    assert_eq!(
+        markdown_to_html_default("").unwrap().reading_time_minutes,
+        0
+    );
+}
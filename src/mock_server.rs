@@ -0,0 +1,151 @@
+//! Minimal HTTP server for testing the github client.
+//! Each connection handles one request, and the handler decides the response.
+//!
+// not every test uses every accessor
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// Request received by the mock server
+#[derive(Clone, Debug)]
+pub(crate) struct MockRequest {
+    pub method: String,
+    /// path, including query string
+    pub path: String,
+    /// (name, value), with name in lower case
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    /// Returns value of header, if present. Name should be lower case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Response generated by the test's handler
+pub(crate) enum MockResponse {
+    /// Send response with status, extra headers, and body
+    Reply(u16, Vec<(String, String)>, String),
+    /// Close the connection without responding, simulating a network failure
+    Drop,
+}
+
+impl MockResponse {
+    /// Response with json body
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        MockResponse::Reply(
+            status,
+            vec![("Content-Type".into(), "application/json".into())],
+            body.to_string(),
+        )
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+pub(crate) struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    /// Start server on a random local port. The server runs until the test process exits.
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let log = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                let log = log.clone();
+                std::thread::spawn(move || serve(stream, handler.as_ref(), &log));
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    /// Base url of server, without trailing slash
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of requests received with the method
+    pub fn count(&self, method: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|r| r.method == method)
+            .count()
+    }
+}
+
+fn serve(stream: TcpStream, handler: &Handler, log: &Mutex<Vec<MockRequest>>) {
+    let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            headers.push((k.trim().to_lowercase(), v.trim().to_string()));
+        }
+    }
+    let len = headers
+        .iter()
+        .find(|(k, _)| k == "content-length")
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; len];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+    let req = MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    log.lock().unwrap().push(req.clone());
+
+    let mut stream = stream;
+    match handler(&req) {
+        MockResponse::Drop => {}
+        MockResponse::Reply(status, extra, body) => {
+            let mut resp = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+                status,
+                body.len()
+            );
+            for (k, v) in extra.iter() {
+                resp.push_str(&format!("{}: {}\r\n", k, v));
+            }
+            resp.push_str("\r\n");
+            resp.push_str(&body);
+            let _ = stream.write_all(resp.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Output sinks for generated site files, and `build_site`, which renders
+//! markdown documents to a sink.
+//!
+use crate::{render::Renderer, Error, Result, TomlMap};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Destination for generated files
+pub trait OutputSink {
+    /// Write file at path relative to the site root
+    fn write(&mut self, rel_path: &Path, bytes: &[u8]) -> Result<()>;
+}
+
+/// Writes files under a folder, creating sub-folders as needed
+#[derive(Clone, Debug)]
+pub struct FsSink {
+    /// Output folder
+    pub dir: PathBuf,
+}
+
+impl FsSink {
+    /// Create sink for output folder
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl OutputSink for FsSink {
+    fn write(&mut self, rel_path: &Path, bytes: &[u8]) -> Result<()> {
+        let path = self.dir.join(site_path(rel_path)?);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+/// Keeps files in memory, keyed by site path (e.g., "guide/intro.html"), for tests and
+/// for serving generated pages without writing them.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySink {
+    /// Files written, keyed by path with '/' separators
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl OutputSink for MemorySink {
+    fn write(&mut self, rel_path: &Path, bytes: &[u8]) -> Result<()> {
+        self.files.insert(site_path(rel_path)?, bytes.to_vec());
+        Ok(())
+    }
+}
+
+/// Convert relative path to a site path with '/' separators, e.g., "guide/intro.html".
+/// Returns Error::RefEscapesRoot if the path is absolute or contains "..".
+pub(crate) fn site_path(rel_path: &Path) -> Result<String> {
+    let mut segments = Vec::new();
+    for component in rel_path.components() {
+        match component {
+            Component::Normal(s) => segments.push(s.to_string_lossy()),
+            Component::CurDir => {}
+            _ => return Err(Error::RefEscapesRoot(rel_path.display().to_string())),
+        }
+    }
+    Ok(segments.join("/"))
+}
+
+/// Generate an html page for each markdown document, and write it to the sink.
+/// Pages are (path, document), where the path is relative to the site root, and the
+/// document may have frontmatter. Each page is rendered with `write_document_html`,
+/// using the template, and written to the path with the extension changed to ".html".
+pub fn build_site<P, D>(
+    renderer: &Renderer,
+    template_name: &str,
+    pages: &[(P, D)],
+    sink: &mut dyn OutputSink,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    D: AsRef<str>,
+{
+    for (path, document) in pages.iter() {
+        write_page(
+            renderer,
+            template_name,
+            path.as_ref(),
+            document.as_ref(),
+            sink,
+        )?;
+    }
+    Ok(())
+}
+
+/// Render one document with the template, and write it to the sink at the path
+/// with the extension changed to ".html". Returns the path written.
+pub(crate) fn write_page(
+    renderer: &Renderer,
+    template_name: &str,
+    rel_path: &Path,
+    document: &str,
+    sink: &mut dyn OutputSink,
+) -> Result<PathBuf> {
+    let out_path = rel_path.with_extension("html");
+    let mut buf = Vec::new();
+    renderer.write_document_html(TomlMap::new(), document, template_name, &mut buf)?;
+    sink.write(&out_path, &buf)?;
+    Ok(out_path)
+}
+
+#[test]
+fn test_build_site() {
+    use crate::render::RenderConfig;
+
+    let renderer = Renderer::init(&RenderConfig {
+        templates: vec![("page", "<title>{{title}}</title>{{content}}")],
+        ..Default::default()
+    })
+    .expect("renderer");
+    let pages = vec![
+        ("index.md", "+++\ntitle = \"Home\"\n+++\n# Welcome\n"),
+        ("guide/intro.md", "Hello"),
+    ];
+    let mut sink = MemorySink::default();
+    build_site(&renderer, "page", &pages, &mut sink).expect("build");
+    assert_eq!(
+        sink.files.keys().collect::<Vec<_>>(),
+        vec!["guide/intro.html", "index.html"]
+    );
+    assert_eq!(
+        sink.files["index.html"],
+        b"<title>Home</title><h1>Welcome</h1>\n".to_vec()
+    );
+    assert_eq!(
+        sink.files["guide/intro.html"],
+        b"<title></title><p>Hello</p>\n".to_vec()
+    );
+
+    // paths must stay inside the site
+    let mut sink = MemorySink::default();
+    assert!(matches!(
+        build_site(&renderer, "page", &[("../x.md", "x")], &mut sink),
+        Err(Error::RefEscapesRoot(_))
+    ));
+    assert!(sink.files.is_empty());
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let mut sink = FsSink::new(dir.path());
+    build_site(&renderer, "page", &pages, &mut sink).expect("build");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("guide/intro.html")).unwrap(),
+        "<title></title><p>Hello</p>\n"
+    );
+}
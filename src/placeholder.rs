@@ -0,0 +1,154 @@
+//! Placeholders for markdown extensions (components and directives) that are expanded
+//! to html. Extensions found in the markdown source are replaced with placeholder comments,
+//! the markdown is converted to html, and the placeholders are replaced with the
+//! expanded html.
+//!
+use crate::Result;
+use pulldown_cmark::{Event, Parser, Tag};
+use std::ops::Range;
+
+const PLACEHOLDER_PREFIX: &str = "<!-- ";
+const PLACEHOLDER_END: &str = " -->";
+
+/// Extension found in markdown source
+pub(crate) trait FoundTag {
+    /// byte range of the whole extension in the source
+    fn range(&self) -> Range<usize>;
+
+    /// True if the extension is a block, whose placeholder is followed by a newline
+    fn is_block(&self) -> bool {
+        false
+    }
+}
+
+/// Html expanded for placeholders, in placeholder order
+pub(crate) struct Placeholders {
+    /// placeholder name, e.g., "mdsite-component"
+    kind: &'static str,
+    expanded: Vec<String>,
+}
+
+impl Placeholders {
+    /// Create empty list for placeholders of the kind
+    pub(crate) fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            expanded: Vec::new(),
+        }
+    }
+
+    /// Returns true if no placeholders were added
+    pub(crate) fn is_empty(&self) -> bool {
+        self.expanded.is_empty()
+    }
+
+    /// Add html, and return the placeholder to insert into the markdown
+    fn add(&mut self, html: String) -> String {
+        let placeholder = self.placeholder(self.expanded.len());
+        self.expanded.push(html);
+        placeholder
+    }
+
+    fn placeholder(&self, ix: usize) -> String {
+        format!(
+            "{}{}:{}{}",
+            PLACEHOLDER_PREFIX, self.kind, ix, PLACEHOLDER_END
+        )
+    }
+
+    /// Replace placeholder comments in html with the expanded html
+    pub(crate) fn replace(&self, html: &str) -> String {
+        let mut out = html.to_string();
+        for (ix, expanded) in self.expanded.iter().enumerate() {
+            out = out.replacen(&self.placeholder(ix), expanded, 1);
+        }
+        out
+    }
+}
+
+/// Replace each tag found by `find` with a placeholder for the html returned by `expand`.
+/// `find` returns the next tag at or after a byte offset. If `expand` returns None,
+/// the tag is left in the markdown. Tags inside code spans and code blocks are skipped.
+/// Returns new markdown.
+pub(crate) fn extract<'md, T, F, X>(
+    markdown: &'md str,
+    find: F,
+    mut expand: X,
+    placeholders: &mut Placeholders,
+) -> Result<String>
+where
+    T: FoundTag,
+    F: Fn(&'md str, usize) -> Option<T>,
+    X: FnMut(&T) -> Result<Option<String>>,
+{
+    let code = code_ranges(markdown);
+    let mut source = String::with_capacity(markdown.len());
+    let mut pos = 0;
+    let mut search = 0;
+    while let Some(tag) = find(markdown, search) {
+        let range = tag.range();
+        // continue after the first char, which may be more than one byte
+        search = range.start
+            + markdown[range.start..]
+                .chars()
+                .next()
+                .map_or(1, char::len_utf8);
+        if code.iter().any(|r| r.contains(&range.start)) {
+            continue;
+        }
+        let html = match expand(&tag)? {
+            Some(html) => html,
+            None => continue,
+        };
+        source.push_str(&markdown[pos..range.start]);
+        source.push_str(&placeholders.add(html));
+        if tag.is_block() {
+            source.push('\n');
+        }
+        pos = range.end;
+        search = range.end;
+    }
+    source.push_str(&markdown[pos..]);
+    Ok(source)
+}
+
+/// byte ranges of code spans and code blocks
+fn code_ranges(markdown: &str) -> Vec<Range<usize>> {
+    Parser::new(markdown)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_placeholders() {
+    struct Word(Range<usize>);
+    impl FoundTag for Word {
+        fn range(&self) -> Range<usize> {
+            self.0.clone()
+        }
+    }
+    let find = |md: &str, from: usize| {
+        md[from..]
+            .find("@x")
+            .map(|ix| Word(from + ix..from + ix + 2))
+    };
+    let mut placeholders = Placeholders::new("test");
+    let md = "a @x b `@x` @x";
+    let mut count = 0;
+    let source = extract(
+        md,
+        find,
+        |_| {
+            count += 1;
+            Ok(if count == 1 { Some("X".into()) } else { None })
+        },
+        &mut placeholders,
+    )
+    .expect("extract");
+    assert_eq!(source, "a <!-- test:0 --> b `@x` @x", "code skipped");
+    assert_eq!(placeholders.replace(&source), "a X b `@x` @x");
+}
@@ -1,12 +1,26 @@
 //! HTML generation
 //!
-use crate::{Result, TomlMap};
-use chrono::DateTime;
-use handlebars::Handlebars;
+use crate::{
+    markdown::{split_markdown, Frontmatter},
+    md_parser::ParseOptions,
+    Error, Result, TomlMap,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use handlebars::{Handlebars, HelperDef};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
+use std::sync::Mutex;
 use toml::value::Value as TomlValue;
 
+/// Output format for write_page
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Markdown is converted to html
+    Html,
+    /// Markdown is converted to plain text, e.g., for email. Use with a text template.
+    Text,
+}
+
 /// Html to insert before and after diff chunks
 pub struct DiffStyle {
     /// Html to insert before a span of inserted content
@@ -39,22 +53,19 @@ impl Default for DiffStyle {
 ///
 pub type Template<'template> = (&'template str, &'template str);
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct RenderConfig<'render> {
     /// Templates to be loaded for renderer. List of template name, data
     pub templates: Vec<Template<'render>>,
+    /// Partials to be loaded for renderer, used from templates with `{{> name}}`.
+    /// List of partial name, data. Names must be distinct from template names.
+    pub partials: Vec<Template<'render>>,
     /// Whether parser is in strict mode (e.g. if true, a variable used in template
     /// that is undefined would raise an error; if false, it would evaluate to 'falsey'
     pub strict_mode: bool,
-}
-
-impl<'render> Default for RenderConfig<'render> {
-    fn default() -> Self {
-        Self {
-            templates: Vec::new(),
-            strict_mode: false,
-        }
-    }
+    /// Whether write_document_html adds the document's raw frontmatter text and format
+    /// to render vars, as `frontmatter_raw` and `frontmatter_format` (default false)
+    pub raw_frontmatter: bool,
 }
 
 /// HBTemplate processor for HTML generation
@@ -63,6 +74,91 @@ pub struct Renderer<'gen> {
     hb: Handlebars<'gen>,
     /// Additional dictionary that supplements data passed to render() method
     vars: TomlMap,
+    /// Options for converting markdown in write_page_html
+    parse_options: ParseOptions,
+    /// Whether to add raw frontmatter to render vars
+    raw_frontmatter: bool,
+    /// Cache of rendered output, if enabled with `with_cache`
+    cache: Option<Mutex<RenderCache>>,
+    /// Whether html pages are minified
+    minify: bool,
+    /// Names of registered partials, which share the template namespace
+    partials: Vec<String>,
+}
+
+/// Render cache statistics
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    /// Number of renders that returned cached output
+    pub hits: u64,
+    /// Number of renders that weren't in the cache
+    pub misses: u64,
+    /// Number of entries in the cache
+    pub len: usize,
+}
+
+/// Cache key: template name and sha1 of the serialized render data
+type CacheKey = (String, [u8; 20]);
+
+/// Least-recently-used cache of rendered output
+struct RenderCache {
+    capacity: usize,
+    /// output and the tick when it was last used
+    entries: HashMap<CacheKey, (String, u64)>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl RenderCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<String> {
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some((output, last_used)) => {
+                *last_used = self.tick;
+                self.stats.hits += 1;
+                Some(output.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, output: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (output, self.tick));
+    }
+}
+
+/// Cache key for rendering the template with data. Data is serialized to json,
+/// which is stable because map keys are sorted.
+fn cache_key(template_name: &str, data: &TomlMap) -> Result<CacheKey> {
+    use sha1::{Digest, Sha1};
+
+    let bytes = serde_json::to_vec(data).map_err(|e| Error::Bug(e.to_string()))?;
+    Ok((template_name.to_string(), Sha1::digest(&bytes).into()))
 }
 
 impl<'gen> Default for Renderer<'gen> {
@@ -85,14 +181,54 @@ impl<'gen> Renderer<'gen> {
         for t in &config.templates {
             hb.register_template_string(t.0, t.1)?;
         }
+        for p in &config.partials {
+            if config.templates.iter().any(|t| t.0 == p.0) {
+                return Err(Error::TemplateNameCollision(p.0.to_string()));
+            }
+            hb.register_partial(p.0, p.1)?;
+        }
 
         let renderer = Self {
             hb,
             vars: TomlMap::new(),
+            parse_options: ParseOptions::default(),
+            raw_frontmatter: config.raw_frontmatter,
+            cache: None,
+            minify: false,
+            partials: config.partials.iter().map(|p| p.0.to_string()).collect(),
         };
         Ok(renderer)
     }
 
+    /// Enable a cache of rendered output, keyed by template name and render data,
+    /// holding up to `capacity` entries. When the cache is full, the least recently
+    /// used entry is removed. The cache is cleared when templates or helpers change.
+    /// Cached output doesn't change with the current time, so if templates use
+    /// `relative-date` or `time-ago`, use `set_build_time`.
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(Mutex::new(RenderCache::new(capacity)));
+        self
+    }
+
+    /// Remove all entries from the render cache, if enabled
+    pub fn clear_cache(&mut self) {
+        if let Some(cache) = self.cache.as_mut() {
+            let cache = cache.get_mut().unwrap_or_else(|e| e.into_inner());
+            cache.entries.clear();
+        }
+    }
+
+    /// Render cache statistics, or None if the cache isn't enabled
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| {
+            let cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+            CacheStats {
+                len: cache.entries.len(),
+                ..cache.stats
+            }
+        })
+    }
+
     /// Replace renderer dict.
     /// Values in the renderer dict override any values passed to render()
     pub fn set_vars(&mut self, vars: TomlMap) {
@@ -117,42 +253,422 @@ impl<'gen> Renderer<'gen> {
         self.vars.remove(key);
     }
 
-    /// Adds template to internal dictionary
+    /// Set options used to convert markdown in write_page_html
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        self.parse_options = options
+    }
+
+    /// Minify html pages generated by write_page_html and write_document_html,
+    /// with `minify_html` (default false)
+    pub fn set_minify(&mut self, enable: bool) {
+        self.minify = enable
+    }
+
+    /// Set a fixed build time, used by helpers such as `relative-date` and `time-ago`
+    /// in place of the current time, so that output is reproducible.
+    /// By default, helpers use the current time.
+    pub fn set_build_time(&mut self, build_time: DateTime<Utc>) {
+        self.clear_cache();
+        for name in RELATIVE_DATE_HELPERS.iter() {
+            self.hb.register_helper(
+                name,
+                Box::new(RelativeDate {
+                    now: Some(build_time),
+                }),
+            );
+        }
+    }
+
+    /// Register a template helper, in addition to the built-in helpers.
+    /// If a helper with the same name exists, it is replaced.
+    /// Helpers should be registered before rendering templates that use them.
+    pub fn register_helper(
+        &mut self,
+        name: &str,
+        helper: Box<dyn HelperDef + Send + Sync + 'gen>,
+    ) -> Result<()> {
+        self.clear_cache();
+        self.hb.register_helper(name, helper);
+        Ok(())
+    }
+
+    /// Adds template to internal dictionary.
+    /// Returns Error::TemplateNameCollision if a partial has the same name.
     pub fn add_template(&mut self, template: Template) -> Result<()> {
+        self.check_partial_collision(template.0)?;
+        self.clear_cache();
         self.hb.register_template_string(template.0, template.1)?;
         Ok(())
     }
 
+    /// Adds template to internal dictionary, after checking that the template references
+    /// each of the required variables, such as "title" or "page.title".
+    /// Variable paths are compared as written in the template (with '/' equivalent to '.'),
+    /// and a reference to a nested value, such as "page.title", counts as a reference to "page".
+    /// Returns Error::MissingTemplateVars, and doesn't register the template,
+    /// if any are not referenced, or Error::TemplateNameCollision if a partial has the same name.
+    pub fn register_template_checked(
+        &mut self,
+        name: &str,
+        source: &str,
+        required_vars: &[&str],
+    ) -> Result<()> {
+        self.check_partial_collision(name)?;
+        let template = handlebars::Template::compile_with_name(source, name.to_string(), false)?;
+        let mut refs = Vec::new();
+        template_refs(&template, &mut refs);
+        let missing = required_vars
+            .iter()
+            .filter(|var| {
+                !refs
+                    .iter()
+                    .any(|r| r == *var || r.starts_with(&format!("{}.", var)))
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::MissingTemplateVars(
+                name.to_string(),
+                missing.join(", "),
+            ));
+        }
+        self.clear_cache();
+        self.hb.register_template(name, template);
+        Ok(())
+    }
+
+    /// Partials and templates share a namespace in handlebars, so a template would
+    /// replace the partial with the same name
+    fn check_partial_collision(&self, name: &str) -> Result<()> {
+        if self.partials.iter().any(|p| p == name) {
+            return Err(Error::TemplateNameCollision(name.to_string()));
+        }
+        Ok(())
+    }
+
     /// Render a template with data.
-    pub fn render<W>(&self, template_name: &str, mut data: TomlMap, writer: &mut W) -> Result<()>
+    pub fn render<W>(&self, template_name: &str, data: TomlMap, writer: &mut W) -> Result<()>
     where
         W: std::io::Write,
     {
         // add variables that extend/override passed data
-        data.extend(self.vars.clone().into_iter());
+        let data = merge_maps(&[&data, &self.vars]);
+        if let Some(cache) = &self.cache {
+            let key = cache_key(template_name, &data)?;
+            let cached = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&key);
+            let output = match cached {
+                Some(output) => output,
+                None => {
+                    let output = self.hb.render(template_name, &data)?;
+                    cache
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(key, output.clone());
+                    output
+                }
+            };
+            writer.write_all(output.as_bytes())?;
+            return Ok(());
+        }
         self.hb.render_to_write(template_name, &data, writer)?;
         Ok(())
     }
 
+    /// Render a template with data, returning the output as a String
+    pub fn render_to_string(&self, template_name: &str, data: TomlMap) -> Result<String> {
+        let mut buf = Vec::new();
+        self.render(template_name, data, &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    /// Convert markdown to html and generate html page, like `write_page_html`,
+    /// returning the page as a String
+    pub fn write_page_html_to_string(
+        &self,
+        map: TomlMap,
+        markdown: &str,
+        template_name: &str,
+    ) -> Result<String> {
+        let mut buf = Vec::new();
+        self.write_page_html(map, markdown, template_name, &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
     /// Convert markdown to html and generate html page,
-    /// using 'map' data as render vars
+    /// using 'map' data as render vars.
+    /// Generated html is in the var `content`, and tables of contents, if any,
+    /// are in `toc` and `tocs`. The vars `word_count` and `reading_time_minutes`
+    /// are set from the markdown text.
     pub fn write_page_html<W: std::io::Write>(
+        &self,
+        map: TomlMap,
+        markdown: &str,
+        template_name: &str,
+        writer: &mut W,
+    ) -> Result<()> {
+        self.write_page(map, markdown, template_name, OutputFormat::Html, writer)
+    }
+
+    /// Generate html page from a markdown document that may have frontmatter.
+    /// Frontmatter values are added to the render vars, with values in 'map' taking
+    /// precedence. If the renderer was configured with `raw_frontmatter`, the frontmatter
+    /// text is added as `frontmatter_raw`, and its format ("toml", "yaml", or "json")
+    /// as `frontmatter_format`.
+    pub fn write_document_html<W: std::io::Write>(
+        &self,
+        map: TomlMap,
+        document: &str,
+        template_name: &str,
+        writer: &mut W,
+    ) -> Result<()> {
+        let (front, body) = split_markdown(document);
+        let mut vars = merge_maps(&[&front.to_toml()?, &map]);
+        if self.raw_frontmatter {
+            let (raw, format) = match front {
+                Frontmatter::Toml(raw) => (raw, "toml"),
+                Frontmatter::Yaml(raw) => (raw, "yaml"),
+                Frontmatter::Json(raw) => (raw, "json"),
+                Frontmatter::Empty => ("", ""),
+            };
+            vars.insert("frontmatter_raw".into(), TomlValue::from(raw));
+            vars.insert("frontmatter_format".into(), TomlValue::from(format));
+        }
+        self.write_page_html(vars, body, template_name, writer)
+    }
+
+    /// Convert markdown to the output format and generate page,
+    /// using 'map' data as render vars.
+    /// For Html, this is the same as `write_page_html`. For Text, the var `content` is
+    /// the plain text from `markdown_to_text`, and tocs are not generated.
+    pub fn write_page<W: std::io::Write>(
         &self,
         mut map: TomlMap,
         markdown: &str,
         template_name: &str,
+        format: OutputFormat,
         mut writer: &mut W,
     ) -> Result<()> {
-        let html = crate::md_parser::markdown_to_html(markdown)?;
+        if format == OutputFormat::Text {
+            let text = crate::md_parser::markdown_to_text(markdown);
+            map.insert("content".into(), TomlValue::from(text));
+            return self.render(template_name, map, &mut writer);
+        }
+        let html = crate::md_parser::markdown_to_html(markdown, &self.parse_options)?;
         map.insert("content".into(), TomlValue::from(html.content));
+        map.insert("word_count".into(), TomlValue::from(html.word_count as i64));
+        map.insert(
+            "reading_time_minutes".into(),
+            TomlValue::from(html.reading_time_minutes as i64),
+        );
         if let Some(toc) = html.toc {
             map.insert("toc".into(), TomlValue::from(toc));
         }
+        if !html.tocs.is_empty() {
+            // named tocs, e.g., {{tocs.[toc-sidebar]}}
+            let tocs = html
+                .tocs
+                .into_iter()
+                .map(|(k, v)| (k, TomlValue::from(v)))
+                .collect::<TomlMap>();
+            map.insert("tocs".into(), TomlValue::Table(tocs));
+        }
+        if self.minify {
+            let page = self.render_to_string(template_name, map)?;
+            writer.write_all(minify_html(&page).as_bytes())?;
+            return Ok(());
+        }
         self.render(template_name, map, &mut writer)?;
         Ok(())
     }
 }
 
+/// Elements whose content is copied unchanged by minify_html
+const MINIFY_RAW_ELEMENTS: &[&str] = &["pre", "code", "textarea", "script", "style"];
+
+/// Elements where whitespace before or after the tag doesn't change the rendered page
+const MINIFY_BLOCK_ELEMENTS: &[&str] = &[
+    "!doctype",
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "br",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "link",
+    "main",
+    "meta",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "script",
+    "section",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Minify html by collapsing whitespace. Runs of whitespace become a single space,
+/// and whitespace between two block-level tags (e.g., `</p>\n<p>`) is removed.
+/// Whitespace next to inline elements (e.g., `<b>a</b> <i>b</i>`) is kept as one space,
+/// and the content of `<pre>`, `<code>`, `<textarea>`, `<script>`, and `<style>`
+/// elements, and comments, are unchanged.
+pub fn minify_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    // name of tag just written, if no text has been written after it
+    let mut last_tag: Option<String> = None;
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map(|ix| ix + 3).unwrap_or(rest.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            last_tag = Some("!--".to_string());
+        } else if c == '<' {
+            let end = tag_end(rest);
+            let name = tag_name(&rest[..end]);
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            if MINIFY_RAW_ELEMENTS.contains(&name.as_str()) && !rest.is_empty() {
+                // copy through the closing tag
+                let close = format!("</{}", name);
+                let raw_end = match rest.to_ascii_lowercase().find(&close) {
+                    Some(ix) => ix + tag_end(&rest[ix..]),
+                    None => rest.len(),
+                };
+                out.push_str(&rest[..raw_end]);
+                rest = &rest[raw_end..];
+            }
+            last_tag = Some(name);
+        } else if c.is_whitespace() {
+            let end = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            rest = &rest[end..];
+            let next_tag = rest
+                .starts_with('<')
+                .then(|| tag_name(&rest[..tag_end(rest)]));
+            let is_block = |tag: &Option<String>| match tag {
+                Some(name) => MINIFY_BLOCK_ELEMENTS.contains(&name.as_str()),
+                None => false,
+            };
+            if out.is_empty() || rest.is_empty() || (is_block(&last_tag) && is_block(&next_tag)) {
+                continue;
+            }
+            out.push(' ');
+        } else {
+            let end = rest
+                .find(|c: char| c == '<' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            last_tag = None;
+        }
+    }
+    out
+}
+
+/// Byte length of the tag at the start of html, through its closing '>',
+/// skipping '>' inside quoted attribute values
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (ix, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return ix + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// Lower-case name of the tag, without '<' or '/', e.g., "p" for "</p>"
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Merge maps, with values in later layers replacing values in earlier layers.
+/// Tables are merged recursively, so a later layer can override one key of a nested
+/// table without replacing the whole table. All other values, including arrays,
+/// are replaced.
+pub fn merge_maps(layers: &[&TomlMap]) -> TomlMap {
+    let mut merged = TomlMap::new();
+    for layer in layers.iter() {
+        merge_into(&mut merged, layer);
+    }
+    merged
+}
+
+/// Apply shared default values (e.g., layout, author, section) to a page's frontmatter.
+/// Values in `overrides` win over `base`, with the same semantics as `merge_maps`:
+/// nested tables are merged recursively, and other values, including arrays, are replaced.
+pub fn merge_defaults(base: &TomlMap, overrides: TomlMap) -> TomlMap {
+    merge_maps(&[base, &overrides])
+}
+
+/// Merge the layers used for rendering a page, in order of increasing precedence:
+///  - config defaults
+///  - site context (e.g., site title, base url)
+///  - page frontmatter
+///  - per-render overlay (e.g., generated content and toc)
+pub fn merge_page_vars(
+    defaults: &TomlMap,
+    site: &TomlMap,
+    frontmatter: &TomlMap,
+    overlay: &TomlMap,
+) -> TomlMap {
+    merge_maps(&[defaults, site, frontmatter, overlay])
+}
+
+/// Recursively merge `layer` into `base`
+fn merge_into(base: &mut TomlMap, layer: &TomlMap) {
+    for (key, val) in layer.iter() {
+        match (base.get_mut(key), val) {
+            (Some(TomlValue::Table(base_table)), TomlValue::Table(table)) => {
+                merge_into(base_table, table)
+            }
+            _ => {
+                base.insert(key.clone(), val.clone());
+            }
+        }
+    }
+}
+
 /// Convert Value to string without adding quotes around strings
 fn json_value_to_string(v: &JsonValue) -> String {
     match v {
@@ -161,9 +677,53 @@ fn json_value_to_string(v: &JsonValue) -> String {
     }
 }
 
+/// Parse date in one of the formats: RFC3339 (`2024-01-15T10:30:00-08:00`),
+/// `2024-01-15`, or `2024-01-15 10:30:00`. Dates without a time are at midnight,
+/// and dates without a time zone are UTC.
+pub fn parse_date(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Some(date);
+    }
+    let utc = FixedOffset::east_opt(0)?;
+    if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return utc.from_local_datetime(&dt).single();
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return utc
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .single();
+    }
+    None
+}
+
+/// Escape characters in serialized json that are special in html: '<', '>', '&', and "'".
+/// They can only occur inside json strings, where the unicode escapes are equivalent.
+fn escape_json_html(json: &str) -> String {
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            '\'' => escaped.push_str("\\u0027"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 /// Add template helpers functions
 ///  'join-csv' turns array of values into comma-separate list
 ///  'format-date' rewrites an ISO8601-formatted date into another format
+///  'relative-date' (or 'time-ago') describes a date relative to now, e.g., "3 days ago"
+///  'markdownify' converts a markdown string to html
+///  'upper', 'lower', 'title-case', and 'slugify' change the case of a string
+///  ('title-case' isn't named 'title', which would hide the common `{{title}}` variable)
+///  'truncate' shortens a string to a number of characters, at a word boundary
+///  'default' writes a fallback value if a value is missing or empty
+///  'format-number' writes a number with thousands separators and optional decimals
+///  'json' writes a value as json
 fn add_base_helpers(hb: &mut Handlebars) {
     use handlebars::{Context, Helper, HelperResult, Output, RenderContext, RenderError};
 
@@ -204,7 +764,7 @@ fn add_base_helpers(hb: &mut Handlebars) {
              _rc: &mut RenderContext,
              out: &mut dyn Output|
              -> HelperResult {
-                // get first arg as string, an ISO8601-formatted date
+                // get first arg as string, a date in one of the formats accepted by parse_date
                 let date = h
                     .param(0)
                     .ok_or_else(|| RenderError::new("expect first param as date"))?
@@ -212,8 +772,12 @@ fn add_base_helpers(hb: &mut Handlebars) {
                     .as_str()
                     .ok_or_else(|| RenderError::new("expect strings"))?;
                 // parse into DateTime
-                let date = DateTime::parse_from_rfc3339(date)
-                    .map_err(|e| RenderError::from_error("date parse", e))?;
+                let date = parse_date(date).ok_or_else(|| {
+                    RenderError::new(format!(
+                        "date parse: '{}' is not RFC3339, YYYY-MM-DD, or YYYY-MM-DD HH:MM:SS",
+                        date
+                    ))
+                })?;
                 // get second arg - the format string
                 let format = h
                     .param(1)
@@ -228,6 +792,481 @@ fn add_base_helpers(hb: &mut Handlebars) {
             },
         ),
     );
+    for name in RELATIVE_DATE_HELPERS.iter() {
+        hb.register_helper(name, Box::new(RelativeDate { now: None }));
+    }
+    //
+    // markdownify: convert markdown string (e.g., a frontmatter summary) to html.
+    // If the result is a single paragraph, the <p> tags are removed, so it can be used inline.
+    hb.register_helper(
+        "markdownify",
+        Box::new(
+            |h: &Helper,
+             _r: &Handlebars,
+             _: &Context,
+             _rc: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                let markdown = h
+                    .param(0)
+                    .ok_or_else(|| RenderError::new("expect first param as markdown"))?
+                    .value()
+                    .as_str()
+                    .ok_or_else(|| RenderError::new("markdownify: expect string"))?;
+                let html = crate::md_parser::markdown_to_html(markdown, &ParseOptions::default())
+                    .map_err(|e| RenderError::new(format!("markdownify: {}", e)))?
+                    .content;
+                out.write(strip_paragraph(&html))?;
+                Ok(())
+            },
+        ),
+    );
+    //
+    // truncate: shorten string to at most N characters, cutting at a word boundary,
+    // and append suffix (default "…") if it was shortened. e.g., {{truncate summary 80}}
+    // or {{truncate summary 80 "..."}}
+    hb.register_helper(
+        "truncate",
+        Box::new(
+            |h: &Helper,
+             _r: &Handlebars,
+             _: &Context,
+             _rc: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                let s = h
+                    .param(0)
+                    .ok_or_else(|| RenderError::new("truncate: expect first param as string"))?
+                    .value()
+                    .as_str()
+                    .ok_or_else(|| RenderError::new("truncate: expect string"))?;
+                let max_chars = h
+                    .param(1)
+                    .ok_or_else(|| RenderError::new("truncate: expect second param as length"))?
+                    .value()
+                    .as_u64()
+                    .ok_or_else(|| RenderError::new("truncate: expect length as number"))?;
+                let suffix = match h.param(2) {
+                    Some(p) => p
+                        .value()
+                        .as_str()
+                        .ok_or_else(|| RenderError::new("truncate: expect suffix as string"))?,
+                    None => TRUNCATE_SUFFIX,
+                };
+                out.write(&truncate_words(s, max_chars as usize, suffix))?;
+                Ok(())
+            },
+        ),
+    );
+    //
+    // default: writes first param unless it's missing, null, or an empty string,
+    // otherwise the second param. e.g., {{default author "Anonymous"}}
+    hb.register_helper(
+        "default",
+        Box::new(
+            |h: &Helper,
+             _r: &Handlebars,
+             _: &Context,
+             _rc: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                let fallback = h
+                    .param(1)
+                    .ok_or_else(|| RenderError::new("default: expect second param as fallback"))?
+                    .value();
+                let value = match h.param(0).map(|p| p.value()) {
+                    None | Some(JsonValue::Null) => fallback,
+                    Some(JsonValue::String(s)) if s.is_empty() => fallback,
+                    Some(value) => value,
+                };
+                out.write(&json_value_to_string(value))?;
+                Ok(())
+            },
+        ),
+    );
+    //
+    // format-number: writes number with thousands separators, and an optional number
+    // of decimals, e.g., {{format-number price 2}} -> "1,234.50". The hash params
+    // `sep` and `point` change the separators, e.g., sep="." point="," -> "1.234,50"
+    hb.register_helper(
+        "format-number",
+        Box::new(
+            |h: &Helper,
+             _r: &Handlebars,
+             _: &Context,
+             _rc: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                let value = h
+                    .param(0)
+                    .ok_or_else(|| RenderError::new("format-number: expect first param as number"))?
+                    .value();
+                let decimals = match h.param(1) {
+                    Some(p) => Some(p.value().as_u64().ok_or_else(|| {
+                        RenderError::new("format-number: expect decimals as number")
+                    })? as usize),
+                    None => None,
+                };
+                let hash_str = |key: &str, default: &'static str| match h.hash_get(key) {
+                    Some(p) => p.value().as_str().map(String::from).ok_or_else(|| {
+                        RenderError::new(format!("format-number: expect {} as string", key))
+                    }),
+                    None => Ok(default.to_string()),
+                };
+                let sep = hash_str("sep", ",")?;
+                let point = hash_str("point", ".")?;
+                let formatted = format_number(value, decimals, &sep, &point).ok_or_else(|| {
+                    RenderError::new(format!(
+                        "format-number: expect number, found '{}'",
+                        json_value_to_string(value)
+                    ))
+                })?;
+                out.write(&formatted)?;
+                Ok(())
+            },
+        ),
+    );
+    //
+    // json: writes value as json, compact, or pretty if the second param is true,
+    // e.g., <script type="application/ld+json">{{json page}}</script>.
+    // '<', '>', '&', and "'" in strings are written as unicode escapes (e.g., "\u003c"),
+    // so a value can't close the script element, and the json can be used in a
+    // single-quoted html attribute, e.g., data-page='{{json page}}'.
+    // Double quotes aren't escaped, so don't use it in a double-quoted attribute.
+    hb.register_helper(
+        "json",
+        Box::new(
+            |h: &Helper,
+             _r: &Handlebars,
+             _: &Context,
+             _rc: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                let value = h
+                    .param(0)
+                    .ok_or_else(|| RenderError::new("json: expect first param as value"))?
+                    .value();
+                let pretty = match h.param(1) {
+                    Some(p) => p
+                        .value()
+                        .as_bool()
+                        .ok_or_else(|| RenderError::new("json: expect pretty as boolean"))?,
+                    None => false,
+                };
+                let json = if pretty {
+                    serde_json::to_string_pretty(value)
+                } else {
+                    serde_json::to_string(value)
+                }
+                .map_err(|e| RenderError::new(format!("json: {}", e)))?;
+                out.write(&escape_json_html(&json))?;
+                Ok(())
+            },
+        ),
+    );
+    //
+    // slugify: writes string as a slug, with the rules used for heading anchors.
+    // The hash params `unicode` (keep non-ascii letters, as with ParseOptions.unicode_slugs)
+    // and `max_len` (maximum length in characters) set the SlugOptions,
+    // e.g., {{slugify title unicode=true max_len=40}}
+    hb.register_helper(
+        "slugify",
+        Box::new(
+            |h: &Helper,
+             _r: &Handlebars,
+             _: &Context,
+             _rc: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                let s = h
+                    .param(0)
+                    .ok_or_else(|| RenderError::new("slugify: expect one param"))?
+                    .value()
+                    .as_str()
+                    .ok_or_else(|| RenderError::new("slugify: expect string"))?;
+                let mut opts = crate::md_parser::SlugOptions::default();
+                if let Some(unicode) = h.hash_get("unicode") {
+                    opts.unicode = unicode
+                        .value()
+                        .as_bool()
+                        .ok_or_else(|| RenderError::new("slugify: expect unicode as boolean"))?;
+                }
+                if let Some(max_len) = h.hash_get("max_len") {
+                    let max_len = max_len
+                        .value()
+                        .as_u64()
+                        .ok_or_else(|| RenderError::new("slugify: expect max_len as number"))?;
+                    opts.max_len = Some(max_len as usize);
+                }
+                out.write(&crate::md_parser::slugify(s, &opts))?;
+                Ok(())
+            },
+        ),
+    );
+    let transforms: [(&'static str, Transform); 3] = [
+        ("upper", str::to_uppercase),
+        ("lower", str::to_lowercase),
+        ("title-case", title_case),
+    ];
+    for (name, transform) in transforms.iter() {
+        hb.register_helper(
+            name,
+            Box::new(StringTransform {
+                name,
+                transform: *transform,
+            }),
+        );
+    }
+}
+
+/// String transform function for StringTransform helper
+type Transform = fn(&str) -> String;
+
+/// Helper that writes its string parameter after applying a transform, e.g., `{{upper name}}`
+struct StringTransform {
+    name: &'static str,
+    transform: Transform,
+}
+
+impl HelperDef for StringTransform {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &handlebars::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc handlebars::Context,
+        _: &mut handlebars::RenderContext<'reg, 'rc>,
+        out: &mut dyn handlebars::Output,
+    ) -> handlebars::HelperResult {
+        use handlebars::RenderError;
+
+        let s = h
+            .param(0)
+            .ok_or_else(|| RenderError::new(format!("{}: expect one param", self.name)))?
+            .value()
+            .as_str()
+            .ok_or_else(|| RenderError::new(format!("{}: expect string", self.name)))?;
+        out.write(&(self.transform)(s))?;
+        Ok(())
+    }
+}
+
+/// Default suffix appended by the truncate helper
+const TRUNCATE_SUFFIX: &str = "…";
+
+/// Shorten string to at most max_chars characters (not counting the suffix), cutting
+/// at the last word boundary, and append suffix. If the first word is longer than
+/// max_chars, it is cut. Strings that are short enough are returned unchanged.
+fn truncate_words(s: &str, max_chars: usize, suffix: &str) -> String {
+    // byte index of the first character past the limit
+    let end = match s.char_indices().nth(max_chars) {
+        Some((ix, _)) => ix,
+        None => return s.to_string(),
+    };
+    let next_is_space = s[end..].starts_with(char::is_whitespace);
+    let cut = if next_is_space {
+        end
+    } else {
+        match s[..end].rfind(char::is_whitespace) {
+            Some(ix) => ix,
+            None => end,
+        }
+    };
+    let mut truncated = s[..cut].trim_end().to_string();
+    truncated.push_str(suffix);
+    truncated
+}
+
+/// Format number with sep between groups of three digits, and point before the decimals.
+/// If decimals is None, integers have no decimals, and floats have as many as needed.
+/// Returns None if the value isn't a number.
+fn format_number(
+    value: &JsonValue,
+    decimals: Option<usize>,
+    sep: &str,
+    point: &str,
+) -> Option<String> {
+    let digits = match (value, decimals) {
+        (JsonValue::Number(n), None) if n.is_f64() => n.as_f64()?.to_string(),
+        (JsonValue::Number(n), None) => n.to_string(),
+        (JsonValue::Number(n), Some(decimals)) => format!("{:.*}", decimals, n.as_f64()?),
+        _ => return None,
+    };
+    let (sign, digits) = match digits.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", digits.as_str()),
+    };
+    let (int, frac) = match digits.find('.') {
+        Some(ix) => (&digits[..ix], Some(&digits[ix + 1..])),
+        None => (digits, None),
+    };
+    let mut formatted = String::from(sign);
+    for (ix, c) in int.chars().enumerate() {
+        if ix > 0 && (int.len() - ix) % 3 == 0 {
+            formatted.push_str(sep);
+        }
+        formatted.push(c);
+    }
+    if let Some(frac) = frac {
+        formatted.push_str(point);
+        formatted.push_str(frac);
+    }
+    Some(formatted)
+}
+
+/// Capitalize the first letter of each word. Other letters are unchanged.
+fn title_case(s: &str) -> String {
+    let mut title = String::with_capacity(s.len());
+    let mut word_start = true;
+    for c in s.chars() {
+        if word_start {
+            title.extend(c.to_uppercase());
+        } else {
+            title.push(c);
+        }
+        word_start = c.is_whitespace();
+    }
+    title
+}
+
+/// If html is a single paragraph, returns its contents without the `<p>` tags
+fn strip_paragraph(html: &str) -> &str {
+    match html
+        .strip_prefix("<p>")
+        .and_then(|s| s.trim_end().strip_suffix("</p>"))
+    {
+        Some(inner) if !inner.contains("<p>") => inner,
+        _ => html,
+    }
+}
+
+/// Names of the helper that describes a date relative to now
+const RELATIVE_DATE_HELPERS: [&str; 2] = ["relative-date", "time-ago"];
+
+/// Helper that describes a date relative to now, e.g., "3 days ago".
+/// Uses the fixed time `now`, if set, otherwise the current time.
+struct RelativeDate {
+    now: Option<DateTime<Utc>>,
+}
+
+impl HelperDef for RelativeDate {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &handlebars::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc handlebars::Context,
+        _: &mut handlebars::RenderContext<'reg, 'rc>,
+        out: &mut dyn handlebars::Output,
+    ) -> handlebars::HelperResult {
+        use handlebars::RenderError;
+
+        let date = h
+            .param(0)
+            .ok_or_else(|| RenderError::new("expect first param as date"))?
+            .value()
+            .as_str()
+            .ok_or_else(|| RenderError::new("expect strings"))?;
+        let parsed = parse_date(date)
+            .ok_or_else(|| RenderError::new(format!("date parse: '{}' is not a date", date)))?;
+        let now = self.now.unwrap_or_else(Utc::now);
+        out.write(&relative_date(parsed.with_timezone(&Utc), now))?;
+        Ok(())
+    }
+}
+
+/// Describe date relative to now: "just now", "5 minutes ago", "in 2 days", etc.
+fn relative_date(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = now.signed_duration_since(date).num_seconds();
+    let abs = secs.unsigned_abs();
+    let (n, unit) = match abs {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (abs / 60, "minute"),
+        3600..=86_399 => (abs / 3600, "hour"),
+        86_400..=2_591_999 => (abs / 86_400, "day"),
+        2_592_000..=31_535_999 => (abs / 2_592_000, "month"),
+        _ => (abs / 31_536_000, "year"),
+    };
+    let plural = if n == 1 { "" } else { "s" };
+    if secs >= 0 {
+        format!("{} {}{} ago", n, unit, plural)
+    } else {
+        format!("in {} {}{}", n, unit, plural)
+    }
+}
+
+/// Collect variable paths referenced in template, normalized to '.' separators
+fn template_refs(template: &handlebars::Template, refs: &mut Vec<String>) {
+    use handlebars::template::TemplateElement;
+
+    for element in template.elements.iter() {
+        match element {
+            TemplateElement::HTMLExpression(param) => param_refs(param, refs),
+            TemplateElement::Expression(helper) | TemplateElement::HelperBlock(helper) => {
+                helper_refs(helper, refs)
+            }
+            TemplateElement::DecoratorExpression(decorator)
+            | TemplateElement::DecoratorBlock(decorator)
+            | TemplateElement::PartialExpression(decorator)
+            | TemplateElement::PartialBlock(decorator) => {
+                for param in decorator.params.iter().chain(decorator.hash.values()) {
+                    param_refs(param, refs);
+                }
+                if let Some(t) = &decorator.template {
+                    template_refs(t, refs);
+                }
+            }
+            TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+        }
+    }
+}
+
+fn helper_refs(helper: &handlebars::template::HelperTemplate, refs: &mut Vec<String>) {
+    for param in std::iter::once(&helper.name)
+        .chain(helper.params.iter())
+        .chain(helper.hash.values())
+    {
+        param_refs(param, refs);
+    }
+    for t in helper.template.iter().chain(helper.inverse.iter()) {
+        template_refs(t, refs);
+    }
+}
+
+fn param_refs(param: &handlebars::template::Parameter, refs: &mut Vec<String>) {
+    use handlebars::template::{Parameter, TemplateElement};
+
+    let raw = match param {
+        Parameter::Name(name) => name,
+        Parameter::Path(handlebars::Path::Relative((_, raw))) => raw,
+        Parameter::Path(handlebars::Path::Local(_)) | Parameter::Literal(_) => return,
+        Parameter::Subexpression(sub) => {
+            match sub.element.as_ref() {
+                TemplateElement::Expression(helper) | TemplateElement::HelperBlock(helper) => {
+                    helper_refs(helper, refs)
+                }
+                TemplateElement::HTMLExpression(param) => param_refs(param, refs),
+                _ => {}
+            }
+            return;
+        }
+    };
+    let path = raw.replace('/', ".");
+    let path = path
+        .trim_start_matches("this.")
+        .trim_start_matches("./")
+        .to_string();
+    refs.push(path);
+}
+
+/// Unit of comparison for generate_diff_ext
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DiffGranularity {
+    /// Compare characters (default)
+    #[default]
+    Char,
+    /// Compare words. A changed word is marked as a whole.
+    Word,
+    /// Compare lines. A changed line is marked as a whole.
+    Line,
 }
 
 /// Generate diff between two text segments.
@@ -236,24 +1275,33 @@ fn add_base_helpers(hb: &mut Handlebars) {
 /// add_style, e.g., "bg-green 100 text-gray-500"
 ///
 pub fn generate_diff(first: &str, second: &str, style: &DiffStyle) -> Result<String> {
-    use dissimilar::Chunk;
+    generate_diff_ext(first, second, style, DiffGranularity::Char)
+}
 
-    let chunks = dissimilar::diff(&first, &second);
+/// Generate diff between two text segments, comparing characters, words, or lines.
+/// Changes are enclosed with the tags in style, as in generate_diff.
+pub fn generate_diff_ext(
+    first: &str,
+    second: &str,
+    style: &DiffStyle,
+    granularity: DiffGranularity,
+) -> Result<String> {
+    let chunks = diff_chunks_ext(first, second, granularity);
 
     // "<span class=\"bg-red-100 text-gray-600 line-through\">");
     // <span class=\"bg-green-100 text-gray-600\">");
     let mut diff_content = String::with_capacity(second.len() + 1048 + 30 * chunks.len());
     for chunk in chunks.iter() {
         match chunk {
-            Chunk::Equal(s) => {
+            DiffChunk::Equal(s) => {
                 diff_content.push_str(s);
             }
-            Chunk::Delete(s) => {
+            DiffChunk::Delete(s) => {
                 diff_content.push_str(&style.del_start);
                 diff_content.push_str(s);
                 diff_content.push_str(&style.del_end);
             }
-            Chunk::Insert(s) => {
+            DiffChunk::Insert(s) => {
                 diff_content.push_str(&style.ins_start);
                 diff_content.push_str(s);
                 diff_content.push_str(&style.ins_end);
@@ -263,43 +1311,832 @@ pub fn generate_diff(first: &str, second: &str, style: &DiffStyle) -> Result<Str
     Ok(diff_content)
 }
 
+/// Span of diff between two texts
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffChunk {
+    /// Text in both
+    Equal(String),
+    /// Text only in the second
+    Insert(String),
+    /// Text only in the first
+    Delete(String),
+}
+
+/// Diff two texts by character, for rendering diffs in formats other than html
+pub fn diff_chunks(first: &str, second: &str) -> Vec<DiffChunk> {
+    diff_chunks_ext(first, second, DiffGranularity::Char)
+}
+
+/// Diff two texts, comparing characters, words, or lines
+pub fn diff_chunks_ext(first: &str, second: &str, granularity: DiffGranularity) -> Vec<DiffChunk> {
+    match granularity {
+        DiffGranularity::Char => diff_chars(first, second),
+        DiffGranularity::Word => diff_tokens(&split_words(first), &split_words(second)),
+        DiffGranularity::Line => diff_tokens(
+            &first.split_inclusive('\n').collect::<Vec<_>>(),
+            &second.split_inclusive('\n').collect::<Vec<_>>(),
+        ),
+    }
+}
+
+/// Diff characters
+fn diff_chars(first: &str, second: &str) -> Vec<DiffChunk> {
+    decode_chunks(dissimilar::diff(first, second), |s| s.to_string())
+}
+
+/// Convert dissimilar chunks to DiffChunks, transforming the text with decode
+fn decode_chunks<F: Fn(&str) -> String>(
+    chunks: Vec<dissimilar::Chunk>,
+    decode: F,
+) -> Vec<DiffChunk> {
+    use dissimilar::Chunk;
+
+    chunks
+        .into_iter()
+        .map(|chunk| match chunk {
+            Chunk::Equal(s) => DiffChunk::Equal(decode(s)),
+            Chunk::Delete(s) => DiffChunk::Delete(decode(s)),
+            Chunk::Insert(s) => DiffChunk::Insert(decode(s)),
+        })
+        .collect()
+}
+
+/// Split text into words and the whitespace between them
+fn split_words(s: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev_space = None;
+    for (ix, c) in s.char_indices() {
+        let space = c.is_whitespace();
+        if prev_space.is_some() && prev_space != Some(space) {
+            tokens.push(&s[start..ix]);
+            start = ix;
+        }
+        prev_space = Some(space);
+    }
+    if start < s.len() {
+        tokens.push(&s[start..]);
+    }
+    tokens
+}
+
+/// Diff sequences of tokens. Each distinct token is mapped to a private-use character,
+/// so that dissimilar can diff the token sequences as strings.
+fn diff_tokens<'t>(first: &[&'t str], second: &[&'t str]) -> Vec<DiffChunk> {
+    let mut code_points = (0xE000..=0xF8FF)
+        .chain(0xF0000..=0xFFFFD)
+        .chain(0x100000..=0x10FFFD)
+        .filter_map(std::char::from_u32);
+    let mut chars: HashMap<&'t str, char> = HashMap::new();
+    let mut tokens: HashMap<char, &'t str> = HashMap::new();
+    let mut encode = |seq: &[&'t str]| -> Option<String> {
+        let mut encoded = String::with_capacity(seq.len() * 3);
+        for token in seq.iter() {
+            let c = match chars.get(token) {
+                Some(c) => *c,
+                None => {
+                    let c = code_points.next()?;
+                    chars.insert(*token, c);
+                    tokens.insert(c, token);
+                    c
+                }
+            };
+            encoded.push(c);
+        }
+        Some(encoded)
+    };
+    match (encode(first), encode(second)) {
+        (Some(a), Some(b)) => decode_chunks(dissimilar::diff(&a, &b), |s| {
+            s.chars().filter_map(|c| tokens.get(&c).copied()).collect()
+        }),
+        // more distinct tokens than characters: compare characters
+        _ => diff_chars(&first.concat(), &second.concat()),
+    }
+}
+
+/// Render template with data containing one value
+#[cfg(test)]
+fn render_with(renderer: &Renderer, template: &str, key: &str, value: TomlValue) -> Result<String> {
+    let mut data = TomlMap::new();
+    data.insert(key.into(), value);
+    renderer.render_to_string(template, data)
+}
+
 #[test]
 fn initializers() {
     let mut r1 = Renderer::default();
     r1.set("x".into(), toml::Value::from("xyz"));
-    assert!(true);
 
     let mut r2 = Renderer::init(&RenderConfig::default()).expect("ok");
     r2.set("x".into(), toml::Value::from("xyz"));
-    assert!(true);
 }
 
 /// Test template processor
 #[test]
 fn test_html_page() {
     use crate::render::Renderer;
+    use crate::LineEnding;
     const TEST_TEMPLATE: &str = "<html><body><h1>{{title}}</h1>{{content}}</body></html>";
 
     let mut map = TomlMap::new();
     map.insert("title".into(), "Abc".into());
 
-    // simulate processing
-    let expected = TEST_TEMPLATE
-        .replace("{{content}}", "<p>hello</p>")
-        .replace("{{title}}", "Abc");
+    let mut gen = Renderer::default();
+    gen.add_template(("test_template", TEST_TEMPLATE))
+        .expect("add test template");
+
+    // simulate processing. The paragraph generated from markdown ends with a newline,
+    // which uses the configured line ending.
+    for (line_ending, eol) in [(LineEnding::Lf, "\n"), (LineEnding::CrLf, "\r\n")].iter() {
+        gen.set_parse_options(ParseOptions::builder().line_ending(*line_ending).build());
+        let expected = TEST_TEMPLATE
+            .replace("{{content}}", &format!("<p>hello</p>{}", eol))
+            .replace("{{title}}", "Abc");
+
+        let mut buf: Vec<u8> = Vec::new();
+        gen.write_page_html(map.clone(), "hello", "test_template", &mut buf)
+            .expect("write page");
+        assert_eq!(expected, String::from_utf8_lossy(&buf));
+    }
+}
+
+#[test]
+fn test_output_format() {
+    let mut gen = Renderer::default();
+    gen.add_template(("page", "<h1>{{title}}</h1>{{content}}"))
+        .expect("html template");
+    gen.add_template(("email", "{{title}}\n\n{{content}}"))
+        .expect("text template");
 
     let mut map = TomlMap::new();
-    map.insert("title".into(), "Abc".into());
+    map.insert("title".into(), "Notice".into());
+    let markdown = "Please *read* the [docs](https://example.com).";
+
+    let mut buf: Vec<u8> = Vec::new();
+    gen.write_page(map.clone(), markdown, "page", OutputFormat::Html, &mut buf)
+        .expect("html");
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        "<h1>Notice</h1><p>Please <em>read</em> the <a href=\"https://example.com\">docs</a>.</p>\n"
+    );
+
+    let mut buf: Vec<u8> = Vec::new();
+    gen.write_page(map, markdown, "email", OutputFormat::Text, &mut buf)
+        .expect("text");
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        "Notice\n\nPlease read the docs (https://example.com).\n"
+    );
+}
+
+#[test]
+fn test_merge_maps() {
+    let defaults: TomlMap = toml::from_str(
+        r#"
+        layout = "page"
+        author = "staff"
+        tags = ["a", "b"]
+        [site]
+        title = "Docs"
+        theme = "light"
+        "#,
+    )
+    .unwrap();
+    let site: TomlMap = toml::from_str(
+        r#"
+        author = "team"
+        [site]
+        theme = "dark"
+        "#,
+    )
+    .unwrap();
+    let page: TomlMap = toml::from_str(
+        r#"
+        author = "alice"
+        tags = ["c"]
+        [site.nav]
+        show = false
+        "#,
+    )
+    .unwrap();
+
+    let merged = merge_page_vars(&defaults, &site, &page, &TomlMap::new());
+    let expected: TomlMap = toml::from_str(
+        r#"
+        layout = "page"
+        author = "alice"
+        tags = ["c"]
+        [site]
+        title = "Docs"
+        theme = "dark"
+        [site.nav]
+        show = false
+        "#,
+    )
+    .unwrap();
+    assert_eq!(merged, expected);
+    assert_eq!(merge_maps(&[&defaults, &site, &page]), expected);
+}
+
+#[test]
+fn test_merge_defaults() {
+    let defaults: TomlMap = toml::from_str(
+        r#"
+        layout = "page"
+        author = "staff"
+        tags = ["docs", "guide"]
+        [section]
+        name = "Guides"
+        weight = 10
+        "#,
+    )
+    .unwrap();
+    let page: TomlMap = toml::from_str(
+        r#"
+        title = "Intro"
+        author = "alice"
+        tags = ["intro"]
+        [section]
+        weight = 1
+        "#,
+    )
+    .unwrap();
+
+    let merged = merge_defaults(&defaults, page);
+    // scalars in the page override defaults, and defaults fill in missing keys
+    assert_eq!(merged["title"].as_str(), Some("Intro"));
+    assert_eq!(merged["author"].as_str(), Some("alice"));
+    assert_eq!(merged["layout"].as_str(), Some("page"));
+    // nested tables are merged
+    assert_eq!(merged["section"]["name"].as_str(), Some("Guides"));
+    assert_eq!(merged["section"]["weight"].as_integer(), Some(1));
+    // arrays are replaced, not appended
+    assert_eq!(merged["tags"], TomlValue::Array(vec!["intro".into()]));
+
+    // a scalar replaces a default table
+    let mut page = TomlMap::new();
+    page.insert("section".into(), "none".into());
+    assert_eq!(
+        merge_defaults(&defaults, page)["section"].as_str(),
+        Some("none")
+    );
+}
+
+#[test]
+fn test_register_helper() {
+    use handlebars::{Context, Helper, HelperResult, Output, RenderContext};
 
     let mut gen = Renderer::default();
-    gen.add_template(("test_template", TEST_TEMPLATE))
-        .expect("add test template");
+    gen.register_helper(
+        "shout",
+        Box::new(
+            |h: &Helper,
+             _r: &Handlebars,
+             _: &Context,
+             _rc: &mut RenderContext,
+             out: &mut dyn Output|
+             -> HelperResult {
+                let s = h.param(0).and_then(|p| p.value().as_str()).unwrap_or("");
+                out.write(&format!("{}!", s.to_uppercase()))?;
+                Ok(())
+            },
+        ),
+    )
+    .expect("register");
+    gen.add_template(("t", "{{shout name}}")).expect("template");
 
+    let mut map = TomlMap::new();
+    map.insert("name".into(), "hey".into());
     let mut buf: Vec<u8> = Vec::new();
-    let result = gen.write_page_html(map, "hello", "test_template", &mut buf);
-    assert!(result.is_ok());
+    gen.render("t", map, &mut buf).expect("render");
+    assert_eq!(String::from_utf8_lossy(&buf), "HEY!");
+}
+
+#[test]
+fn test_render_to_string() {
+    let mut gen = Renderer::default();
+    gen.add_template(("t", "<h1>{{title}}</h1>{{content}}"))
+        .expect("template");
+    let mut map = TomlMap::new();
+    map.insert("title".into(), "Abc".into());
+
+    let mut buf: Vec<u8> = Vec::new();
+    gen.render("t", map.clone(), &mut buf).expect("render");
+    let s = gen
+        .render_to_string("t", map.clone())
+        .expect("render_to_string");
+    assert_eq!(s.as_bytes(), buf.as_slice());
+    assert_eq!(s, "<h1>Abc</h1>");
+
+    let mut buf: Vec<u8> = Vec::new();
+    gen.write_page_html(map.clone(), "hello", "t", &mut buf)
+        .expect("write_page_html");
+    let s = gen
+        .write_page_html_to_string(map, "hello", "t")
+        .expect("write_page_html_to_string");
+    assert_eq!(s.as_bytes(), buf.as_slice());
+    assert_eq!(s, "<h1>Abc</h1><p>hello</p>\n");
+}
+
+#[test]
+fn test_raw_frontmatter() {
+    const TEMPLATE: &str = "<h1>{{title}}</h1><pre data-format=\"{{frontmatter_format}}\">{{frontmatter_raw}}</pre>{{content}}";
+    let doc = "+++\ntitle = \"Abc\"\n+++\nhello";
+
+    let config = RenderConfig {
+        templates: vec![("t", TEMPLATE)],
+        raw_frontmatter: true,
+        ..Default::default()
+    };
+    let gen = Renderer::init(&config).expect("init");
+    let mut buf: Vec<u8> = Vec::new();
+    gen.write_document_html(TomlMap::new(), doc, "t", &mut buf)
+        .expect("write");
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        "<h1>Abc</h1><pre data-format=\"toml\">title = \"Abc\"</pre><p>hello</p>\n"
+    );
+
+    // not enabled by default
+    let mut gen = Renderer::default();
+    gen.add_template(("t", TEMPLATE)).expect("template");
+    let mut buf: Vec<u8> = Vec::new();
+    gen.write_document_html(TomlMap::new(), doc, "t", &mut buf)
+        .expect("write");
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        "<h1>Abc</h1><pre data-format=\"\"></pre><p>hello</p>\n"
+    );
+}
+
+#[test]
+fn test_partials() {
+    let config = RenderConfig {
+        templates: vec![("page", "{{> header}}<main>{{body}}</main>")],
+        partials: vec![("header", "<header>{{title}}</header>")],
+        ..Default::default()
+    };
+    let gen = Renderer::init(&config).expect("init");
+    let mut map = TomlMap::new();
+    map.insert("title".into(), "Abc".into());
+    map.insert("body".into(), "text".into());
+    assert_eq!(
+        gen.render_to_string("page", map).expect("render"),
+        "<header>Abc</header><main>text</main>"
+    );
+
+    let config = RenderConfig {
+        templates: vec![("page", "x")],
+        partials: vec![("page", "y")],
+        ..Default::default()
+    };
+    assert!(matches!(
+        Renderer::init(&config),
+        Err(Error::TemplateNameCollision(name)) if name == "page"
+    ));
+
+    // templates added later are checked too
+    let mut gen = Renderer::init(&RenderConfig {
+        partials: vec![("header", "<header/>")],
+        ..Default::default()
+    })
+    .expect("init");
+    assert!(matches!(
+        gen.add_template(("header", "x")),
+        Err(Error::TemplateNameCollision(name)) if name == "header"
+    ));
+    assert!(matches!(
+        gen.register_template_checked("header", "x", &[]),
+        Err(Error::TemplateNameCollision(_))
+    ));
+    gen.add_template(("page", "{{> header}}"))
+        .expect("no collision");
+}
+
+#[test]
+fn test_register_template_checked() {
+    let mut renderer = Renderer::default();
+    let res = renderer.register_template_checked(
+        "page",
+        "<h1>{{page.heading}}</h1>{{#if author}}{{author}}{{/if}}{{join-csv tags}}",
+        &["title", "author", "page", "tags"],
+    );
+    assert!(
+        matches!(&res, Err(Error::MissingTemplateVars(name, missing)) if name == "page" && missing == "title"),
+        "missing title rejected: {:?}",
+        res
+    );
+    assert!(
+        renderer.render_to_string("page", TomlMap::new()).is_err(),
+        "rejected template not registered"
+    );
+
+    renderer
+        .register_template_checked(
+            "page",
+            "<title>{{ title }}</title>{{#each page.items}}{{this}}{{/each}}",
+            &["title", "page.items"],
+        )
+        .expect("all vars referenced");
+    assert_eq!(
+        render_with(&renderer, "page", "title", "Hello".into()).unwrap(),
+        "<title>Hello</title>"
+    );
+}
+
+#[test]
+fn test_markdownify() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("summary", "<div>{{markdownify summary}}</div>"))
+        .expect("template");
+    let render = |summary: TomlValue| render_with(&renderer, "summary", "summary", summary);
+    assert_eq!(
+        render("Some **bold** text".into()).unwrap(),
+        "<div>Some <strong>bold</strong> text</div>"
+    );
+    // multiple blocks keep their tags
+    assert_eq!(
+        render("One\n\nTwo".into()).unwrap(),
+        "<div><p>One</p>\n<p>Two</p>\n</div>"
+    );
+    match render(TomlValue::Integer(5)) {
+        Err(e) => assert!(e.to_string().contains("markdownify"), "{}", e),
+        Ok(s) => panic!("expected error, got {}", s),
+    }
+}
+
+#[test]
+fn test_case_helpers() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template((
+            "case",
+            "{{upper name}}|{{lower name}}|{{title-case name}}|{{slugify name}}",
+        ))
+        .expect("template");
+    let render = |name: TomlValue| render_with(&renderer, "case", "name", name);
+    assert_eq!(
+        render("getting started with HTML".into()).unwrap(),
+        "GETTING STARTED WITH HTML|getting started with html|Getting Started With HTML|getting-started-with-html"
+    );
+    // slugify matches heading anchors
+    assert_eq!(
+        render("α-ω a.b".into()).unwrap(),
+        "Α-Ω A.B|α-ω a.b|Α-ω A.b|a-o-a-b"
+    );
+    match render(TomlValue::Boolean(true)) {
+        Err(e) => assert!(e.to_string().contains("upper: expect string"), "{}", e),
+        Ok(s) => panic!("expected error, got {}", s),
+    }
+
+    // slugify options
+    renderer
+        .add_template((
+            "slug",
+            "{{slugify name unicode=true}}|{{slugify name max_len=8}}",
+        ))
+        .expect("template");
+    assert_eq!(
+        render_with(&renderer, "slug", "name", "Ärger über Öl".into()).unwrap(),
+        "ärger-über-öl|arger"
+    );
+}
+
+#[test]
+fn test_truncate() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("card", "{{truncate text 12}}"))
+        .expect("template");
+    renderer
+        .add_template(("suffix", r#"{{truncate text 12 "..."}}"#))
+        .expect("template");
+    let render =
+        |template: &str, text: &str| render_with(&renderer, template, "text", text.into()).unwrap();
+    // short strings are unchanged
+    assert_eq!(render("card", "Short"), "Short");
+    assert_eq!(render("card", "Exactly 12 c"), "Exactly 12 c");
+    // long strings are cut at a word boundary
+    assert_eq!(render("card", "The quick brown fox"), "The quick…");
+    assert_eq!(render("card", "The quick br own"), "The quick br…");
+    assert_eq!(render("suffix", "The quick brown fox"), "The quick...");
+    // a single long word is cut
+    assert_eq!(render("card", "Supercalifragilistic"), "Supercalifra…");
+    // multibyte characters are counted as characters, and never split
+    assert_eq!(render("card", "héllo wörld ünïcode"), "héllo wörld…");
+    assert_eq!(
+        render("card", "日本語のテキストを短くする"),
+        "日本語のテキストを短くす…"
+    );
+
+    assert!(renderer
+        .render_to_string("card", TomlMap::new())
+        .unwrap_err()
+        .to_string()
+        .contains("truncate"));
+}
 
-    // had to remove newlines - there's an added \n after
-    let output = String::from_utf8_lossy(&buf).replace("\n", "");
-    assert_eq!(expected, output);
+#[test]
+fn test_default_helper() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("byline", r#"By {{default author "Anonymous"}}"#))
+        .expect("template");
+    let render = |author: Option<TomlValue>| {
+        match author {
+            Some(author) => render_with(&renderer, "byline", "author", author),
+            None => renderer.render_to_string("byline", TomlMap::new()),
+        }
+        .unwrap()
+    };
+    assert_eq!(render(Some("alice".into())), "By alice");
+    assert_eq!(render(Some(TomlValue::Integer(0))), "By 0");
+    assert_eq!(render(None), "By Anonymous");
+    assert_eq!(render(Some("".into())), "By Anonymous");
+
+    // missing nested values are null
+    renderer
+        .add_template(("nested", r#"{{default page.author "Anonymous"}}"#))
+        .expect("template");
+    assert_eq!(
+        renderer.render_to_string("nested", TomlMap::new()).unwrap(),
+        "Anonymous"
+    );
+}
+
+#[test]
+fn test_format_number_helper() {
+    let mut renderer = Renderer::default();
+    let templates = [
+        ("number", "{{format-number n}}"),
+        ("price", "{{format-number n 2}}"),
+        ("euro", r#"{{format-number n 2 sep="." point=","}}"#),
+    ];
+    for template in templates.iter() {
+        renderer.add_template(*template).expect("template");
+    }
+    let render = |template: &str, n: TomlValue| render_with(&renderer, template, "n", n);
+    let ok = |template: &str, n: TomlValue| render(template, n).expect("render");
+
+    assert_eq!(ok("number", TomlValue::Integer(1234567)), "1,234,567");
+    assert_eq!(ok("number", TomlValue::Integer(-1234)), "-1,234");
+    assert_eq!(ok("number", TomlValue::Integer(123)), "123");
+    assert_eq!(ok("number", TomlValue::Integer(0)), "0");
+    assert_eq!(ok("number", TomlValue::Float(1234.5)), "1,234.5");
+    assert_eq!(ok("price", TomlValue::Integer(1234567)), "1,234,567.00");
+    assert_eq!(ok("price", TomlValue::Float(1234567.891)), "1,234,567.89");
+    assert_eq!(
+        ok("price", TomlValue::Float(999.999)),
+        "1,000.00",
+        "rounding"
+    );
+    assert_eq!(ok("price", TomlValue::Float(-0.125)), "-0.12");
+    assert_eq!(ok("euro", TomlValue::Float(1234567.891)), "1.234.567,89");
+
+    let err = render("number", "lots".into()).expect_err("not a number");
+    assert!(err.to_string().contains("expect number"), "{}", err);
+}
+
+#[test]
+fn test_json_helper() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("compact", "{{json page}}"))
+        .expect("template");
+    renderer
+        .add_template(("pretty", "{{json page true}}"))
+        .expect("template");
+    let data: TomlMap = toml::from_str(
+        r#"
+        [page]
+        title = "Say \"hi\""
+        tags = ["a", "b"]
+        [page.author]
+        name = "Kim"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        renderer.render_to_string("compact", data.clone()).unwrap(),
+        r#"{"author":{"name":"Kim"},"tags":["a","b"],"title":"Say \"hi\""}"#
+    );
+    assert_eq!(
+        renderer.render_to_string("pretty", data).unwrap(),
+        r#"{
+  "author": {
+    "name": "Kim"
+  },
+  "tags": [
+    "a",
+    "b"
+  ],
+  "title": "Say \"hi\""
+}"#
+    );
+
+    // html special characters can't close the script element, or a quoted attribute
+    renderer
+        .add_template((
+            "script",
+            "<script type=\"application/ld+json\">{{json page}}</script>",
+        ))
+        .expect("template");
+    let data: TomlMap = toml::from_str(
+        r#"
+        [page]
+        title = "</script><script>alert('x' & 1)</script>"
+        "#,
+    )
+    .unwrap();
+    let html = renderer.render_to_string("script", data).unwrap();
+    assert_eq!(
+        html,
+        concat!(
+            r#"<script type="application/ld+json">{"title":"#,
+            r#""\u003c/script\u003e\u003cscript\u003ealert(\u0027x\u0027 \u0026 1)\u003c/script\u003e"}"#,
+            "</script>"
+        )
+    );
+    let json = html
+        .trim_start_matches(r#"<script type="application/ld+json">"#)
+        .trim_end_matches("</script>");
+    let value: serde_json::Value = serde_json::from_str(json).expect("valid json");
+    assert_eq!(value["title"], "</script><script>alert('x' & 1)</script>");
+}
+
+#[test]
+fn test_render_cache() {
+    let mut renderer = Renderer::default().with_cache(2);
+    renderer
+        .add_template(("hello", "Hello {{name}}"))
+        .expect("template");
+    let data = |name: &str| {
+        let mut data = TomlMap::new();
+        data.insert("name".into(), name.into());
+        data
+    };
+    let stats = |hits, misses, len| Some(CacheStats { hits, misses, len });
+
+    assert_eq!(
+        renderer.render_to_string("hello", data("a")).unwrap(),
+        "Hello a"
+    );
+    assert_eq!(renderer.cache_stats(), stats(0, 1, 1));
+    assert_eq!(
+        renderer.render_to_string("hello", data("a")).unwrap(),
+        "Hello a"
+    );
+    assert_eq!(
+        renderer.cache_stats(),
+        stats(1, 1, 1),
+        "second render is cached"
+    );
+
+    // least recently used entry is removed when full
+    renderer.render_to_string("hello", data("b")).unwrap();
+    renderer.render_to_string("hello", data("a")).unwrap();
+    renderer.render_to_string("hello", data("c")).unwrap();
+    assert_eq!(renderer.cache_stats(), stats(2, 3, 2));
+    renderer.render_to_string("hello", data("a")).unwrap();
+    assert_eq!(renderer.cache_stats(), stats(3, 3, 2), "a is still cached");
+    renderer.render_to_string("hello", data("b")).unwrap();
+    assert_eq!(renderer.cache_stats(), stats(3, 4, 2), "b was removed");
+
+    // changing a template clears the cache
+    renderer
+        .add_template(("hello", "Hi {{name}}"))
+        .expect("template");
+    assert_eq!(renderer.cache_stats(), stats(3, 4, 0));
+    assert_eq!(
+        renderer.render_to_string("hello", data("a")).unwrap(),
+        "Hi a"
+    );
+
+    renderer.clear_cache();
+    assert_eq!(renderer.cache_stats().unwrap().len, 0);
+    assert_eq!(Renderer::default().cache_stats(), None);
+}
+
+#[test]
+fn test_minify_html() {
+    let html = concat!(
+        "<!DOCTYPE html>\n<html>\n  <body>\n    <div class=\"toc\">\n",
+        "      <p>Some   <b>bold</b> <i>italic</i>\n text</p>\n    </div>\n",
+        "    <pre><code>fn main() {\n    println!(\"  hi  \");\n}\n</code></pre>\n",
+        "    <p>Use <code>a  b</code> here</p>\n",
+        "    <!-- keep   this -->\n  </body>\n</html>\n"
+    );
+    assert_eq!(
+        minify_html(html),
+        concat!(
+            "<!DOCTYPE html><html><body><div class=\"toc\">",
+            "<p>Some <b>bold</b> <i>italic</i> text</p></div>",
+            "<pre><code>fn main() {\n    println!(\"  hi  \");\n}\n</code></pre>",
+            "<p>Use <code>a  b</code> here</p>",
+            " <!-- keep   this --> </body></html>"
+        )
+    );
+
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("page", "<main>\n  {{content}}\n</main>\n"))
+        .expect("template");
+    renderer.set_minify(true);
+    let page = renderer
+        .write_page_html_to_string(TomlMap::new(), "# Title\n\nHello\n", "page")
+        .expect("render");
+    assert_eq!(page, "<main><h1>Title</h1><p>Hello</p></main>");
+}
+
+#[test]
+fn test_format_date() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("date", r#"{{format-date date "%Y-%m-%d %H:%M %z"}}"#))
+        .expect("template");
+    let render = |date: &str| render_with(&renderer, "date", "date", date.into());
+    assert_eq!(
+        render("2024-01-15T10:30:00-08:00").unwrap(),
+        "2024-01-15 10:30 -0800"
+    );
+    assert_eq!(render("2024-01-15").unwrap(), "2024-01-15 00:00 +0000");
+    assert_eq!(
+        render("2024-01-15 10:30:00").unwrap(),
+        "2024-01-15 10:30 +0000"
+    );
+    match render("Jan 15, 2024") {
+        Err(e) => assert!(e.to_string().contains("Jan 15, 2024"), "{}", e),
+        Ok(s) => panic!("expected error, got {}", s),
+    }
+}
+
+#[test]
+fn test_diff_granularity() {
+    let style = DiffStyle {
+        ins_start: "[+".into(),
+        ins_end: "+]".into(),
+        del_start: "[-".into(),
+        del_end: "-]".into(),
+    };
+    let first = "The quick brown fox.\nSecond line.\n";
+    let second = "The quick green fox.\nSecond line.\n";
+    assert_eq!(
+        generate_diff_ext(first, second, &style, DiffGranularity::Line).unwrap(),
+        "[-The quick brown fox.\n-][+The quick green fox.\n+]Second line.\n"
+    );
+    assert_eq!(
+        generate_diff_ext(first, second, &style, DiffGranularity::Word).unwrap(),
+        "The quick [-brown-][+green+] fox.\nSecond line.\n"
+    );
+    let chars = generate_diff(first, second, &style).unwrap();
+    assert!(chars.starts_with("The quick "), "{}", chars);
+    assert!(
+        !chars.contains("[-brown-]"),
+        "only changed characters: {}",
+        chars
+    );
+    assert!(chars.ends_with(" fox.\nSecond line.\n"));
+}
+
+#[test]
+fn test_diff_chunks() {
+    assert_eq!(
+        diff_chunks("hello world", "hello big world"),
+        vec![
+            DiffChunk::Equal("hello ".into()),
+            DiffChunk::Insert("big ".into()),
+            DiffChunk::Equal("world".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_build_time() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("t", "{{relative-date date}}"))
+        .expect("template");
+    renderer.set_build_time(Utc.with_ymd_and_hms(2024, 1, 18, 12, 0, 0).unwrap());
+    let render = |date: &str| render_with(&renderer, "t", "date", date.into()).unwrap();
+    assert_eq!(render("2024-01-15"), "3 days ago");
+    assert_eq!(render("2024-01-18T11:59:30Z"), "just now");
+    assert_eq!(render("2024-01-18 11:00:00"), "1 hour ago");
+    assert_eq!(render("2024-01-18T12:05:00Z"), "in 5 minutes");
+}
+
+#[test]
+fn test_time_ago() {
+    let mut renderer = Renderer::default();
+    renderer
+        .add_template(("posted", "posted {{time-ago date}}"))
+        .expect("template");
+    renderer.set_build_time(Utc.with_ymd_and_hms(2024, 6, 15, 12, 0, 0).unwrap());
+    let render = |date: &str| render_with(&renderer, "posted", "date", date.into()).unwrap();
+    assert_eq!(render("2024-06-15T11:59:59Z"), "posted just now");
+    assert_eq!(render("2024-06-15T11:55:00Z"), "posted 5 minutes ago");
+    assert_eq!(render("2024-06-15 09:00:00"), "posted 3 hours ago");
+    assert_eq!(render("2024-06-13T12:00:00+00:00"), "posted 2 days ago");
+    assert_eq!(render("2024-03-15"), "posted 3 months ago");
+    assert_eq!(render("2022-06-01"), "posted 2 years ago");
 }
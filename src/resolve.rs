@@ -0,0 +1,197 @@
+//! Resolve links and image references in markdown documents
+//!
+//! References are resolved relative to the document containing them (or to the site
+//! root, if they start with '/'), and classified so that link-rewriting, image, and
+//! link-checking passes all treat them the same way.
+//!
+use crate::{Error, Result};
+use std::path::{Component, Path};
+
+/// Extensions of pages (a link to a page is internal; anything else local is an asset)
+const PAGE_EXTENSIONS: &[&str] = &["md", "markdown", "html", "htm"];
+
+/// Kind of reference
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RefKind {
+    /// Another page in the site (or a directory index)
+    Internal,
+    /// Url with a scheme (`https:`, `mailto:`, ...) or protocol-relative (`//host/...`)
+    External,
+    /// Fragment within the current page (`#section`)
+    Anchor,
+    /// Local non-page file, such as an image, stylesheet, or pdf
+    Asset,
+}
+
+/// Reference resolved against the current document
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedRef {
+    /// Kind of reference
+    pub kind: RefKind,
+    /// For internal and asset references, the normalized path relative to the site root,
+    /// with '/' separators and no leading slash. For external references, the original url.
+    /// Empty for anchors.
+    pub path: String,
+    /// Query string, without the '?'
+    pub query: Option<String>,
+    /// Fragment, without the '#'
+    pub fragment: Option<String>,
+}
+
+/// Resolve a reference from the document at `current_rel_path` (a path relative to the
+/// site root, such as `MarkdownPath.rel_path`). Relative references are resolved against
+/// the document's directory, and references beginning with '/' against the site root.
+/// `.` and `..` segments are normalized. Returns Error::RefEscapesRoot if the resolved path
+/// would be outside the site root.
+pub fn resolve_ref(current_rel_path: &Path, reference: &str) -> Result<ResolvedRef> {
+    let kind = ref_kind(reference);
+    match kind {
+        RefKind::Anchor => {
+            return Ok(ResolvedRef {
+                kind,
+                path: String::new(),
+                query: None,
+                fragment: Some(reference[1..].to_string()),
+            })
+        }
+        RefKind::External => {
+            return Ok(ResolvedRef {
+                kind,
+                path: reference.to_string(),
+                query: None,
+                fragment: None,
+            })
+        }
+        RefKind::Internal | RefKind::Asset => {}
+    }
+
+    let (path, query, fragment) = split_ref(reference);
+
+    let mut segments: Vec<String> = Vec::new();
+    if !path.starts_with('/') {
+        if let Some(dir) = current_rel_path.parent() {
+            // rel_path from the scanner contains only normal components
+            for c in dir.components() {
+                if let Component::Normal(s) = c {
+                    segments.push(s.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    for seg in path.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(Error::RefEscapesRoot(reference.to_string()));
+                }
+            }
+            _ => segments.push(seg.to_string()),
+        }
+    }
+
+    Ok(ResolvedRef {
+        kind,
+        path: segments.join("/"),
+        query: query.map(String::from),
+        fragment: fragment.map(String::from),
+    })
+}
+
+/// Kind of reference, as classified by `resolve_ref`, without resolving its path.
+/// This doesn't fail for references outside the site root, so it can be used
+/// where the current document isn't known.
+pub fn ref_kind(reference: &str) -> RefKind {
+    if reference.starts_with('#') {
+        return RefKind::Anchor;
+    }
+    if is_external(reference) {
+        return RefKind::External;
+    }
+    let (path, _, _) = split_ref(reference);
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if PAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()) => RefKind::Internal,
+        Some(_) => RefKind::Asset,
+        None => RefKind::Internal,
+    }
+}
+
+/// Split local reference into (path, query, fragment), without the '?' and '#'
+pub(crate) fn split_ref(reference: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (rest, fragment) = match reference.split_once('#') {
+        Some((rest, frag)) => (rest, Some(frag)),
+        None => (reference, None),
+    };
+    match rest.split_once('?') {
+        Some((path, query)) => (path, Some(query), fragment),
+        None => (rest, None, fragment),
+    }
+}
+
+/// Returns true if the reference has a url scheme or is protocol-relative
+pub(crate) fn is_external(reference: &str) -> bool {
+    if reference.starts_with("//") {
+        return true;
+    }
+    match reference.find(':') {
+        Some(ix) => {
+            let scheme = &reference[..ix];
+            // don't mistake a path segment for a scheme
+            !scheme.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+#[test]
+fn test_resolve_ref() {
+    let page = Path::new("guide/intro/page.md");
+
+    let r = resolve_ref(page, "../x.md").expect("parent");
+    assert_eq!(r.kind, RefKind::Internal);
+    assert_eq!(r.path, "guide/x.md");
+
+    let r = resolve_ref(page, "./a/../b.md#sec").expect("dot segments");
+    assert_eq!(r.path, "guide/intro/b.md");
+    assert_eq!(r.fragment.as_deref(), Some("sec"));
+
+    let r = resolve_ref(page, "/abs").expect("absolute");
+    assert_eq!(r.kind, RefKind::Internal);
+    assert_eq!(r.path, "abs");
+
+    let r = resolve_ref(page, "img/logo.png?v=2").expect("asset");
+    assert_eq!(r.kind, RefKind::Asset);
+    assert_eq!(r.path, "guide/intro/img/logo.png");
+    assert_eq!(r.query.as_deref(), Some("v=2"));
+
+    let r = resolve_ref(page, "https://example.com/a.md").expect("external");
+    assert_eq!(r.kind, RefKind::External);
+    assert_eq!(r.path, "https://example.com/a.md");
+    assert_eq!(
+        resolve_ref(page, "mailto:me@example.com").unwrap().kind,
+        RefKind::External
+    );
+
+    let r = resolve_ref(page, "#top").expect("anchor");
+    assert_eq!(r.kind, RefKind::Anchor);
+    assert_eq!(r.fragment.as_deref(), Some("top"));
+
+    assert!(matches!(
+        resolve_ref(page, "../../../etc"),
+        Err(Error::RefEscapesRoot(_))
+    ));
+    assert!(matches!(
+        resolve_ref(Path::new("page.md"), "/../x.md"),
+        Err(Error::RefEscapesRoot(_))
+    ));
+
+    // kind without resolving, even outside the root
+    assert_eq!(ref_kind("../../../x.md#a"), RefKind::Internal);
+    assert_eq!(ref_kind("img/logo.png?v=2"), RefKind::Asset);
+    assert_eq!(ref_kind("//cdn.example.com/x.md"), RefKind::External);
+    assert_eq!(ref_kind("#top"), RefKind::Anchor);
+}
@@ -0,0 +1,83 @@
+//! Search index, for client-side search libraries such as lunr or Fuse
+//!
+use crate::{md_parser::plain_text_with, Error, Result};
+use serde::Serialize;
+
+/// Page to add to the search index
+#[derive(Clone, Debug)]
+pub struct IndexedPage {
+    /// Page title
+    pub title: String,
+    /// Page url, e.g., "/guide/intro.html"
+    pub url: String,
+    /// Page markdown, without frontmatter
+    pub markdown: String,
+}
+
+/// Options for build_search_index_with
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndexOptions {
+    /// Include code blocks and inline code in indexed text (default false)
+    pub include_code: bool,
+}
+
+/// Search index entry
+#[derive(Serialize)]
+struct SearchEntry<'page> {
+    title: &'page str,
+    url: &'page str,
+    text: String,
+}
+
+/// Generate json search index: an array of `{title, url, text}` objects,
+/// where text is the plain text of the page (see `md_parser::plain_text`).
+pub fn build_search_index(pages: &[IndexedPage]) -> Result<String> {
+    build_search_index_with(pages, &SearchIndexOptions::default())
+}
+
+/// Generate json search index, like `build_search_index`, with options
+pub fn build_search_index_with(pages: &[IndexedPage], opt: &SearchIndexOptions) -> Result<String> {
+    let entries = pages
+        .iter()
+        .map(|page| SearchEntry {
+            title: &page.title,
+            url: &page.url,
+            text: plain_text_with(&page.markdown, opt.include_code),
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string(&entries).map_err(|e| Error::Bug(e.to_string()))
+}
+
+#[test]
+fn test_build_search_index() {
+    let pages = vec![IndexedPage {
+        title: "Install".into(),
+        url: "/install.html".into(),
+        markdown: concat!(
+            "# Install\n\n",
+            "Run the *installer*, then `mdsite --help`.\n\n",
+            "```sh\ncargo install mdsite\n```\n\n",
+            "<div class=\"note\">html</div>\n\n",
+            "- one\n- two\n"
+        )
+        .into(),
+    }];
+    let json: serde_json::Value =
+        serde_json::from_str(&build_search_index(&pages).expect("index")).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([{
+            "title": "Install",
+            "url": "/install.html",
+            "text": "Install Run the installer, then . one two",
+        }])
+    );
+
+    let opt = SearchIndexOptions { include_code: true };
+    let json: serde_json::Value =
+        serde_json::from_str(&build_search_index_with(&pages, &opt).expect("index")).unwrap();
+    assert_eq!(
+        json[0]["text"],
+        "Install Run the installer, then mdsite --help. cargo install mdsite one two"
+    );
+}
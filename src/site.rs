@@ -0,0 +1,140 @@
+//! Site generation: scan source folders, render each markdown page with the
+//! template selected by its frontmatter, and write html files to an output sink.
+//!
+use crate::{
+    file_scan::{index_sources, ScanOptions},
+    markdown::split_markdown,
+    output::{write_page, OutputSink},
+    render::Renderer,
+    Result,
+};
+use std::path::{Path, PathBuf};
+
+/// Template used for pages whose frontmatter has no `layout`
+pub const DEFAULT_LAYOUT: &str = "page";
+
+/// Options for build_from_sources
+pub struct SiteOptions {
+    /// Options for scanning source folders
+    pub scan: ScanOptions,
+    /// Template for pages without a `layout` frontmatter value (default: "page")
+    pub default_layout: String,
+}
+
+impl Default for SiteOptions {
+    fn default() -> Self {
+        Self {
+            scan: ScanOptions::default(),
+            default_layout: DEFAULT_LAYOUT.to_string(),
+        }
+    }
+}
+
+/// Results of build_from_sources
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// Files written, relative to the output folder
+    pub written: Vec<PathBuf>,
+    /// Source path and error message for each page that couldn't be generated
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+/// Generate html for every markdown file in the source folders, and write it to the sink
+/// (e.g., an `FsSink` for an output folder). Unlike `output::build_site`, which renders
+/// documents with one template, each page is rendered with the template named by its
+/// frontmatter `layout` value, or the default layout. Pages are written to their path
+/// relative to their source folder, with the extension changed to ".html".
+/// Errors scanning the source folders are returned. Errors reading, rendering,
+/// or writing a page are added to the report, and other pages are still generated.
+pub fn build_from_sources(
+    sources: &[PathBuf],
+    renderer: &Renderer,
+    opt: &SiteOptions,
+    sink: &mut dyn OutputSink,
+) -> Result<BuildReport> {
+    let scan = index_sources(sources, &opt.scan)?;
+    let mut report = BuildReport::default();
+    for page in scan.markdown.iter() {
+        match build_page(&page.path, &page.rel_path, renderer, opt, sink) {
+            Ok(out_path) => report.written.push(out_path),
+            Err(e) => report.errors.push((page.path.clone(), e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+/// Render one page with its layout and write it to the sink. Returns the path written.
+fn build_page(
+    path: &Path,
+    rel_path: &Path,
+    renderer: &Renderer,
+    opt: &SiteOptions,
+    sink: &mut dyn OutputSink,
+) -> Result<PathBuf> {
+    let document = std::fs::read_to_string(path)?;
+    let (front, _) = split_markdown(&document);
+    let layout = match front.to_toml()?.get("layout").and_then(|v| v.as_str()) {
+        Some(layout) => layout.to_string(),
+        None => opt.default_layout.clone(),
+    };
+    write_page(renderer, &layout, rel_path, &document, sink)
+}
+
+#[test]
+fn test_build_from_sources() {
+    use crate::{output::FsSink, render::RenderConfig};
+
+    let src = tempfile::tempdir().expect("tempdir");
+    let files = [
+        ("index.md", "+++\ntitle = \"Home\"\n+++\n# Welcome\n"),
+        (
+            "blog/first.md",
+            "---\ntitle: First\nlayout: post\n---\nHello\n",
+        ),
+        ("blog/bad.md", "+++\nlayout = \"missing\"\n+++\nOops\n"),
+        ("notes.txt", "not markdown"),
+    ];
+    for (name, content) in files.iter() {
+        let path = src.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    let renderer = Renderer::init(&RenderConfig {
+        templates: vec![
+            ("page", "<title>{{title}}</title>{{content}}"),
+            ("post", "<article>{{title}}: {{content}}</article>"),
+        ],
+        ..Default::default()
+    })
+    .expect("renderer");
+
+    let out = tempfile::tempdir().expect("tempdir");
+    let report = build_from_sources(
+        &[src.path().to_path_buf()],
+        &renderer,
+        &SiteOptions::default(),
+        &mut FsSink::new(out.path()),
+    )
+    .expect("build");
+
+    let mut written = report.written.clone();
+    written.sort();
+    assert_eq!(
+        written,
+        vec![
+            PathBuf::from("blog/first.html"),
+            PathBuf::from("index.html")
+        ]
+    );
+    assert_eq!(
+        std::fs::read_to_string(out.path().join("index.html")).unwrap(),
+        "<title>Home</title><h1>Welcome</h1>\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(out.path().join("blog/first.html")).unwrap(),
+        "<article>First: <p>Hello</p>\n</article>"
+    );
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].0, src.path().join("blog/bad.md"));
+    assert!(!out.path().join("blog/bad.html").exists());
+}
@@ -0,0 +1,156 @@
+//! Site index - maps page titles and slugs to output urls, for resolving
+//! cross-document links such as `[[Page Title]]`.
+//!
+use crate::{
+    file_scan::MarkdownData,
+    md_parser::{slugify, SlugOptions},
+};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Indexed page
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexEntry {
+    /// Page title, from frontmatter, or the file name if the page has no title
+    pub title: String,
+    /// Unique slug for the title. If several pages have the same title slug, the first
+    /// (ordered by rel_path) gets the plain slug, and others get "-1", "-2", ...
+    pub slug: String,
+    /// Output url, e.g., "/guide/intro.html"
+    pub url: String,
+}
+
+/// Index of pages by title and slug
+#[derive(Debug, Default)]
+pub struct SiteIndex {
+    entries: Vec<IndexEntry>,
+    by_slug: HashMap<String, usize>,
+}
+
+impl SiteIndex {
+    /// Build index from pages. `title` returns the title from a page's frontmatter.
+    /// Pages whose frontmatter failed to parse, or that have no title,
+    /// are indexed by file name (without extension).
+    pub fn build<T, F>(pages: &[MarkdownData<T>], title: F) -> Self
+    where
+        T: DeserializeOwned,
+        F: Fn(&T) -> Option<String>,
+    {
+        // sort by path so that disambiguation is deterministic
+        let mut pages = pages.iter().collect::<Vec<_>>();
+        pages.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+
+        let mut index = SiteIndex::default();
+        let opts = SlugOptions::default();
+        for page in pages {
+            let title = page
+                .frontmatter
+                .as_ref()
+                .ok()
+                .and_then(&title)
+                .unwrap_or_else(|| file_stem(&page.rel_path));
+            let base = slugify(&title, &opts);
+            let mut slug = base.clone();
+            let mut n = 0;
+            while index.by_slug.contains_key(&slug) {
+                n += 1;
+                slug = format!("{}-{}", base, n);
+            }
+            index.by_slug.insert(slug.clone(), index.entries.len());
+            index.entries.push(IndexEntry {
+                title,
+                slug,
+                url: page_url(&page.rel_path),
+            });
+        }
+        index
+    }
+
+    /// All entries, ordered by rel_path
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Look up url for page title. Matching uses the title's slug, so it is
+    /// insensitive to case and punctuation. For duplicate titles, this returns
+    /// the first page; others can be found with `url_for_slug`.
+    pub fn url_for_title(&self, title: &str) -> Option<&str> {
+        self.url_for_slug(&slugify(title, &SlugOptions::default()))
+    }
+
+    /// Look up url for a unique slug
+    pub fn url_for_slug(&self, slug: &str) -> Option<&str> {
+        self.by_slug
+            .get(slug)
+            .map(|ix| self.entries[*ix].url.as_str())
+    }
+}
+
+/// Url of generated html page: "/" + rel_path, with the extension changed to ".html"
+pub(crate) fn page_url(rel_path: &Path) -> String {
+    let html = rel_path.with_extension("html");
+    let segments = html
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    format!("/{}", segments.join("/"))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_site_index() {
+    use crate::{Error, TomlMap};
+    use std::path::PathBuf;
+
+    fn page(rel_path: &str, title: Option<&str>) -> MarkdownData<TomlMap> {
+        let frontmatter = match title {
+            Some(title) => {
+                let mut map = TomlMap::new();
+                map.insert("title".into(), title.into());
+                Ok(map)
+            }
+            None => Err(Error::FrontmatterParse("missing".into())),
+        };
+        MarkdownData {
+            path: PathBuf::from("content").join(rel_path),
+            rel_path: PathBuf::from(rel_path),
+            frontmatter,
+        }
+    }
+    let title = |fm: &TomlMap| fm.get("title").and_then(|t| t.as_str()).map(String::from);
+
+    let pages = vec![
+        page("guide/setup.md", Some("Getting Started")),
+        page("blog/start.md", Some("Getting Started")),
+        page("faq.md", None),
+    ];
+    let index = SiteIndex::build(&pages, title);
+    assert_eq!(index.entries().len(), 3);
+    assert_eq!(
+        index.url_for_title("Getting Started"),
+        Some("/blog/start.html"),
+        "first by path wins"
+    );
+    assert_eq!(
+        index.url_for_title("getting started!"),
+        Some("/blog/start.html"),
+        "match by slug"
+    );
+    assert_eq!(
+        index.url_for_slug("getting-started-1"),
+        Some("/guide/setup.html"),
+        "duplicate disambiguated"
+    );
+    assert_eq!(
+        index.url_for_title("faq"),
+        Some("/faq.html"),
+        "file name fallback"
+    );
+    assert_eq!(index.url_for_title("missing"), None);
+}
@@ -0,0 +1,186 @@
+//! Sitemap (`sitemap.xml`) generation, for search engines
+//!
+use crate::{feed::toml_date, md_parser::escape_html, Error, Result, TomlMap};
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+/// Page in the sitemap
+#[derive(Clone, Debug, PartialEq)]
+pub struct SitemapEntry {
+    /// Url path of the page, relative to the base url, e.g., "/guide/intro.html"
+    pub path: String,
+    /// Time the page was last modified
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+impl SitemapEntry {
+    /// Entry for a page
+    pub fn new<T: Into<String>>(path: T, lastmod: Option<DateTime<Utc>>) -> Self {
+        Self {
+            path: path.into(),
+            lastmod,
+        }
+    }
+
+    /// Entry for a page, with lastmod from its frontmatter `updated` value, or `date` if
+    /// there's no `updated`. If neither is set, the entry has no lastmod.
+    /// Returns Error::FrontmatterParse if the value isn't a valid date.
+    pub fn from_frontmatter<T: Into<String>>(map: &TomlMap, path: T) -> Result<Self> {
+        let path = path.into();
+        let lastmod = match map.get("updated").or_else(|| map.get("date")) {
+            Some(value) => Some(toml_date(value).map(DateTime::<Utc>::from).ok_or_else(|| {
+                Error::FrontmatterParse(format!("sitemap entry '{}' has an invalid date", path))
+            })?),
+            None => None,
+        };
+        Ok(Self { path, lastmod })
+    }
+
+    /// Entry for the html page generated from a scanned markdown file. lastmod is the file's
+    /// modified time, if the scan used `ScanOptions.collect_metadata`.
+    #[cfg(feature = "file-scan")]
+    pub fn from_markdown_path(mdp: &crate::file_scan::MarkdownPath) -> Self {
+        Self {
+            path: crate::site_index::page_url(&mdp.rel_path),
+            lastmod: mdp.modified.map(DateTime::<Utc>::from),
+        }
+    }
+}
+
+/// Page to add to a sitemap with `entries_from_pages`
+#[derive(Clone, Debug)]
+pub struct SitemapPage<'p> {
+    /// Source path of the page, for reporting errors
+    pub path: PathBuf,
+    /// Url path of the page, relative to the base url
+    pub url_path: String,
+    /// Page frontmatter
+    pub frontmatter: &'p TomlMap,
+}
+
+/// Sitemap entries for the pages, like `SitemapEntry::from_frontmatter`. Pages with an
+/// invalid date are skipped, and returned with their errors, so one bad page doesn't
+/// prevent generating the sitemap.
+/// Returns (entries, skipped pages)
+pub fn entries_from_pages(pages: &[SitemapPage<'_>]) -> (Vec<SitemapEntry>, Vec<(PathBuf, Error)>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for page in pages.iter() {
+        match SitemapEntry::from_frontmatter(page.frontmatter, page.url_path.as_str()) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => skipped.push((page.path.clone(), e)),
+        }
+    }
+    (entries, skipped)
+}
+
+/// Generate sitemap xml for the pages. Each page url is the base url (e.g.,
+/// "https://example.com") followed by the page path. Urls are xml-escaped,
+/// and lastmod is written as a date (YYYY-MM-DD).
+/// Returns Error::InvalidBaseUrl if the base url isn't an http or https url.
+pub fn generate_sitemap(base_url: &str, pages: &[SitemapEntry]) -> Result<String> {
+    if !(base_url.starts_with("http://") || base_url.starts_with("https://")) {
+        return Err(Error::InvalidBaseUrl(base_url.to_string()));
+    }
+    let base_url = base_url.trim_end_matches('/');
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"
+    ));
+    for page in pages.iter() {
+        let sep = if page.path.starts_with('/') { "" } else { "/" };
+        let url = format!("{}{}{}", base_url, sep, page.path);
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", escape_html(&url)));
+        if let Some(lastmod) = &page.lastmod {
+            xml.push_str(&format!(
+                "    <lastmod>{}</lastmod>\n",
+                lastmod.format("%Y-%m-%d")
+            ));
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    Ok(xml)
+}
+
+#[test]
+fn test_generate_sitemap() {
+    use chrono::TimeZone;
+
+    let pages = vec![
+        SitemapEntry::new(
+            "/index.html",
+            Some(Utc.with_ymd_and_hms(2024, 3, 5, 10, 30, 0).unwrap()),
+        ),
+        SitemapEntry::new("search.html?q=a&b=<c>", None),
+    ];
+    let xml = generate_sitemap("https://example.com/", &pages).expect("sitemap");
+    assert_eq!(
+        xml,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/index.html</loc>
+    <lastmod>2024-03-05</lastmod>
+  </url>
+  <url>
+    <loc>https://example.com/search.html?q=a&amp;b=&lt;c&gt;</loc>
+  </url>
+</urlset>
+"#
+    );
+
+    assert!(matches!(
+        generate_sitemap("example.com", &pages),
+        Err(Error::InvalidBaseUrl(_))
+    ));
+}
+
+#[test]
+fn test_entries_from_pages() {
+    let fronts = [
+        "title = \"One\"\ndate = \"2024-01-01\"",
+        "title = \"Two\"\ndate = \"last tuesday\"",
+        "title = \"Three\"",
+    ]
+    .iter()
+    .map(|front| toml::from_str::<TomlMap>(front).unwrap())
+    .collect::<Vec<_>>();
+    let pages = fronts
+        .iter()
+        .enumerate()
+        .map(|(n, frontmatter)| SitemapPage {
+            path: PathBuf::from(format!("{}.md", n)),
+            url_path: format!("/{}.html", n),
+            frontmatter,
+        })
+        .collect::<Vec<_>>();
+    let (entries, skipped) = entries_from_pages(&pages);
+    assert_eq!(
+        entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(),
+        vec!["/0.html", "/2.html"]
+    );
+    assert!(entries[0].lastmod.is_some());
+    assert!(entries[1].lastmod.is_none());
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].0, PathBuf::from("1.md"));
+}
+
+#[cfg(feature = "file-scan")]
+#[test]
+fn test_sitemap_from_scan() {
+    use crate::file_scan::{index_sources, ScanOptions};
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::create_dir_all(dir.path().join("guide")).unwrap();
+    std::fs::write(dir.path().join("guide/intro.md"), "# Intro\n").unwrap();
+    let opt = ScanOptions {
+        collect_metadata: true,
+        ..Default::default()
+    };
+    let scan = index_sources(&[dir.path().to_path_buf()], &opt).expect("scan");
+    let entry = SitemapEntry::from_markdown_path(&scan.markdown[0]);
+    assert_eq!(entry.path, "/guide/intro.html");
+    assert!(entry.lastmod.is_some());
+}
@@ -0,0 +1,105 @@
+//! Social media meta tags (Open Graph and Twitter cards)
+//!
+use crate::{md_parser::escape_html, TomlMap};
+
+/// Page information for generating meta tags, for values that aren't in frontmatter
+#[derive(Clone, Debug, Default)]
+pub struct PageContext {
+    /// Canonical url of the page
+    pub url: String,
+    /// Site name, for `og:site_name`
+    pub site_name: Option<String>,
+    /// Page excerpt, used as the description if frontmatter has no `description`
+    pub excerpt: Option<String>,
+    /// Image url used if frontmatter has no `image`
+    pub default_image: Option<String>,
+    /// Twitter account of the site, e.g., "@example", for `twitter:site`
+    pub twitter_site: Option<String>,
+}
+
+/// Generate Open Graph (`<meta property="og:...">`) and Twitter card
+/// (`<meta name="twitter:...">`) tags for a page, from the frontmatter values
+/// `title`, `description`, and `image`. If description is not set, the context's
+/// excerpt is used, and if image is not set, the context's default image.
+/// Tags for missing values are omitted. Values are html-escaped.
+pub fn social_meta(map: &TomlMap, ctx: &PageContext) -> String {
+    let get = |key: &str| map.get(key).and_then(|v| v.as_str()).map(String::from);
+    let title = get("title");
+    let description = get("description").or_else(|| ctx.excerpt.clone());
+    let image = get("image").or_else(|| ctx.default_image.clone());
+
+    let mut tags = Vec::new();
+    let mut add = |attr: &str, name: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            tags.push(format!(
+                "<meta {}=\"{}\" content=\"{}\">",
+                attr,
+                name,
+                escape_html(value)
+            ));
+        }
+    };
+    let url = Some(ctx.url.clone()).filter(|u| !u.is_empty());
+    add("property", "og:type", &Some("website".to_string()));
+    add("property", "og:title", &title);
+    add("property", "og:description", &description);
+    add("property", "og:url", &url);
+    add("property", "og:image", &image);
+    add("property", "og:site_name", &ctx.site_name);
+    let card = if image.is_some() {
+        "summary_large_image"
+    } else {
+        "summary"
+    };
+    add("name", "twitter:card", &Some(card.to_string()));
+    add("name", "twitter:site", &ctx.twitter_site);
+    add("name", "twitter:title", &title);
+    add("name", "twitter:description", &description);
+    add("name", "twitter:image", &image);
+    tags.join("\n")
+}
+
+#[test]
+fn test_social_meta() {
+    let mut map = TomlMap::new();
+    map.insert("title".into(), "Tips & \"Tricks\"".into());
+    map.insert("image".into(), "https://example.com/tips.png".into());
+    let ctx = PageContext {
+        url: "https://example.com/tips.html".into(),
+        excerpt: Some("Some <b>tips</b>".into()),
+        default_image: Some("https://example.com/logo.png".into()),
+        ..Default::default()
+    };
+    let meta = social_meta(&map, &ctx);
+    assert_eq!(
+        meta,
+        [
+            r#"<meta property="og:type" content="website">"#,
+            r#"<meta property="og:title" content="Tips &amp; &quot;Tricks&quot;">"#,
+            r#"<meta property="og:description" content="Some &lt;b&gt;tips&lt;/b&gt;">"#,
+            r#"<meta property="og:url" content="https://example.com/tips.html">"#,
+            r#"<meta property="og:image" content="https://example.com/tips.png">"#,
+            r#"<meta name="twitter:card" content="summary_large_image">"#,
+            r#"<meta name="twitter:title" content="Tips &amp; &quot;Tricks&quot;">"#,
+            r#"<meta name="twitter:description" content="Some &lt;b&gt;tips&lt;/b&gt;">"#,
+            r#"<meta name="twitter:image" content="https://example.com/tips.png">"#,
+        ]
+        .join("\n")
+    );
+
+    // without explicit image, the default is used
+    map.remove("image");
+    map.insert("description".into(), "Explicit".into());
+    let meta = social_meta(&map, &ctx);
+    assert!(meta.contains(r#"<meta property="og:image" content="https://example.com/logo.png">"#));
+    assert!(meta.contains(r#"<meta property="og:description" content="Explicit">"#));
+
+    // without any image, the card is a summary
+    let ctx = PageContext {
+        url: ctx.url,
+        ..Default::default()
+    };
+    let meta = social_meta(&map, &ctx);
+    assert!(!meta.contains("og:image"));
+    assert!(meta.contains(r#"<meta name="twitter:card" content="summary">"#));
+}